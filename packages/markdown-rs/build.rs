@@ -0,0 +1,36 @@
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+fn main() {
+    napi_build::setup();
+
+    // syntect dumps are only readable by the release that wrote them, so the
+    // resolved version names the highlight asset cache.
+    let manifest_dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap());
+    let lockfile = manifest_dir
+        .ancestors()
+        .map(|dir| dir.join("Cargo.lock"))
+        .find(|path| path.is_file());
+    if let Some(lockfile) = &lockfile {
+        println!("cargo:rerun-if-changed={}", lockfile.display());
+    }
+    let version = lockfile
+        .and_then(|path| fs::read_to_string(path).ok())
+        .and_then(|lock| locked_version(&lock, "syntect"))
+        .unwrap_or_else(|| "unknown".to_string());
+    println!("cargo:rustc-env=SYNTECT_VERSION={version}");
+}
+
+// The `version` of the `[[package]]` entry named `name`.
+fn locked_version(lock: &str, name: &str) -> Option<String> {
+    let name_line = format!("name = \"{name}\"");
+    let mut lines = lock.lines().skip_while(|line| line.trim() != name_line);
+    lines.next()?;
+    lines
+        .next()?
+        .trim()
+        .strip_prefix("version = \"")?
+        .strip_suffix('"')
+        .map(str::to_string)
+}
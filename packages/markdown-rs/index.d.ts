@@ -1,20 +1,281 @@
 /* auto-generated by NAPI-RS */
-export declare function parse(input: string): string;
+export interface ParseOptions {
+	positions?: boolean;
+	gfm?: boolean;
+	footnotes?: boolean;
+	math?: boolean;
+	definitionLists?: boolean;
+	superscript?: boolean;
+	subscript?: boolean;
+	frontmatter?: boolean;
+}
+
+export declare function parse(
+	input: string,
+	options?: ParseOptions | undefined | null,
+): string;
+
+export declare function parseAsync(
+	input: string,
+	options?: ParseOptions | undefined | null,
+): Promise<string>;
 
 export declare function render(input: string): string;
 
 export declare function renderGfm(input: string): string;
 
+export interface SanitizePolicy {
+	allowedTags?: Array<string>;
+	tagAttributes?: Record<string, Array<string>>;
+	allowedClasses?: Record<string, Array<string>>;
+	urlSchemes?: Array<string>;
+	linkRel?: string;
+}
+
+export interface UrlPolicy {
+	allowedSchemes?: Array<string>;
+	relativeOnly?: boolean;
+	dataImages?: Array<string>;
+}
+
+export interface TocOptions {
+	minLevel?: number;
+	maxLevel?: number;
+	ordered?: boolean;
+	placeholders?: Array<string>;
+}
+
+export interface SlugOptions {
+	style?: string;
+	separator?: string;
+	lowercase?: boolean;
+	prefix?: string;
+}
+
+export interface ThemePairOptions {
+	light?: string;
+	dark?: string;
+}
+
 export interface RenderOptions {
 	sanitize?: boolean;
 	syntaxHighlight?: boolean;
+	highlightMode?: "inline" | "classes" | "dual";
+	theme?: string;
+	themes?: ThemePairOptions;
+	classPrefix?: string;
+	languageAliases?: Record<string, string>;
+	detectLanguage?: boolean;
 	toc?: boolean;
+	tocOptions?: TocOptions;
 	directives?: boolean;
 	gfm?: boolean;
 	footnotes?: boolean;
+	spoiler?: boolean;
+	smartPunctuation?: boolean;
+	linkify?: boolean;
+	admonitions?: boolean;
+	attributes?: boolean;
+	math?: boolean;
+	definitionLists?: boolean;
+	superscript?: boolean;
+	subscript?: boolean;
+	frontmatter?: boolean;
+	headingIds?: boolean;
+	slug?: SlugOptions;
+	snippets?: boolean;
+	includes?: boolean;
+	files?: Record<string, string>;
+	root?: string;
+	baseDir?: string;
+	sanitizePolicy?: SanitizePolicy;
+	urlPolicy?: UrlPolicy;
+	rawHtml?: "allow" | "escape" | "strip" | "allowlist";
+	rawHtmlTags?: Array<string>;
 }
 
 export declare function renderWithOptions(
 	input: string,
 	options?: RenderOptions | undefined | null,
 ): string;
+
+export declare function renderAsync(
+	input: string,
+	options?: RenderOptions | undefined | null,
+): Promise<RenderedDocument>;
+
+export interface BatchInput {
+	id: string;
+	markdown: string;
+}
+
+export interface BatchResult {
+	id: string;
+	html?: string;
+	error?: string;
+	diagnostics: Array<Diagnostic>;
+}
+
+export declare function renderBatch(
+	inputs: Array<BatchInput>,
+	options?: RenderOptions | undefined | null,
+): Array<BatchResult>;
+
+export interface HeadingInfo {
+	level: number;
+	text: string;
+	slug: string;
+}
+
+export interface TocEntry {
+	level: number;
+	text: string;
+	slug: string;
+	children: Array<TocEntry>;
+}
+
+export interface LinkInfo {
+	url: string;
+	title: string;
+	text: string;
+}
+
+export interface ImageInfo {
+	url: string;
+	title: string;
+	alt: string;
+}
+
+export interface SourcePoint {
+	line: number;
+	column: number;
+	offset: number;
+}
+
+export interface SourceRange {
+	start: SourcePoint;
+	end: SourcePoint;
+}
+
+export interface Diagnostic {
+	severity: string;
+	message: string;
+	code: string;
+	range?: SourceRange;
+	fix?: Fix;
+}
+
+export interface Fix {
+	range: SourceRange;
+	text: string;
+}
+
+export interface RenderedDocument {
+	html: string;
+	frontmatter?: any;
+	headings: Array<HeadingInfo>;
+	toc: Array<TocEntry>;
+	links: Array<LinkInfo>;
+	images: Array<ImageInfo>;
+	wordCount: number;
+	readingTime: number;
+	excerpt?: string;
+	diagnostics: Array<Diagnostic>;
+}
+
+export declare function renderDocument(
+	input: string,
+	options?: RenderOptions | undefined | null,
+): RenderedDocument;
+
+export declare function getToc(
+	input: string,
+	options?: RenderOptions | undefined | null,
+): Array<TocEntry>;
+
+export interface ExpandedMarkdown {
+	markdown: string;
+	diagnostics: Array<Diagnostic>;
+}
+
+export declare function expandIncludes(
+	input: string,
+	options?: RenderOptions | undefined | null,
+): ExpandedMarkdown;
+
+export interface LintConfig {
+	rules?: Record<string, "off" | "warning" | "error">;
+	maxLineLength?: number;
+	headingPunctuation?: string;
+	listMarker?: "-" | "*" | "+" | "consistent";
+}
+
+export interface LintResult {
+	diagnostics: Array<Diagnostic>;
+	fixed: string;
+	errorCount: number;
+	warningCount: number;
+}
+
+export declare function lint(
+	input: string,
+	config?: LintConfig | undefined | null,
+): LintResult;
+
+export declare function getThemeCss(
+	theme: string,
+	prefix?: string | undefined | null,
+): string;
+
+export interface DualThemeCssOptions {
+	classPrefix?: string;
+	darkSelector?: string;
+}
+
+export declare function getDualThemeCss(
+	options?: DualThemeCssOptions | undefined | null,
+): string;
+
+export interface HighlightAssetsOptions {
+	cacheDir?: string;
+}
+
+export interface HighlightAssetsInfo {
+	hash: string;
+	syntaxes: number;
+	themes: number;
+	cached: boolean;
+	cacheError?: string;
+}
+
+export declare function registerHighlightAssets(
+	dir: string,
+	options?: HighlightAssetsOptions | undefined | null,
+): HighlightAssetsInfo;
+
+export declare function resetHighlightAssets(): void;
+
+export declare function renderAst(
+	astJson: string,
+	options?: RenderOptions | undefined | null,
+): string;
+
+export interface FormatOptions {
+	bullet?: string;
+	orderedDelimiter?: string;
+	emphasis?: string;
+	strong?: string;
+	headingStyle?: string;
+	alignTables?: boolean;
+	lineWidth?: number;
+}
+
+export declare function stringify(
+	astJson: string,
+	style?: FormatOptions | undefined | null,
+): string;
+
+export declare function format(
+	input: string,
+	style?: FormatOptions | undefined | null,
+): string;
@@ -1,5 +1,41 @@
 import { createRequire } from "module";
 const require = createRequire(import.meta.url);
-const { parse, renderGfm, renderWithOptions } = require("./markdown-rs.node");
+const {
+	expandIncludes,
+	format,
+	getDualThemeCss,
+	getThemeCss,
+	getToc,
+	lint,
+	parse,
+	parseAsync,
+	registerHighlightAssets,
+	renderAst,
+	renderAsync,
+	renderBatch,
+	renderDocument,
+	renderGfm,
+	renderWithOptions,
+	resetHighlightAssets,
+	stringify,
+} = require("./markdown-rs.node");
 
-export { parse, renderGfm, renderWithOptions };
+export {
+	expandIncludes,
+	format,
+	getDualThemeCss,
+	getThemeCss,
+	getToc,
+	lint,
+	parse,
+	parseAsync,
+	registerHighlightAssets,
+	renderAst,
+	renderAsync,
+	renderBatch,
+	renderDocument,
+	renderGfm,
+	renderWithOptions,
+	resetHighlightAssets,
+	stringify,
+};
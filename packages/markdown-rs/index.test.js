@@ -1,5 +1,26 @@
 import { test, expect } from "bun:test";
-import { renderGfm, renderWithOptions } from "./index.js";
+import { mkdirSync, mkdtempSync, writeFileSync } from "fs";
+import { tmpdir } from "os";
+import { join } from "path";
+import {
+	expandIncludes,
+	format,
+	getDualThemeCss,
+	getThemeCss,
+	getToc,
+	lint,
+	parse,
+	parseAsync,
+	registerHighlightAssets,
+	renderAst,
+	renderAsync,
+	renderBatch,
+	renderDocument,
+	renderGfm,
+	renderWithOptions,
+	resetHighlightAssets,
+	stringify,
+} from "./index.js";
 
 test("renders basic markdown to html", () => {
 	const markdown = "# Hello, World!";
@@ -41,6 +62,105 @@ test("renders Table of Contents (TOC) plugin", () => {
 	expect(html).toContain(">Subtitle</a>");
 });
 
+test("links the table of contents to unique heading ids", () => {
+	const markdown = "[toc]\n\n# What's New?\n\n## Setup\n\n## Setup\n\n## Custom {#custom-id}";
+	const html = renderWithOptions(markdown, { toc: true });
+	expect(html).toContain('<h1 id="whats-new">');
+	expect(html).toContain('<h2 id="setup">');
+	expect(html).toContain('<h2 id="setup-1">');
+	expect(html).toContain('<h2 id="custom-id">');
+	expect(html).toContain('href="#setup-1"');
+	expect(html).toContain('href="#custom-id"');
+});
+
+test("leaves the inline language suffix out of heading ids", () => {
+	const markdown = "[toc]\n\n# Title `x{:rust}`";
+	const html = renderWithOptions(markdown, { toc: true, syntaxHighlight: true, sanitize: false });
+	expect(html).toContain('<li><a href="#title-x">Title x</a>');
+	expect(html).toContain('<h1 id="title-x">');
+	expect(renderWithOptions("# Title `x{:rust}`", { headingIds: true })).toContain('<h1 id="title-x">');
+});
+
+test("limits the table of contents to a level range", () => {
+	const markdown = "[toc]\n\n# Title\n\n## Section\n\n### Detail\n\n#### Fine print";
+	const html = renderWithOptions(markdown, {
+		toc: true,
+		tocOptions: { minLevel: 2, maxLevel: 3 },
+	});
+	expect(html).toContain(
+		'<ul class="toc"><li><a href="#section" rel="noopener noreferrer">Section</a><ul><li><a href="#detail" rel="noopener noreferrer">Detail</a></li></ul></li></ul>',
+	);
+	expect(html).not.toContain("<p>");
+});
+
+test("renders an ordered table of contents at custom placeholders", () => {
+	const markdown = "[[toc]]\n\n# One\n\n# Two\n\n${toc}\n\n[toc]";
+	const html = renderWithOptions(markdown, {
+		toc: true,
+		sanitize: false,
+		tocOptions: { ordered: true, placeholders: ["[[toc]]", "${toc}"] },
+	});
+	const toc = '<ol class="toc"><li><a href="#one">One</a></li><li><a href="#two">Two</a></li></ol>';
+	expect(html.split(toc)).toHaveLength(3);
+	expect(html).toContain("<p>[toc]</p>");
+});
+
+test("returns the table of contents as a tree", () => {
+	const markdown = "# Title\n\n## Setup\n\n### Install\n\n## Setup";
+	expect(getToc(markdown, { tocOptions: { minLevel: 2 } })).toEqual([
+		{
+			level: 2,
+			text: "Setup",
+			slug: "setup",
+			children: [{ level: 3, text: "Install", slug: "install", children: [] }],
+		},
+		{ level: 2, text: "Setup", slug: "setup-1", children: [] },
+	]);
+	expect(renderDocument(markdown, { tocOptions: { maxLevel: 1 } }).toc).toEqual([
+		{ level: 1, text: "Title", slug: "title", children: [] },
+	]);
+});
+
+test("adds heading ids on request", () => {
+	expect(renderWithOptions("# Hello, World!")).toBe("<h1>Hello, World!</h1>\n");
+	expect(renderWithOptions("# Hello, World!", { headingIds: true })).toBe(
+		'<h1 id="hello-world">Hello, World!</h1>\n',
+	);
+	expect(renderWithOptions("# Café & `code`", { headingIds: true })).toContain(
+		'id="café--code"',
+	);
+});
+
+test("keeps heading ids unique when an explicit id is taken", () => {
+	const markdown = "# Intro\n\n## Details {#intro}\n\n## More {#intro}";
+	const { html, headings, diagnostics } = renderDocument(markdown, { headingIds: true });
+	expect(html).toBe(
+		'<h1 id="intro">Intro</h1>\n<h2 id="intro-1">Details</h2>\n<h2 id="intro-2">More</h2>\n',
+	);
+	expect(headings.map((h) => h.slug)).toEqual(["intro", "intro-1", "intro-2"]);
+	expect(diagnostics.map((d) => [d.code, d.message, d.range.start.line])).toEqual([
+		["duplicate-heading-id", "Heading id #intro is already taken, so #intro-1 is used instead", 3],
+		["duplicate-heading-id", "Heading id #intro is already taken, so #intro-2 is used instead", 5],
+	]);
+	expect(renderDocument(markdown).diagnostics).toEqual([]);
+});
+
+test("configures heading slugs", () => {
+	const markdown = "# Hello, World!";
+	expect(
+		renderWithOptions(markdown, {
+			headingIds: true,
+			slug: { style: "simple", prefix: "user-content-" },
+		}),
+	).toContain('id="user-content-hello,-world!"');
+	expect(
+		renderWithOptions(markdown, {
+			headingIds: true,
+			slug: { separator: "_", lowercase: false },
+		}),
+	).toContain('id="Hello_World"');
+});
+
 test("renders footnotes", () => {
 	const markdown = `
 Here is a footnote reference,[^1] and another.[^longnote].
@@ -68,3 +188,1442 @@ Here is a footnote reference,[^1].
 	expect(html).not.toContain('<sup class="footnote-ref">');
 	expect(html).not.toContain('<div class="footnote-definition" id="fn-1">');
 });
+
+test("renders admonitions", () => {
+	const markdown = `
+::: tip Heads up
+Be careful.
+:::
+`;
+	const html = renderWithOptions(markdown, { admonitions: true, sanitize: false });
+	expect(html).toContain('<div class="admonition tip">');
+	expect(html).toContain('<p class="admonition-title">Heads up</p>');
+	expect(html).toContain("<p>Be careful.</p>\n</div>");
+	expect(html).not.toContain(":::");
+});
+
+test("closes admonitions folded into the last item of a list", () => {
+	for (const list of ["- a\n- b\n:::", "- a\n\n- b\n:::", "- a\n  - b\n:::"]) {
+		const markdown = `::: tip\n${list}\n\nafter`;
+		const html = renderWithOptions(markdown, { admonitions: true, sanitize: false });
+		expect(html).toContain("</ul>\n</div>\n<p>after</p>");
+		expect(html).not.toContain(":::");
+		expect(renderDocument(markdown, { admonitions: true }).diagnostics).toEqual([]);
+	}
+	expect(renderWithOptions("::: tip\n- :::\n:::", { admonitions: true, sanitize: false })).toBe(
+		'<div class="admonition tip">\n<ul>\n<li>:::</li>\n</ul>\n</div>\n',
+	);
+});
+
+test("leaves admonitions as text when disabled", () => {
+	const html = renderWithOptions("::: tip\nBe careful.\n:::", { sanitize: false });
+	expect(html).toContain("::: tip");
+	expect(html).not.toContain("admonition");
+});
+
+test("renders directives", () => {
+	const markdown = "::youtube[dQw4w9WgXcQ]\n\n::note[Remember this]\n\n::unknown[x]";
+	const html = renderWithOptions(markdown, { directives: true, sanitize: false });
+	expect(html).toContain('src="https://www.youtube.com/embed/dQw4w9WgXcQ"');
+	expect(html).toContain('<div class="note">Remember this</div>');
+	expect(html).toContain("<p>::unknown[x]</p>");
+});
+
+test("renders paragraph attributes", () => {
+	const html = renderWithOptions("Lead paragraph {#intro .lead}", {
+		attributes: true,
+		sanitize: false,
+	});
+	expect(html).toContain('<p id="intro" class="lead">Lead paragraph</p>');
+});
+
+test("keeps paragraph ids apart from heading ids", () => {
+	const markdown = "# Intro\n\nLead {#intro}\n\nMore {#intro}\n\n## Lead {#lead}";
+	const { html, headings, diagnostics } = renderDocument(markdown, { attributes: true, headingIds: true });
+	expect(html).toBe(
+		'<h1 id="intro">Intro</h1>\n<p id="intro-1">Lead</p>\n<p id="intro-2">More</p>\n<h2 id="lead">Lead</h2>\n',
+	);
+	expect(headings.map((h) => h.slug)).toEqual(["intro", "lead"]);
+	expect(diagnostics.map((d) => [d.code, d.message, d.range.start.line])).toEqual([
+		["duplicate-paragraph-id", "Paragraph id #intro is already taken, so #intro-1 is used instead", 3],
+		["duplicate-paragraph-id", "Paragraph id #intro is already taken, so #intro-2 is used instead", 5],
+	]);
+	expect(renderWithOptions("Lead {#intro}\n\n# Intro", { attributes: true, toc: true })).toContain(
+		'<h1 id="intro-1">Intro</h1>',
+	);
+});
+
+test("renders math", () => {
+	const html = renderWithOptions("Euler: $e^{i\\pi} + 1 = 0$", {
+		math: true,
+		sanitize: false,
+	});
+	expect(html).toContain('<span class="math-inline">e^{i\\pi} + 1 = 0</span>');
+});
+
+test("renders spoilers", () => {
+	const markdown = "<spoiler>\n\nThe butler did it.\n\n</spoiler>";
+	const html = renderWithOptions(markdown, { spoiler: true, sanitize: false });
+	expect(html).toContain('<details class="spoiler"><summary>Spoiler</summary>');
+	expect(html).toContain("<p>The butler did it.</p>\n</details>");
+});
+
+test("linkifies bare urls", () => {
+	const markdown = "Visit https://example.com/docs. Or `https://example.org`.";
+	const html = renderWithOptions(markdown, { linkify: true, sanitize: false });
+	expect(html).toContain(
+		'<a href="https://example.com/docs">https://example.com/docs</a>.',
+	);
+	expect(html).toContain("<code>https://example.org</code>");
+});
+
+test("leaves urls inside raw anchors alone when linkifying", () => {
+	const markdown = 'See <a href="https://example.com">https://example.com</a> or https://example.org';
+	expect(renderWithOptions(markdown, { linkify: true, sanitize: false })).toBe(
+		'<p>See <a href="https://example.com">https://example.com</a> or <a href="https://example.org">https://example.org</a></p>\n',
+	);
+});
+
+test("applies smart punctuation", () => {
+	const markdown = `"Quoted" -- it's done... \`"raw"\``;
+	const html = renderWithOptions(markdown, {
+		smartPunctuation: true,
+		sanitize: false,
+	});
+	expect(html).toContain("“Quoted” – it’s done…");
+	expect(html).toContain('<code>"raw"</code>');
+});
+
+test("sanitizes html by default", () => {
+	const markdown = `<script>alert(1)</script>
+
+<b onclick="steal()">bold</b> [link](https://example.com)`;
+	const html = renderWithOptions(markdown);
+	expect(html).not.toContain("<script>");
+	expect(html).not.toContain("onclick");
+	expect(html).toContain(
+		'<a href="https://example.com" rel="noopener noreferrer">link</a>',
+	);
+});
+
+test("skips sanitization when disabled", () => {
+	const html = renderWithOptions('<b onclick="x()">bold</b>', {
+		sanitize: false,
+	});
+	expect(html).toContain('<b onclick="x()">bold</b>');
+});
+
+test("links bracketed text to itself when sanitizing", () => {
+	const markdown = "See [guide] here.\n\n::youtube[dQw4w9WgXcQ]\n\n[toc]\n\n# Title";
+	const html = renderWithOptions(markdown, { toc: true });
+	expect(html).toContain('<a href="guide" rel="noopener noreferrer">guide</a>');
+	expect(html).toContain("::youtube[dQw4w9WgXcQ]");
+	expect(html).toContain('<a href="#title"');
+	expect(renderWithOptions("See [guide] here.", { sanitize: false })).toBe(
+		"<p>See [guide] here.</p>\n",
+	);
+	expect(format("See [guide] here.")).toBe("See [guide] here.\n");
+});
+
+test("sanitizes plugin output", () => {
+	const markdown = "::: tip\nKept\n:::\n\n<div class=\"evil tip\">x</div>";
+	const html = renderWithOptions(markdown, { admonitions: true });
+	expect(html).toContain('<div class="admonition tip">');
+	expect(html).toContain('<div class="tip">x</div>');
+});
+
+test("keeps the output of gfm and plugins when sanitizing by default", () => {
+	const tasks = renderWithOptions("- [x] done\n- [ ] todo");
+	expect(tasks).toContain('<li><input disabled="" checked="" type="checkbox">\ndone</li>');
+	expect(tasks).toContain('<li><input disabled="" type="checkbox">\ntodo</li>');
+
+	const video = renderWithOptions("::youtube[dQw4w9WgXcQ]", { directives: true });
+	expect(video).toContain(
+		'<div style="position: relative; padding-bottom: 56.25%; height: 0; overflow: hidden;"><iframe src="https://www.youtube.com/embed/dQw4w9WgXcQ" style="position: absolute; top: 0; left: 0; width: 100%; height: 100%;" allowfullscreen="" frameborder="0"></iframe></div>',
+	);
+
+	const attributes = renderWithOptions("Lead {#intro .lead}\n\n# Title {#top .big}", { attributes: true });
+	expect(attributes).toContain('<p id="intro" class="lead">Lead</p>');
+	expect(attributes).toContain('<h1 id="top" class="big">Title</h1>');
+});
+
+test("only lets checkbox inputs and youtube embeds through", () => {
+	const html = renderWithOptions(
+		'<input type="text" name="q" onfocus="x()"> <iframe src="https://evil.example/"></iframe> <div style="color: red">x</div>',
+	);
+	expect(html).toContain('<input type="checkbox">');
+	expect(html).not.toContain("<iframe");
+	expect(html).toContain("<div>x</div>");
+	const embeds = renderWithOptions('<iframe src="https://evil.example/"></iframe>', {
+		directives: true,
+		gfm: false,
+	});
+	expect(embeds).toContain("<iframe></iframe>");
+	expect(renderWithOptions('<input type="checkbox">', { gfm: false })).not.toContain("<input");
+});
+
+test("keeps only the ids and classes the markdown sets on paragraphs and headings", () => {
+	expect(renderWithOptions('<p id="a" class="x" onclick="y">hi</p>')).toBe("<p>hi</p>");
+	const html = renderWithOptions(
+		'Lead {#intro .lead}\n\n# Title {.big}\n\n<h2 id="intro" class="lead other">Raw</h2>\n\n<p id="top">x</p>',
+		{ attributes: true, headingIds: true },
+	);
+	expect(html).toContain('<p id="intro" class="lead">Lead</p>');
+	expect(html).toContain('<h1 id="title" class="big">Title</h1>');
+	expect(html).toContain('<h2 id="intro" class="lead">Raw</h2>');
+	expect(html).toContain("<p>x</p>");
+	expect(renderWithOptions("::: tip Heads up\nKept\n:::", { admonitions: true })).toContain(
+		'<p class="admonition-title">',
+	);
+});
+
+test("applies a custom sanitize policy", () => {
+	const markdown =
+		'<mark class="hl other">hit</mark> <kbd>k</kbd> [ftp](ftp://files.example.com)';
+	const html = renderWithOptions(markdown, {
+		sanitizePolicy: {
+			allowedTags: ["p", "a", "mark"],
+			allowedClasses: { mark: ["hl"] },
+			urlSchemes: ["ftp"],
+			linkRel: "nofollow",
+		},
+	});
+	expect(html).toContain('<mark class="hl">hit</mark>');
+	expect(html).not.toContain("<kbd>");
+	expect(html).toContain('<a href="ftp://files.example.com" rel="nofollow">');
+});
+
+test("allows extra attributes through the sanitize policy", () => {
+	const html = renderWithOptions('<a href="/x" target="_blank">x</a>', {
+		sanitizePolicy: { tagAttributes: { a: ["href", "target"] }, linkRel: "" },
+	});
+	expect(html).toContain('<a href="/x" target="_blank">x</a>');
+});
+
+test("drops links and images with disallowed urls even without sanitizing", () => {
+	const markdown = [
+		"[a](javascript:alert(1)) [b](VBScript:msgbox(1)) [c](java&#x09;script:alert(1)) [ok](https://example.com)",
+		"",
+		"![page](data:text/html;base64,PHNjcmlwdD4=) ![dot](data:image/png;base64,iVBORw0KGgo=) ![svg](data:image/svg+xml,<svg/>)",
+		"",
+		"[ref] [docs](./docs.md)",
+		"",
+		"[ref]: javascript:alert(2)",
+	].join("\n");
+	const html = renderWithOptions(markdown, { sanitize: false });
+	expect(html).toBe(
+		'<p>a b c <a href="https://example.com">ok</a></p>\n' +
+			'<p>page <img src="data:image/png;base64,iVBORw0KGgo=" alt="dot" /> svg</p>\n' +
+			'<p>ref <a href="./docs.md">docs</a></p>\n',
+	);
+	expect(renderDocument(markdown, { sanitize: false }).links.map((l) => l.url)).toEqual([
+		"https://example.com",
+		"./docs.md",
+	]);
+});
+
+test("applies a configured url policy", () => {
+	const markdown =
+		"[web](https://example.com) [mail](mailto:a@example.com) [cdn](//cdn.example.com/x) [rel](/docs#intro) ![i](data:image/gif;base64,R0lGOD)";
+	expect(
+		renderWithOptions(markdown, {
+			sanitize: false,
+			urlPolicy: { allowedSchemes: ["https"], dataImages: [] },
+		}),
+	).toBe(
+		'<p><a href="https://example.com">web</a> mail <a href="//cdn.example.com/x">cdn</a> <a href="/docs#intro">rel</a> i</p>\n',
+	);
+	expect(
+		renderWithOptions(markdown, { sanitize: false, urlPolicy: { relativeOnly: true } }),
+	).toBe(
+		'<p>web mail cdn <a href="/docs#intro">rel</a> <img src="data:image/gif;base64,R0lGOD" alt="i" /></p>\n',
+	);
+	const linkified = renderWithOptions("See https://example.com and www.example.org", {
+		linkify: true,
+		sanitize: false,
+		urlPolicy: { relativeOnly: true },
+	});
+	expect(linkified).toBe("<p>See https://example.com and www.example.org</p>\n");
+});
+
+test("treats any leading pair of slashes and backslashes as protocol-relative", () => {
+	// `/\evil.com` and `\/evil.com`; the markdown escapes the backslash of the second.
+	const markdown = "[a](/\\evil.com) [b](\\\\/evil.com) [c](/docs)";
+	expect(renderWithOptions(markdown, { sanitize: false })).toBe(
+		'<p><a href="/%5Cevil.com">a</a> <a href="%5C/evil.com">b</a> <a href="/docs">c</a></p>\n',
+	);
+	expect(
+		renderWithOptions(markdown, { sanitize: false, urlPolicy: { relativeOnly: true } }),
+	).toBe('<p>a b <a href="/docs">c</a></p>\n');
+});
+
+test("escapes or strips raw html while rendering", () => {
+	const markdown = 'Hi <b onclick="x()">there</b>\n\n<div>\n<script>alert(1)</script>\n</div>';
+	expect(renderWithOptions(markdown, { sanitize: false, rawHtml: "escape" })).toBe(
+		'<p>Hi &lt;b onclick="x()"&gt;there&lt;/b&gt;</p>\n' +
+			"<p>&lt;div&gt;\n&lt;script&gt;alert(1)&lt;/script&gt;\n&lt;/div&gt;</p>\n",
+	);
+	expect(renderWithOptions(markdown, { sanitize: false, rawHtml: "strip" })).toBe("<p>Hi there</p>\n");
+	expect(renderWithOptions(markdown, { sanitize: false })).toContain("<script>alert(1)</script>");
+});
+
+test("keeps allowlisted raw html tags with safe attributes", () => {
+	const markdown = [
+		'Press <kbd class="k">Ctrl</kbd> <img src=x onerror=alert(1)> <a href="java&#x73;cript:alert(1)" title="t">a</a> <a href="https://example.com/?a=1&amp;b=2">ok</a>',
+		"",
+		'<div onclick="steal()"',
+		'  title="box">',
+		"<style>body { display: none }</style>",
+		"<mark>hit</mark> 1 < 2",
+		"</div>",
+	].join("\n");
+	expect(renderWithOptions(markdown, { sanitize: false, rawHtmlTags: ["kbd", "a", "div", "mark"] })).toBe(
+		'<p>Press <kbd>Ctrl</kbd>  <a title="t">a</a> <a href="https://example.com/?a=1&amp;b=2">ok</a></p>\n' +
+			'<div title="box">\n\n<mark>hit</mark> 1 &lt; 2\n</div>',
+	);
+	expect(renderWithOptions("<kbd>K</kbd> <span>s</span>", { sanitize: false, rawHtml: "allowlist" })).toBe(
+		"<p><kbd>K</kbd> s</p>\n",
+	);
+});
+
+test("leaves html emitted by plugins alone when escaping raw html", () => {
+	const markdown = "::: tip\nKept <i>i</i>\n:::\n\n<spoiler>\n\nSecret\n\n</spoiler>";
+	const html = renderWithOptions(markdown, { admonitions: true, spoiler: true, rawHtml: "escape" });
+	expect(html).toContain('<div class="admonition tip">');
+	expect(html).toContain("Kept &lt;i&gt;i&lt;/i&gt;");
+	expect(html).toContain('<details class="spoiler">');
+});
+
+test("omits source positions by default", () => {
+	const ast = JSON.parse(parse("# Title"));
+	expect(ast.position).toBeUndefined();
+	expect(ast.children[0].position).toBeUndefined();
+});
+
+test("adds source positions to every node", () => {
+	const ast = JSON.parse(parse("# Title\n\nSome *text*", { positions: true }));
+	const [heading, paragraph] = ast.children;
+	expect(ast.position.start).toEqual({ line: 1, column: 1, offset: 0 });
+	expect(heading.children[0].position).toEqual({
+		start: { line: 1, column: 3, offset: 2 },
+		end: { line: 1, column: 8, offset: 7 },
+	});
+	const emphasis = paragraph.children[1];
+	expect(emphasis.type).toBe("Emph");
+	expect(emphasis.position).toEqual({
+		start: { line: 3, column: 6, offset: 14 },
+		end: { line: 3, column: 12, offset: 20 },
+	});
+});
+
+test("counts positions in UTF-16 code units", () => {
+	const source = "😀 *a*";
+	const ast = JSON.parse(parse(source, { positions: true }));
+	const emphasis = ast.children[0].children[1];
+	expect(emphasis.position.start.offset).toBe(source.indexOf("*"));
+	expect(emphasis.position.start.column).toBe(source.indexOf("*") + 1);
+});
+
+test("keeps task list markers in the ast", () => {
+	const ast = JSON.parse(parse("- [x] done\n- [ ] todo"));
+	const [done, todo] = ast.children[0].children;
+	expect(done.children[0].type).toEqual({ TaskListMarker: { checked: true } });
+	expect(todo.children[0].type).toEqual({ TaskListMarker: { checked: false } });
+});
+
+test("keeps heading attributes in the ast", () => {
+	const ast = JSON.parse(parse("# Title {#main .wide data-x=1}"));
+	expect(ast.children[0].type).toEqual({
+		Heading: {
+			level: 1,
+			id: "main",
+			classes: ["wide"],
+			attrs: [["data-x", "1"]],
+		},
+	});
+});
+
+test("splits the code block info string into lang and meta", () => {
+	const ast = JSON.parse(parse('```rust title="main.rs" {1,3}\nfn main() {}\n```'));
+	expect(ast.children[0].type).toEqual({
+		CodeBlock: { lang: "rust", meta: 'title="main.rs" {1,3}' },
+	});
+});
+
+test("keeps link types and reference ids in the ast", () => {
+	const ast = JSON.parse(parse("[a][ref] <https://example.com>\n\n[ref]: /url"));
+	const [reference, , autolink] = ast.children[0].children;
+	expect(reference.type.Link.link_type).toBe("Reference");
+	expect(reference.type.Link.id).toBe("ref");
+	expect(autolink.type.Link.link_type).toBe("Autolink");
+});
+
+test("parses optional syntax extensions into the ast", () => {
+	const markdown = "Term\n: Definition\n\n^sup^ ~sub~ $x$";
+	const ast = JSON.parse(
+		parse(markdown, {
+			definitionLists: true,
+			superscript: true,
+			subscript: true,
+			math: true,
+		}),
+	);
+	const [list, paragraph] = ast.children;
+	expect(list.type).toBe("DefinitionList");
+	expect(list.children.map((node) => node.type)).toEqual([
+		"DefinitionListTitle",
+		"DefinitionListDefinition",
+	]);
+	expect(paragraph.children.map((node) => node.type)).toEqual([
+		"Superscript",
+		"Text",
+		"Subscript",
+		"Text",
+		"InlineMath",
+	]);
+});
+
+test("renders definition lists and superscript", () => {
+	const html = renderWithOptions("Term\n: Definition\n\n^2^ squared", {
+		definitionLists: true,
+		superscript: true,
+	});
+	expect(html).toContain("<dt>Term</dt>\n<dd>Definition</dd>");
+	expect(html).toContain("<sup>2</sup> squared");
+});
+
+test("renders a parsed ast like the markdown it came from", () => {
+	const markdown = `# Title
+
+- [x] done
+- todo
+
+| a | b |
+|:--|--:|
+| 1 | 2 |
+
+\`\`\`rust
+fn main() {}
+\`\`\`
+
+Text with [a link](https://example.com "Title") and \`code\`.[^1]
+
+[^1]: A footnote.
+`;
+	const options = { sanitize: false };
+	expect(renderAst(parse(markdown), options)).toBe(
+		renderWithOptions(markdown, options),
+	);
+});
+
+test("renders a modified ast", () => {
+	const ast = JSON.parse(parse("See [docs](/old/path)."));
+	const link = ast.children[0].children[1];
+	link.type.Link.dest_url = "/new/path";
+	ast.children.push({
+		type: "Paragraph",
+		children: [{ type: "Text", content: "Injected" }],
+	});
+	const html = renderAst(JSON.stringify(ast), { sanitize: false });
+	expect(html).toContain('<a href="/new/path">docs</a>');
+	expect(html).toContain("<p>Injected</p>");
+});
+
+test("applies plugins and sanitization when rendering an ast", () => {
+	const ast = JSON.parse(parse("::: tip\nCareful\n:::"));
+	ast.children.push({
+		type: "HtmlBlock",
+		children: [{ type: "Html", content: "<script>alert(1)</script>" }],
+	});
+	const html = renderAst(JSON.stringify(ast), { admonitions: true });
+	expect(html).toContain('<div class="admonition tip">');
+	expect(html).not.toContain("<script>");
+});
+
+test("throws on an invalid ast", () => {
+	expect(() => renderAst('{"type":"Nope"}')).toThrow();
+});
+
+test("extracts yaml frontmatter", () => {
+	const markdown = "---\ntitle: Hello\ntags: [a, b]\ndraft: false\n---\n\n# Body";
+	const { html, frontmatter } = renderDocument(markdown);
+	expect(html).toBe("<h1>Body</h1>\n");
+	expect(frontmatter).toEqual({ title: "Hello", tags: ["a", "b"], draft: false });
+});
+
+test("extracts toml frontmatter", () => {
+	const markdown = '+++\ntitle = "Hello"\ndate = 2024-01-02\n\n[extra]\nweight = 3\nat = [1979-05-27T07:32:00Z]\n+++\n\nBody';
+	const { html, frontmatter } = renderDocument(markdown);
+	expect(html).toBe("<p>Body</p>\n");
+	expect(frontmatter).toEqual({
+		title: "Hello",
+		date: "2024-01-02",
+		extra: { weight: 3, at: ["1979-05-27T07:32:00Z"] },
+	});
+});
+
+test("keeps frontmatter out of rendered html", () => {
+	const markdown = "---\nurl: https://example.com\n---\nBody";
+	expect(renderWithOptions(markdown, { linkify: true, frontmatter: true })).toBe("<p>Body</p>\n");
+	expect(renderWithOptions(markdown)).toContain("<hr");
+	expect(renderDocument(markdown).html).toBe("<p>Body</p>\n");
+	expect(renderDocument(markdown, { frontmatter: false }).html).toContain("<hr");
+	expect(renderDocument("Body").frontmatter).toBeUndefined();
+});
+
+test("keeps frontmatter in the ast and when formatting", () => {
+	const markdown = "---\ntitle: Hello\n---\n\nBody\n";
+	const ast = JSON.parse(parse(markdown));
+	expect(ast.children[0].type).toEqual({ MetadataBlock: { kind: "YamlStyle" } });
+	expect(format(markdown)).toBe(markdown);
+});
+
+test("reads alert markers only when parsing", () => {
+	const markdown = "> [!NOTE]\n> body\n";
+	const ast = JSON.parse(parse(markdown));
+	expect(ast.children[0].type).toEqual({ BlockQuote: { kind: "Note" } });
+	expect(renderWithOptions(markdown, { sanitize: false })).toBe("<blockquote>\n<p>[!NOTE]\nbody</p>\n</blockquote>\n");
+	expect(format(markdown)).toBe(markdown);
+});
+
+test("throws on invalid frontmatter", () => {
+	expect(() => renderDocument("---\ntitle: [\n---\n")).toThrow();
+});
+
+test("returns a structured document", () => {
+	const markdown = [
+		"---\ntitle: Guide\n---",
+		"# Intro {#start}",
+		"Read [the docs](/docs \"Docs\") first.",
+		"## Setup",
+		"![Diagram](/diagram.png)",
+		"```sh\nnot counted\n```",
+		"# Next",
+	].join("\n\n");
+	const doc = renderDocument(markdown);
+	expect(doc.html).toContain('<h1 id="start">Intro</h1>');
+	expect(doc.frontmatter).toEqual({ title: "Guide" });
+	expect(doc.headings).toEqual([
+		{ level: 1, text: "Intro", slug: "start" },
+		{ level: 2, text: "Setup", slug: "setup" },
+		{ level: 1, text: "Next", slug: "next" },
+	]);
+	expect(doc.toc).toEqual([
+		{
+			level: 1,
+			text: "Intro",
+			slug: "start",
+			children: [{ level: 2, text: "Setup", slug: "setup", children: [] }],
+		},
+		{ level: 1, text: "Next", slug: "next", children: [] },
+	]);
+	expect(doc.links).toEqual([{ url: "/docs", title: "Docs", text: "the docs" }]);
+	expect(doc.images).toEqual([{ url: "/diagram.png", title: "", alt: "Diagram" }]);
+	expect(doc.wordCount).toBe(8);
+	expect(doc.readingTime).toBe(1);
+	expect(doc.excerpt).toBe("Read the docs first.");
+});
+
+test("counts highlighted inline code like other inline code", () => {
+	const markdown = "# Using `let x{:rust}`\n\nCall [`run(){:js}`](/run) now.";
+	const plain = renderDocument(markdown.replaceAll(/\{:\w+\}/g, ""));
+	const doc = renderDocument(markdown, { syntaxHighlight: true });
+	expect(doc.html).toContain('<code class="hl-code language-rust">');
+	expect(doc.headings).toEqual(plain.headings);
+	expect(doc.headings[0].text).toBe("Using let x");
+	expect(doc.links).toEqual([{ url: "/run", title: "", text: "run()" }]);
+	expect(doc.wordCount).toBe(plain.wordCount);
+	expect(doc.excerpt).toBe("Call run() now.");
+});
+
+test("collects links added by plugins", () => {
+	const doc = renderDocument("Visit https://example.com today.", { linkify: true });
+	expect(doc.links).toEqual([
+		{ url: "https://example.com", title: "", text: "https://example.com" },
+	]);
+	expect(doc.excerpt).toBe("Visit https://example.com today.");
+});
+
+test("estimates reading time from the word count", () => {
+	const doc = renderDocument("word ".repeat(450));
+	expect(doc.wordCount).toBe(450);
+	expect(doc.readingTime).toBe(3);
+	expect(renderDocument("").readingTime).toBe(0);
+});
+
+test("formats lists, emphasis and headings to a consistent style", () => {
+	const markdown = "Title\n=====\n\n* one\n* two\n\n1) first\n2) second\n\n_em_ and __strong__";
+	expect(format(markdown)).toBe(
+		"# Title\n\n- one\n- two\n\n1. first\n2. second\n\n*em* and **strong**\n",
+	);
+	expect(
+		format(markdown, {
+			bullet: "+",
+			orderedDelimiter: ")",
+			emphasis: "_",
+			strong: "_",
+			headingStyle: "setext",
+		}),
+	).toBe("Title\n=====\n\n+ one\n+ two\n\n1) first\n2) second\n\n_em_ and __strong__\n");
+});
+
+test("aligns table columns", () => {
+	const markdown = "|a|long header|c|\n|:-|:-:|-:|\n|wide cell|x|y|";
+	expect(format(markdown)).toBe(
+		"| a         | long header |   c |\n" +
+			"| :-------- | :---------: | --: |\n" +
+			"| wide cell |      x      |   y |\n",
+	);
+	expect(format(markdown, { alignTables: false })).toBe(
+		"| a | long header | c |\n| :-- | :-: | --: |\n| wide cell | x | y |\n",
+	);
+});
+
+test("wraps paragraphs at the configured width", () => {
+	const markdown = "one two three four five six seven eight nine ten";
+	expect(format(markdown, { lineWidth: 20 })).toBe(
+		"one two three four\nfive six seven eight\nnine ten\n",
+	);
+});
+
+test("keeps control characters and unbreakable spaces when wrapping", () => {
+	expect(format("a\u001eb *c*\n")).toBe("a\u001eb *c*\n");
+	expect(format("x\u001fy z\nw", { lineWidth: 4 })).toBe("x\u001fy\nz w\n");
+	expect(format("see `a b c` and [d](<e f g>)", { lineWidth: 4 })).toBe(
+		"see\n`a b c`\nand\n[d](<e f g>)\n",
+	);
+});
+
+test("keeps admonitions and directives on their own lines", () => {
+	const markdown = "::: warning\nCareful here\n:::\n\n::youtube[dQw4w9WgXcQ]\n\n[toc]";
+	expect(format(markdown, { lineWidth: 80 })).toBe(`${markdown}\n`);
+});
+
+test("escapes text that would otherwise become markdown", () => {
+	const markdown = "\\# not a heading\n\n1\\. not a list\n\nliteral \\*stars\\* and \\`ticks\\`";
+	const formatted = format(markdown);
+	expect(formatted).toBe(`${markdown}\n`);
+	expect(renderGfm(formatted)).not.toContain("<h1>");
+	expect(renderGfm(formatted)).not.toContain("<ol>");
+});
+
+test("formatting is idempotent", () => {
+	const markdown = [
+		"Intro with a [link](https://example.com \"Title\") and `code`.",
+		"> [!NOTE]\n> Quoted *text*",
+		"- [x] done\n- [ ] todo\n  - nested",
+		"```rust title=\"main.rs\"\nfn main() {}\n```",
+		"Term\n: Definition",
+		"Footnote[^1] and ~~gone~~ and $x^2$.",
+		"[^1]: The note.",
+		"See [the docs][docs].",
+		"[docs]: https://example.com/docs",
+	].join("\n\n");
+	const once = format(markdown);
+	expect(format(once)).toBe(once);
+	expect(once).toContain("[the docs][docs]");
+	expect(once).toContain("[docs]: https://example.com/docs");
+	expect(once).toContain('```rust title="main.rs"');
+});
+
+test("formatting is idempotent at narrow widths", () => {
+	const markdowns = [
+		"Text with \\* star and 1. not list",
+		"foo\n\\* bar and \\# baz and 2) qux",
+		"a \\\\ b c\n\\- d \\> e \\+ f",
+		"x ^ y ^^ z\n[a\nb] c",
+		"::youtube[dQw4w9WgXcQ]\n\\* foo bar",
+	];
+	for (const markdown of markdowns) {
+		for (const lineWidth of [1, 4, 8, 12, 20]) {
+			const once = format(markdown, { lineWidth });
+			expect(format(once, { lineWidth })).toBe(once);
+			expect(renderGfm(once)).not.toContain("<li>");
+		}
+	}
+	expect(format("Text with \\* star and 1. not list", { lineWidth: 12 })).toBe(
+		"Text with *\nstar and 1.\nnot list\n",
+	);
+});
+
+test("stringifies a parsed ast", () => {
+	const markdown = "## Heading {#intro}\n\nSome **bold** text.\n";
+	const ast = parse(markdown, { positions: true });
+	expect(stringify(ast)).toBe(markdown);
+	expect(renderGfm(stringify(ast))).toBe(renderGfm(markdown));
+});
+
+test("throws when stringifying an invalid ast", () => {
+	expect(() => stringify("{}")).toThrow();
+});
+
+const rustFence = "```rust\nfn main() {\n    let x = 1;\n}\n```";
+
+test("highlights code with prefixed classes by default", () => {
+	const html = renderWithOptions(rustFence, { sanitize: true, syntaxHighlight: true });
+	expect(html).toContain('<pre class="hl-code"><code class="language-rust">');
+	expect(html).toContain('<span class="hl-source hl-rust">');
+	expect(html.replace(/<[^>]+>/g, "")).toContain("fn main() {\n    let x = 1;\n}\n");
+});
+
+test("uses a custom class prefix", () => {
+	const html = renderWithOptions(rustFence, {
+		sanitize: true,
+		syntaxHighlight: true,
+		classPrefix: "tok-",
+	});
+	expect(html).toContain('<pre class="tok-code">');
+	expect(html).toContain('class="tok-source tok-rust"');
+	expect(html).not.toContain("hl-");
+});
+
+test("highlights code with inline styles from a theme", () => {
+	const dark = renderWithOptions(rustFence, {
+		sanitize: true,
+		syntaxHighlight: true,
+		highlightMode: "inline",
+	});
+	expect(dark).toContain('<pre style="background-color:#2b303b;">');
+	expect(dark).toMatch(/<span style="color:#[0-9a-f]{6};">/);
+
+	const light = renderWithOptions(rustFence, {
+		sanitize: true,
+		syntaxHighlight: true,
+		highlightMode: "inline",
+		theme: "InspiredGitHub",
+	});
+	expect(light).toContain('<pre style="background-color:#ffffff;">');
+	expect(light).not.toBe(dark);
+});
+
+test("strips untrusted styles and classes on code", () => {
+	const html = renderWithOptions(
+		'<span style="position:fixed" class="evil hl-ok">x</span>',
+		{ sanitize: true },
+	);
+	expect(html).not.toContain("position");
+	expect(html).not.toContain("evil");
+	expect(html).toContain('class="hl-ok"');
+});
+
+test("exports theme css for class highlighting", () => {
+	const css = getThemeCss("InspiredGitHub", "hl-");
+	expect(css).toContain(".hl-code");
+	expect(css).toContain(".hl-keyword");
+	expect(getThemeCss("base16-ocean.dark")).toContain(".hl-code");
+	expect(() => getThemeCss("no-such-theme")).toThrow();
+});
+
+test("highlights code against light and dark themes", () => {
+	const html = renderWithOptions(rustFence, {
+		sanitize: true,
+		syntaxHighlight: true,
+		themes: { light: "InspiredGitHub", dark: "base16-ocean.dark" },
+	});
+	expect(html).toContain(
+		'<pre class="hl-dual" style="--shiki-light:#323232;--shiki-light-bg:#ffffff;--shiki-dark:#c0c5ce;--shiki-dark-bg:#2b303b;">',
+	);
+	expect(html).toMatch(
+		/<span style="--shiki-light:#[0-9a-f]{6};(--shiki-light-font[a-z-]+:[a-z]+;)*--shiki-dark:#[0-9a-f]{6};[^"]*">fn<\/span>/,
+	);
+	expect(html.replace(/<[^>]+>/g, "")).toContain("fn main() {\n    let x = 1;\n}\n");
+	expect(html).not.toContain("color:#");
+});
+
+test("selects dual mode explicitly with default themes", () => {
+	const html = renderWithOptions(rustFence, {
+		syntaxHighlight: true,
+		highlightMode: "dual",
+		classPrefix: "code-",
+	});
+	expect(html).toContain('<pre class="code-dual"');
+	expect(html).toContain("--shiki-light-bg:#ffffff;");
+});
+
+test("exports css that switches dual themes", () => {
+	const css = getDualThemeCss();
+	expect(css).toContain(".hl-dual {\n  color: var(--shiki-light);");
+	expect(css).toContain("@media (prefers-color-scheme: dark)");
+	expect(css).toContain("background-color: var(--shiki-dark-bg);");
+
+	const toggled = getDualThemeCss({ classPrefix: "code-", darkSelector: "html.dark" });
+	expect(toggled).toContain("html.dark .code-dual span {");
+	expect(toggled).not.toContain("@media");
+});
+
+test("wraps highlighted lines and reads the fence meta", () => {
+	const markdown = [
+		'```rust title="src/main.rs" {2,4-5} showLineNumbers{10}',
+		"fn main() {",
+		"    let a = 1;",
+		"    let b = 2;",
+		"    let c = 3;",
+		"}",
+		"```",
+	].join("\n");
+	const html = renderWithOptions(markdown, { sanitize: true, syntaxHighlight: true });
+	expect(html).toContain(
+		'<div class="hl-code-block"><div class="hl-code-title">src/main.rs</div><pre class="hl-code hl-line-numbers hl-has-highlighted">',
+	);
+	expect(html).toContain('<code class="language-rust">');
+	expect(html).toContain(
+		'<span class="hl-line" data-line="1"><span class="hl-line-number" aria-hidden="true">10</span>',
+	);
+	expect(html).toContain('<span class="hl-line hl-highlighted" data-line="2">');
+	expect(html).toContain('<span class="hl-line" data-line="3">');
+	expect(html).toContain('<span class="hl-line hl-highlighted" data-line="5">');
+	expect(html.match(/data-line=/g).length).toBe(5);
+	expect(html).toContain('<span class="hl-storage hl-type hl-function hl-rust">fn</span>');
+});
+
+test("clamps oversized notation counts and first line numbers", () => {
+	const focused = renderWithOptions(
+		"```js\nlet a; // [!code focus:18446744073709551615]\nlet b;\n```",
+		{ syntaxHighlight: true },
+	);
+	expect(focused).toContain('<span class="hl-line hl-focused" data-line="1">');
+	expect(focused).toContain('<span class="hl-line hl-focused" data-line="2">');
+
+	const numbered = renderWithOptions("```js showLineNumbers{18446744073709551615}\nlet a;\n```", {
+		syntaxHighlight: true,
+	});
+	expect(numbered).toContain('aria-hidden="true">1</span>');
+});
+
+test("accepts vitepress style fence meta", () => {
+	const html = renderWithOptions("```js{1}:line-numbers=3\nlet a;\nlet b;\n```", {
+		syntaxHighlight: true,
+	});
+	expect(html).toContain('<code class="language-js">');
+	expect(html).toContain('<span class="hl-line hl-highlighted" data-line="1">');
+	expect(html).toContain('aria-hidden="true">4</span>');
+});
+
+test("applies focus and highlight comments", () => {
+	const markdown = [
+		"```ts",
+		"const a = 1; // [!code focus]",
+		"const b = 2; // [!code highlight:2]",
+		"const c = 3;",
+		"```",
+	].join("\n");
+	const html = renderWithOptions(markdown, { sanitize: true, syntaxHighlight: true });
+	expect(html).toContain('<pre class="hl-code hl-has-highlighted hl-has-focused">');
+	expect(html).toContain('<span class="hl-line hl-focused" data-line="1">');
+	expect(html).toContain('<span class="hl-line hl-highlighted" data-line="2">');
+	expect(html).toContain('<span class="hl-line hl-highlighted" data-line="3">');
+	expect(html).not.toContain("[!code");
+});
+
+test("keeps scopes that span lines balanced on each line", () => {
+	const html = renderWithOptions("```rust\n/* one\ntwo */\n```", { syntaxHighlight: true });
+	for (const line of html.match(/<span class="hl-line"[^]*?data-line="\d+">.*\n/g)) {
+		expect(line.match(/<span/g).length).toBe(line.match(/<\/span>/g).length);
+	}
+	expect(html).toContain('data-line="2"><span class="hl-source hl-rust"><span class="hl-comment hl-block hl-rust">two ');
+});
+
+test("highlights diff-<lang> fences as the language with diff lines", () => {
+	const markdown = "```diff-rust\n fn main() {\n-    old();\n+    new();\n }\n```";
+	const html = renderWithOptions(markdown, { sanitize: true, syntaxHighlight: true });
+	expect(html).toContain('<pre class="hl-code hl-has-diff"><code class="language-rust">');
+	expect(html).toContain('<span class="hl-line" data-line="1"><span class="hl-source hl-rust">');
+	expect(html).toContain('<span class="hl-line hl-diff-remove" data-line="2">');
+	expect(html).toContain('<span class="hl-line hl-diff-add" data-line="3">');
+	expect(html).toContain('<span class="hl-storage hl-type hl-function hl-rust">fn</span>');
+	expect(html.replace(/<[^>]+>/g, "")).toBe(
+		"fn main() {\n    old();\n    new();\n}\n\n",
+	);
+});
+
+test("marks the lines of plain diff fences", () => {
+	const html = renderWithOptions("```diff\n--- a.txt\n+++ b.txt\n-x\n+y\n z\n```", {
+		syntaxHighlight: true,
+	});
+	expect(html).toContain('<span class="hl-line" data-line="1">');
+	expect(html).toContain('<span class="hl-line" data-line="2">');
+	expect(html).toContain('<span class="hl-line hl-diff-remove" data-line="3">');
+	expect(html).toContain('<span class="hl-line hl-diff-add" data-line="4">');
+	expect(html).toContain('<span class="hl-markup hl-inserted hl-diff">');
+	expect(html.replace(/<[^>]+>/g, "")).toContain("-x\n+y\n z\n");
+});
+
+test("applies diff comments", () => {
+	const markdown = [
+		"```js",
+		"old(); // [!code --]",
+		"next(); // [!code ++:2]",
+		"last();",
+		"```",
+	].join("\n");
+	const html = renderWithOptions(markdown, { sanitize: true, syntaxHighlight: true });
+	expect(html).toContain('<pre class="hl-code hl-has-diff">');
+	expect(html).toContain('<span class="hl-line hl-diff-remove" data-line="1">');
+	expect(html).toContain('<span class="hl-line hl-diff-add" data-line="2">');
+	expect(html).toContain('<span class="hl-line hl-diff-add" data-line="3">');
+	expect(html).not.toContain("[!code");
+});
+
+const quillSyntax = `%YAML 1.2
+---
+name: Quill
+file_extensions: [quill]
+scope: source.quill
+contexts:
+  main:
+    - match: \\b(summon|banish)\\b
+      scope: keyword.control.quill
+`;
+
+const quillTheme = `<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+	<key>name</key>
+	<string>Quill Night</string>
+	<key>settings</key>
+	<array>
+		<dict>
+			<key>settings</key>
+			<dict>
+				<key>background</key>
+				<string>#101010</string>
+				<key>foreground</key>
+				<string>#eeeeee</string>
+			</dict>
+		</dict>
+		<dict>
+			<key>scope</key>
+			<string>keyword</string>
+			<key>settings</key>
+			<dict>
+				<key>foreground</key>
+				<string>#ff0088</string>
+			</dict>
+		</dict>
+	</array>
+</dict>
+</plist>
+`;
+
+test("registers custom syntaxes and themes with a dump cache", () => {
+	const dir = mkdtempSync(join(tmpdir(), "markdown-rs-assets-"));
+	const cacheDir = join(dir, ".cache");
+	writeFileSync(join(dir, "quill.sublime-syntax"), quillSyntax);
+	writeFileSync(join(dir, "quill-night.tmTheme"), quillTheme);
+	const markdown = "```quill\nsummon dragon\n```";
+
+	try {
+		const first = registerHighlightAssets(dir, { cacheDir });
+		expect(first.cached).toBe(false);
+		expect(first.cacheError).toBeUndefined();
+		expect(first.hash).toMatch(/^[0-9a-f]{16}$/);
+
+		const second = registerHighlightAssets(dir, { cacheDir });
+		expect(second).toEqual({ ...first, cached: true });
+
+		const html = renderWithOptions(markdown, {
+			sanitize: true,
+			syntaxHighlight: true,
+			highlightMode: "inline",
+			theme: "quill-night",
+		});
+		expect(html).toContain('<pre style="background-color:#101010;">');
+		expect(html).toContain('<span style="color:#ff0088;">summon</span>');
+		expect(getThemeCss("quill-night")).toContain(".hl-code");
+		expect(
+			renderWithOptions("```rust\nfn main() {}\n```", { syntaxHighlight: true }),
+		).toContain("hl-rust");
+
+		writeFileSync(join(dir, "quill.sublime-syntax"), quillSyntax.replace("banish", "vanish"));
+		const changed = registerHighlightAssets(dir, { cacheDir });
+		expect(changed.cached).toBe(false);
+		expect(changed.hash).not.toBe(first.hash);
+
+		const blocked = join(dir, "blocked");
+		writeFileSync(blocked, "");
+		const uncached = registerHighlightAssets(dir, { cacheDir: blocked });
+		expect(uncached.cached).toBe(false);
+		expect(uncached.cacheError).toContain(`Cannot write the cache to ${blocked}`);
+		expect(uncached.syntaxes).toBe(changed.syntaxes);
+	} finally {
+		resetHighlightAssets();
+	}
+	expect(renderWithOptions(markdown, { syntaxHighlight: true })).toContain("hl-text hl-plain");
+	expect(() => getThemeCss("quill-night")).toThrow();
+});
+
+test("throws when registering a missing directory", () => {
+	expect(() => registerHighlightAssets(join(tmpdir(), "markdown-rs-missing-dir"))).toThrow();
+});
+
+test("resolves fence languages through aliases", () => {
+	const highlight = (markdown, options) =>
+		renderWithOptions(markdown, { syntaxHighlight: true, ...options });
+	const ts = highlight("```ts\nconst a = 1;\n```");
+	expect(ts).toContain('<code class="language-ts">');
+	expect(ts).toContain('<span class="hl-source hl-js">');
+	expect(highlight("```sh\necho hi\n```")).toContain("hl-source hl-shell");
+	expect(highlight("```jsonc\n{}\n```")).toContain("hl-source hl-json");
+	expect(highlight("```vue\n<template></template>\n```")).toContain("hl-text hl-html");
+
+	const custom = highlight("```mylang\nfn main() {}\n```", {
+		languageAliases: { MyLang: "rust" },
+	});
+	expect(custom).toContain('<code class="language-mylang">');
+	expect(custom).toContain("hl-source hl-rust");
+});
+
+test("detects the language of unlabeled fences", () => {
+	const detect = (code, options) => {
+		const html = renderWithOptions("```\n" + code + "\n```", {
+			syntaxHighlight: true,
+			...options,
+		});
+		return html.match(/<code(?: class="language-([^"]+)")?>/)[1];
+	};
+	expect(detect("#!/usr/bin/env python3\nprint(1)")).toBe("python");
+	expect(detect("#!/bin/sh\nls")).toBe("bash");
+	expect(detect("<?php echo 1;")).toBe("php");
+	expect(detect('{"a": [1, 2]}')).toBe("json");
+	expect(detect("fn main() {\n    let mut x = 1;\n}")).toBe("rust");
+	expect(detect("def greet(name):\n    print(name)")).toBe("python");
+	expect(detect("const add = (a, b) => a + b;\nconsole.log(add(1, 2));")).toBe("javascript");
+	expect(detect("package main\n\nfunc main() {\n\tx := 1\n}")).toBe("go");
+	expect(detect("SELECT id FROM users WHERE id = 1;")).toBe("sql");
+	expect(detect("just some words")).toBeUndefined();
+	expect(detect("fn main() {\n    let mut x = 1;\n}", { detectLanguage: false })).toBeUndefined();
+});
+
+test("highlights inline code with a language suffix", () => {
+	const markdown = "Call `let x = 5;{:rust}` or `plain {:}` code.";
+	const html = renderWithOptions(markdown, { sanitize: true, syntaxHighlight: true });
+	expect(html).toContain('<code class="hl-code language-rust"><span class="hl-source hl-rust">');
+	expect(html).toContain('<span class="hl-storage hl-type hl-rust">let</span>');
+	expect(html).not.toContain("{:rust}");
+	expect(html).toContain("<code>plain {:}</code>");
+
+	const inline = renderWithOptions(markdown, {
+		sanitize: true,
+		syntaxHighlight: true,
+		highlightMode: "inline",
+	});
+	expect(inline).toContain('<code class="language-rust" style="background-color:#2b303b;">');
+	expect(inline).toMatch(/<span style="color:#[0-9a-f]{6};">let<\/span>/);
+
+	const dual = renderWithOptions(markdown, { syntaxHighlight: true, highlightMode: "dual" });
+	expect(dual).toContain('<code class="hl-dual language-rust" style="--shiki-light:');
+});
+
+test("names the inline language without highlighting", () => {
+	expect(renderWithOptions("`let x = 5{:rust}`")).toBe('<p><code class="language-rust">let x = 5</code></p>\n');
+});
+
+test("applies fence meta without highlighting", () => {
+	const markdown = '```rust title="main.rs" {2} showLineNumbers\nfn main() {\n    let x = 1 < 2;\n}\n```';
+	for (const sanitize of [false, true]) {
+		const html = renderWithOptions(markdown, { sanitize });
+		expect(html).toContain('<div class="hl-code-title">main.rs</div>');
+		expect(html).toContain('<code class="language-rust">');
+		expect(html).toContain('<span class="hl-line hl-highlighted" data-line="2">');
+		expect(html).toContain("let x = 1 &lt; 2;");
+		expect(html).toContain('<span class="hl-line-number" aria-hidden="true">1</span>');
+	}
+	expect(renderWithOptions("```rust\nfn main() {}\n```")).toBe(
+		'<pre><code class="language-rust">fn main() {}\n</code></pre>\n',
+	);
+});
+
+const snippetRoot = mkdtempSync(join(tmpdir(), "markdown-rs-snippets-"));
+mkdirSync(join(snippetRoot, "snippets"));
+mkdirSync(join(snippetRoot, "docs"));
+writeFileSync(
+	join(snippetRoot, "snippets", "example.rs"),
+	[
+		"use std::io;",
+		"",
+		"// #region main",
+		"fn main() {",
+		"    // #region inner",
+		"    let x = 1;",
+		"    // #endregion inner",
+		'    println!("{}", x);',
+		"}",
+		"// #endregion main",
+		"",
+	].join("\n"),
+);
+
+test("imports snippets from files under the root", () => {
+	const html = renderWithOptions("<<< @/snippets/example.rs\n\nText", {
+		snippets: true,
+		root: snippetRoot,
+	});
+	expect(html).toMatch(/^<pre><code class="language-rs">use std::io;\n/);
+	expect(html).toContain("// #endregion main\n</code></pre>\n<p>Text</p>");
+});
+
+test("cuts regions, highlights lines and sets the title of snippets", () => {
+	const html = renderWithOptions("<<< @/snippets/example.rs#main{2-3 rust} [main.rs]", {
+		snippets: true,
+		root: snippetRoot,
+		syntaxHighlight: true,
+	});
+	expect(html).toContain('<div class="hl-code-title">main.rs</div>');
+	expect(html).toContain('<code class="language-rust">');
+	expect(html).toContain('<span class="hl-line hl-highlighted" data-line="2">');
+	expect(html).toContain('<span class="hl-line hl-highlighted" data-line="3">');
+	expect(html.match(/data-line=/g).length).toBe(4);
+	expect(html).not.toContain("#region");
+	expect(html.replace(/<[^>]+>/g, "")).toContain("main.rsfn main() {\n    let x = 1;\n");
+});
+
+test("resolves relative snippet paths against the base directory", () => {
+	const html = renderWithOptions("<<< ../snippets/example.rs#inner", {
+		snippets: true,
+		root: snippetRoot,
+		baseDir: join(snippetRoot, "docs"),
+	});
+	expect(html).toBe('<pre><code class="language-rs">let x = 1;\n</code></pre>\n');
+});
+
+test("only takes commented lines for region markers", () => {
+	writeFileSync(
+		join(snippetRoot, "snippets", "counter.py"),
+		[
+			"# region count",
+			"region = 1",
+			"endregion_count = 0",
+			"#endregion count",
+			"<!-- #region page -->",
+			"<p>page</p>",
+			"<!-- #endregion page -->",
+			"",
+		].join("\n"),
+	);
+	const render = (markdown) => renderWithOptions(markdown, { snippets: true, root: snippetRoot });
+	expect(render("<<< @/snippets/counter.py#count")).toBe(
+		'<pre><code class="language-py">region = 1\nendregion_count = 0\n</code></pre>\n',
+	);
+	expect(render("<<< @/snippets/counter.py#page")).toBe(
+		'<pre><code class="language-py">&lt;p&gt;page&lt;/p&gt;\n</code></pre>\n',
+	);
+});
+
+test("reports snippets that cannot be imported", () => {
+	const { html, diagnostics } = renderDocument(
+		"<<< @/snippets/missing.rs\n\n<<< @/../outside.rs\n\n<<< @/snippets/example.rs#nope\n<<< @/snippets/example.rs",
+		{ snippets: true, root: snippetRoot },
+	);
+	expect(html).toContain(
+		"<p>&lt;&lt;&lt; @/snippets/missing.rs</p>\n<p>&lt;&lt;&lt; @/../outside.rs</p>\n<p>&lt;&lt;&lt; @/snippets/example.rs#nope</p>\n<pre>",
+	);
+	expect(diagnostics.map((d) => [d.severity, d.code, d.range.start.line])).toEqual([
+		["error", "snippet-not-found", 1],
+		["error", "snippet-outside-root", 3],
+		["error", "snippet-region-not-found", 5],
+	]);
+	expect(diagnostics[0].message).toContain("Cannot read @/snippets/missing.rs: ");
+	expect(diagnostics[1].message).toBe("Cannot read @/../outside.rs: outside of the root directory");
+	expect(diagnostics[2].message).toBe("Region nope not found in @/snippets/example.rs");
+	expect(diagnostics[2].range.end.line).toBe(5);
+	expect(renderWithOptions("<<< @/snippets/example.rs")).toBe(
+		"<p>&lt;&lt;&lt; @/snippets/example.rs</p>\n",
+	);
+});
+
+const includeFiles = {
+	"shared/install.md": [
+		"---",
+		"title: Install",
+		"---",
+		"# Install",
+		"",
+		"Line 3",
+		"Line 4",
+		"",
+		"## Requirements",
+		"",
+		"<!--@include: ./requirements.md-->",
+		"",
+		"## Usage",
+		"",
+		"Run it.",
+		"",
+	].join("\n"),
+	"shared/requirements.md": "- Node 18\n- Rust 1.80\n",
+	"loop/a.md": "A\n\n<!--@include: ./b.md-->\n",
+	"loop/b.md": "B\n\n<!--@include: ./a.md-->\n",
+};
+
+test("includes markdown files before parsing", () => {
+	const html = renderWithOptions("<!--@include: ./shared/install.md{2,4}-->\n\nAfter", {
+		includes: true,
+		files: includeFiles,
+	});
+	expect(html).toBe("<p>Line 3\nLine 4</p>\n<p>After</p>\n");
+	expect(
+		renderWithOptions("<!--@include: ./shared/install.md-->", {
+			files: includeFiles,
+			sanitize: false,
+		}),
+	).toBe("<!--@include: ./shared/install.md-->");
+});
+
+test("includes the section under a heading, expanding nested includes", () => {
+	const { markdown, diagnostics } = expandIncludes(
+		"<!--@include: @/shared/install.md#requirements-->",
+		{ files: includeFiles },
+	);
+	expect(markdown).toBe("## Requirements\n\n- Node 18\n- Rust 1.80");
+	expect(diagnostics).toEqual([]);
+	const toc = getToc("# Guide\n\n<!--@include: ./shared/install.md-->", {
+		includes: true,
+		files: includeFiles,
+	});
+	expect(toc.map((entry) => entry.text)).toEqual(["Guide", "Install"]);
+	expect(toc[1].children.map((entry) => entry.text)).toEqual(["Requirements", "Usage"]);
+});
+
+test("reports include cycles and missing files as diagnostics", () => {
+	const document = renderDocument(
+		"# Doc\n\n<!--@include: ./loop/a.md-->\n\n<!--@include: ./missing.md-->\n\n<!--@include: ./shared/install.md#nope-->\n\n<!--@include: ../up.md-->",
+		{ includes: true, files: includeFiles },
+	);
+	expect(document.html).toContain("<p>A</p>\n<p>B</p>");
+	expect(
+		document.diagnostics.map((d) => [d.severity, d.code, d.message, d.range.start.line]),
+	).toEqual([
+		["error", "include-cycle", "Include cycle: loop/a.md -> loop/b.md -> loop/a.md", 3],
+		["error", "include-not-found", "Cannot read missing.md: no such file", 5],
+		["error", "include-section-not-found", "No heading #nope in shared/install.md", 7],
+		["error", "include-outside-root", "Cannot read ../up.md: outside of the root directory", 9],
+	]);
+	expect(document.diagnostics[1].range).toEqual({
+		start: { line: 5, column: 1, offset: 37 },
+		end: { line: 5, column: 30, offset: 66 },
+	});
+});
+
+test("finds include sections by the configured heading ids", () => {
+	const include = "<!--@include: ./shared/install.md#Requirements-->";
+	const custom = expandIncludes(include, { files: includeFiles, slug: { lowercase: false } });
+	expect(custom.markdown).toBe("## Requirements\n\n- Node 18\n- Rust 1.80");
+	expect(custom.diagnostics).toEqual([]);
+	const plain = expandIncludes(include, { files: includeFiles });
+	expect(plain.diagnostics.map((d) => d.code)).toEqual(["include-section-not-found"]);
+});
+
+test("stops includes that would grow the document without bound", () => {
+	const files = { "bomb/20.md": "word ".repeat(20000) };
+	for (let level = 0; level < 20; level++) {
+		files[`bomb/${level}.md`] = `<!--@include: ./${level + 1}.md-->\n<!--@include: ./${level + 1}.md-->\n`;
+	}
+	const { markdown, diagnostics } = expandIncludes("<!--@include: ./bomb/0.md-->", { files });
+	expect(markdown.length).toBeLessThan(9 * 1024 * 1024);
+	expect(diagnostics.length).toBeGreaterThan(0);
+	expect(diagnostics[0].code).toBe("include-too-large");
+	expect(diagnostics[0].message).toBe(
+		"Including bomb/20.md would add more than 8388608 bytes to the document",
+	);
+});
+
+test("leaves includes in code blocks alone and reads from the root", () => {
+	writeFileSync(join(snippetRoot, "docs", "part.md"), "Included *text*\n");
+	const markdown = "```md\n# Example\n<!--@include: ./part.md-->\n```\n\n<!--@include: ./part.md-->";
+	const html = renderWithOptions(markdown, {
+		includes: true,
+		root: snippetRoot,
+		baseDir: join(snippetRoot, "docs"),
+	});
+	expect(html).toContain("&lt;!--@include: ./part.md--&gt;");
+	expect(html).toContain("<p>Included <em>text</em></p>");
+});
+
+test("renders batches in input order", () => {
+	const inputs = Array.from({ length: 50 }, (_, i) => ({
+		id: `page-${i}`,
+		markdown: `# Page ${i}\n\n${"text ".repeat(i)}`,
+	}));
+	const results = renderBatch(inputs, { headingIds: true });
+	expect(results.map((result) => result.id)).toEqual(inputs.map((input) => input.id));
+	expect(results[7].html).toBe(renderWithOptions(inputs[7].markdown, { headingIds: true }));
+	expect(results[7].error).toBeUndefined();
+	expect(renderBatch([])).toEqual([]);
+});
+
+test("reports the diagnostics of each document in a batch", () => {
+	const results = renderBatch(
+		[
+			{ id: "ok", markdown: "<!--@include: ./shared/requirements.md-->" },
+			{ id: "missing", markdown: "<!--@include: ./missing.md-->" },
+		],
+		{ includes: true, files: includeFiles },
+	);
+	expect(results[0].html).toBe("<ul>\n<li>Node 18</li>\n<li>Rust 1.80</li>\n</ul>\n");
+	expect(results[0].diagnostics).toEqual([]);
+	expect(results[1].html).toBe("");
+	expect(results[1].diagnostics.map((diagnostic) => diagnostic.code)).toEqual([
+		"include-not-found",
+	]);
+});
+
+test("renders and parses asynchronously with the same output", async () => {
+	const markdown = "# Title\n\n```rust\nfn main() {}\n```\n\nSome *text*.";
+	const options = { syntaxHighlight: true, headingIds: true };
+	const pending = renderAsync(markdown, options);
+	expect(pending).toBeInstanceOf(Promise);
+	expect(await pending).toEqual(renderDocument(markdown, options));
+	expect((await renderAsync(markdown)).html).toBe(renderWithOptions(markdown));
+	const { diagnostics } = await renderAsync("<!--@include: ./missing.md-->", {
+		includes: true,
+		files: includeFiles,
+	});
+	expect(diagnostics.map((d) => d.code)).toEqual(["include-not-found"]);
+	expect(await parseAsync(markdown, { positions: true })).toBe(
+		parse(markdown, { positions: true }),
+	);
+});
+
+test("reports containers left open and directives left as text", () => {
+	const document = renderDocument(
+		"::youtube[not a valid id!]\n\n::tweet[123]\n\n::: warning Careful\nText\n\n::: tip\nInside\n:::",
+		{ admonitions: true, directives: true },
+	);
+	expect(document.html).toMatch(/<\/div>\n<\/div>\n$/);
+	expect(document.diagnostics).toEqual([
+		{
+			severity: "warning",
+			message: "Invalid content for ::youtube: not a valid id!",
+			code: "invalid-directive",
+			range: {
+				start: { line: 1, column: 1, offset: 0 },
+				end: { line: 1, column: 27, offset: 26 },
+			},
+		},
+		{
+			severity: "warning",
+			message: "Unknown directive ::tweet",
+			code: "unknown-directive",
+			range: {
+				start: { line: 3, column: 1, offset: 28 },
+				end: { line: 3, column: 13, offset: 40 },
+			},
+		},
+		{
+			severity: "error",
+			message: "`::: warning` is never closed, so it contains the rest of the document",
+			code: "unclosed-container",
+			range: {
+				start: { line: 5, column: 1, offset: 42 },
+				end: { line: 5, column: 20, offset: 61 },
+			},
+		},
+	]);
+	expect(renderDocument("::tweet[123]").diagnostics).toEqual([]);
+});
+
+test("reports broken references and invalid attribute blocks", () => {
+	const markdown = "See [the docs][docs] and [guide][].\n\nA [shortcut] and [x][ok].\n\nStyled {#intro data-x}\n\n[ok]: /ok";
+	const { diagnostics } = renderDocument(markdown, { attributes: true });
+	expect(diagnostics.map((d) => [d.severity, d.code, d.message, d.range.start.column])).toEqual([
+		["warning", "broken-reference", "No definition for reference [docs]", 5],
+		["warning", "broken-reference", "No definition for reference [guide]", 26],
+		["warning", "invalid-attributes", "Invalid attribute `data-x`, expected `#id` or `.class`", 1],
+	]);
+	const [result] = renderBatch([{ id: "a", markdown: "[x][missing]" }]);
+	expect(result.diagnostics.map((d) => d.code)).toEqual(["broken-reference"]);
+});
+
+test("lints headings, links, images and lists", () => {
+	const markdown = [
+		"# Guide",
+		"",
+		"### Setup.",
+		"",
+		"## Guide",
+		"",
+		"See https://example.com/docs, or [the API]() and [](https://example.com).",
+		"",
+		"![](diagram.png)",
+		"",
+		"- one",
+		"* two",
+	].join("\n");
+	const result = lint(markdown);
+	expect(result.diagnostics.map((d) => [d.code, d.message, d.range.start.line])).toEqual([
+		["heading-increment", "Heading level 3 follows level 1; expected 2", 3],
+		["heading-punctuation", 'Heading ends with "."', 3],
+		["no-duplicate-headings", 'Duplicate heading "Guide", first used on line 1', 5],
+		["no-bare-urls", "Bare URL https://example.com/docs", 7],
+		["no-empty-links", "Link has no destination", 7],
+		["no-empty-links", "Link to https://example.com has no text", 7],
+		["image-alt-text", "Image diagram.png has no alt text", 9],
+		["list-marker-style", "List marker * should be -", 12],
+	]);
+	expect(result.warningCount).toBe(8);
+	expect(result.errorCount).toBe(0);
+});
+
+test("applies lint fixes", () => {
+	const markdown = "# Guide\n\n### Setup.\n\nSee https://example.com.\n\n* one\n- two\n";
+	const result = lint(markdown, { listMarker: "-" });
+	expect(result.fixed).toBe("# Guide\n\n## Setup\n\nSee <https://example.com>.\n\n- one\n- two\n");
+	const fix = result.diagnostics.find((d) => d.code === "no-bare-urls").fix;
+	expect(fix).toEqual({
+		range: {
+			start: { line: 5, column: 5, offset: 25 },
+			end: { line: 5, column: 24, offset: 44 },
+		},
+		text: "<https://example.com>",
+	});
+	expect(lint(result.fixed, { listMarker: "-" }).diagnostics).toEqual([]);
+});
+
+test("configures lint rule severity and options", () => {
+	const long = "word ".repeat(20).trim();
+	const markdown = `# Intro!\n\n${long}\n\n![](a.png)`;
+	const result = lint(markdown, {
+		rules: { "image-alt-text": "error", "heading-punctuation": "off" },
+		maxLineLength: 60,
+	});
+	expect(result.diagnostics.map((d) => [d.severity, d.code])).toEqual([
+		["warning", "line-length"],
+		["error", "image-alt-text"],
+	]);
+	expect(result.errorCount).toBe(1);
+	expect(result.warningCount).toBe(1);
+	expect(lint(`Short\n\n\`\`\`\n${long}\n\`\`\`\n\n${"x".repeat(100)}`).diagnostics).toEqual([]);
+	expect(lint("# Intro?", { headingPunctuation: "?" }).diagnostics.map((d) => d.code)).toEqual([
+		"heading-punctuation",
+	]);
+});
+
+test("leaves line length off and code and frontmatter alone by default", () => {
+	const markdown = `---\ncover: https://example.com/cover.png\n---\n\n${"word ".repeat(30)}\n\n\`\`\`html\n<img src="https://example.com/a.png">\n\`\`\``;
+	expect(lint(markdown).diagnostics).toEqual([]);
+	expect(lint(markdown, { rules: { "line-length": "error" } }).diagnostics.map((d) => d.code)).toEqual([
+		"line-length",
+	]);
+});
+
+test("honours lint-disable comments", () => {
+	const markdown = [
+		"<!-- lint-disable-next-line no-bare-urls -->",
+		"Quiet https://a.example",
+		"",
+		"Loud https://b.example",
+		"",
+		"<!-- lint-disable -->",
+		"",
+		"![](c.png) https://c.example",
+		"",
+		"<!-- lint-enable image-alt-text -->",
+		"",
+		"![](d.png) https://d.example",
+	].join("\n");
+	expect(lint(markdown).diagnostics.map((d) => [d.code, d.range.start.line])).toEqual([
+		["no-bare-urls", 4],
+		["image-alt-text", 12],
+	]);
+});
@@ -0,0 +1,51 @@
+// Lints the markdown files given as arguments and exits non-zero on any error,
+// or on more warnings than `--max-warnings N`. `--fix` writes the fixes back.
+// Rules are configured by `--config file.json`, or by `markdown-lint.json` in
+// the working directory if there is one; see `LintConfig` in index.d.ts.
+// Used by the pre-commit hook.
+import { existsSync, readFileSync, writeFileSync } from "fs";
+import pc from "picocolors";
+
+const args = process.argv.slice(2);
+const option = (name) => {
+	const index = args.indexOf(name);
+	if (index === -1) return undefined;
+	const [, value] = args.splice(index, 2);
+	return value;
+};
+const maxWarnings = Number(option("--max-warnings") ?? Infinity);
+const configPath =
+	option("--config") ?? (existsSync("markdown-lint.json") ? "markdown-lint.json" : undefined);
+const fix = args.includes("--fix");
+const files = args.filter((arg) => arg !== "--fix");
+
+// The native module only exists once the package has been built.
+if (!existsSync(new URL("../markdown-rs.node", import.meta.url))) {
+	console.warn(
+		pc.yellow(
+			"Skipping markdown lint: markdown-rs is not built. Run `bun run build` in packages/markdown-rs to enable it.",
+		),
+	);
+	process.exit(0);
+}
+const { lint } = await import("../index.js");
+const config = configPath ? JSON.parse(readFileSync(configPath, "utf-8")) : undefined;
+
+let errors = 0;
+let warnings = 0;
+for (const file of files) {
+	const source = readFileSync(file, "utf-8");
+	const result = lint(source, config);
+	if (fix && result.fixed !== source) {
+		writeFileSync(file, result.fixed);
+	}
+	for (const { severity, message, code, range } of result.diagnostics) {
+		const at = range ? `${range.start.line}:${range.start.column}` : "";
+		const label = severity === "error" ? pc.red(severity) : pc.yellow(severity);
+		console.log(`${file}:${at} ${label} ${message} ${pc.dim(code)}`);
+	}
+	errors += result.errorCount;
+	warnings += result.warningCount;
+}
+
+process.exit(errors > 0 || warnings > maxWarnings ? 1 : 0);
@@ -1,7 +1,19 @@
 use crate::config::RenderFlags;
-use pulldown_cmark::{Event, Options, Parser};
+use pulldown_cmark::{BrokenLink, BrokenLinkCallback, CowStr, Event, LinkType, Options, Parser};
+use std::cell::RefCell;
+use std::ops::Range;
+use std::rc::Rc;
 
-fn get_parser_options(flags: RenderFlags) -> Options {
+/// The full (`[text][ref]`) and collapsed (`[ref][]`) references without a
+/// definition that a parser came across, with their source range. Shortcut
+/// references (`[ref]`) are left out, since brackets in prose are rarely
+/// meant as links.
+pub type BrokenReferences = Rc<RefCell<Vec<(Range<usize>, String)>>>;
+
+/// Events with the source byte range of each.
+pub type OffsetEvents<'a> = Box<dyn Iterator<Item = (Event<'a>, Range<usize>)> + 'a>;
+
+fn get_parser_options(flags: &RenderFlags) -> Options {
     let mut options = Options::empty();
     options.insert(Options::ENABLE_HEADING_ATTRIBUTES);
 
@@ -11,27 +23,99 @@ fn get_parser_options(flags: RenderFlags) -> Options {
         options.insert(Options::ENABLE_TASKLISTS);
     }
 
+    if flags.gfm && flags.gfm_alerts {
+        options.insert(Options::ENABLE_GFM);
+    }
+
     if flags.footnotes {
         options.insert(Options::ENABLE_FOOTNOTES);
     }
 
+    if flags.math {
+        options.insert(Options::ENABLE_MATH);
+    }
+
+    if flags.definition_lists {
+        options.insert(Options::ENABLE_DEFINITION_LIST);
+    }
+
+    if flags.superscript {
+        options.insert(Options::ENABLE_SUPERSCRIPT);
+    }
+
+    if flags.subscript {
+        options.insert(Options::ENABLE_SUBSCRIPT);
+    }
+
+    if flags.frontmatter {
+        options.insert(Options::ENABLE_YAML_STYLE_METADATA_BLOCKS);
+        options.insert(Options::ENABLE_PLUSES_DELIMITED_METADATA_BLOCKS);
+    }
+
     options
 }
 
 
-pub fn create_parser<'a>(input: &'a str, flags: RenderFlags) -> Box<dyn Iterator<Item = Event<'a>> + 'a> {
+// With sanitizing on, a shortcut reference without a definition links to its
+// own text, as it always has. Brackets that belong to plugin syntax are left
+// alone: directives (`::youtube[id]`), snippet imports (`<<< path [title]`)
+// and table of contents placeholders.
+fn broken_link_fallback<'a>(
+    input: &str,
+    placeholders: &[String],
+    link: BrokenLink<'a>,
+) -> Option<(CowStr<'a>, CowStr<'a>)> {
+    if link.reference.starts_with("javascript:") {
+        return Some(("".into(), "".into()));
+    }
+    let line_start = input[..link.span.start].rfind('\n').map_or(0, |i| i + 1);
+    let line_end = input[link.span.end..].find('\n').map_or(input.len(), |i| link.span.end + i);
+    let line = input[line_start..line_end].trim();
+    if line.starts_with("::") || line.starts_with("<<<") || placeholders.iter().any(|p| p == line) {
+        return None;
+    }
+    Some((link.reference, "".into()))
+}
+
+fn new_parser<'a>(
+    input: &'a str,
+    flags: &RenderFlags,
+    broken: Option<BrokenReferences>,
+) -> Parser<'a, impl BrokenLinkCallback<'a>> {
     let options = get_parser_options(flags);
-    let callback = |link: pulldown_cmark::BrokenLink<'a>| {
-        if link.reference.starts_with("javascript:") {
-            Some(("".into(), "".into()))
-        } else {
-            Some((link.reference, "".into()))
+    let placeholders = if flags.toc {
+        flags.toc_options.placeholders.clone()
+    } else {
+        Vec::new()
+    };
+    let sanitize = flags.sanitize;
+    let callback = move |link: BrokenLink<'a>| {
+        if let Some(broken) = &broken {
+            if matches!(link.link_type, LinkType::Reference | LinkType::Collapsed) {
+                broken.borrow_mut().push((link.span.clone(), link.reference.to_string()));
+            }
+        }
+        if !sanitize {
+            return None;
         }
+        broken_link_fallback(input, &placeholders, link)
     };
+    Parser::new_with_broken_link_callback(input, options, Some(callback))
+}
 
-    if flags.sanitize {
-        Box::new(Parser::new_with_broken_link_callback(input, options, Some(callback)))
-    } else {
-        Box::new(Parser::new_with_broken_link_callback(input, options, None::<fn(pulldown_cmark::BrokenLink<'a>) -> Option<(pulldown_cmark::CowStr<'a>, pulldown_cmark::CowStr<'a>)>>))
-    }
+pub fn create_parser<'a>(input: &'a str, flags: &RenderFlags) -> Box<dyn Iterator<Item = Event<'a>> + 'a> {
+    Box::new(new_parser(input, flags, None))
+}
+
+/// Like [`create_parser`], but yields the source byte range of every event.
+pub fn create_offset_parser<'a>(input: &'a str, flags: &RenderFlags) -> OffsetEvents<'a> {
+    Box::new(new_parser(input, flags, None).into_offset_iter())
+}
+
+/// Like [`create_offset_parser`], but also records the references without a
+/// definition as the parser reaches them.
+pub fn create_checked_parser<'a>(input: &'a str, flags: &RenderFlags) -> (OffsetEvents<'a>, BrokenReferences) {
+    let broken = BrokenReferences::default();
+    let parser = new_parser(input, flags, Some(Rc::clone(&broken)));
+    (Box::new(parser.into_offset_iter()), broken)
 }
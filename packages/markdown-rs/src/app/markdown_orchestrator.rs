@@ -1,9 +1,22 @@
-use crate::components::{ast::build_ast, render::render_to_html_string};
-use crate::config::RenderFlags;
-use crate::adapters::pulldown_cmark::parser::create_parser;
+use crate::components::{
+    ast::{build_ast, build_ast_with_positions},
+    lint,
+    document::DocumentCollector,
+    render::{render_document as render_page, render_node, render_to_html_string},
+    stringify::stringify as stringify_node,
+    toc::build_toc,
+};
+use crate::config::{FormatFlags, LintFlags, ParseFlags, RenderFlags};
+use crate::constants::DEFAULT_ASSETS_CACHE_DIR;
+use crate::error::{AppError, AppResult};
+use crate::services::{frontmatter::parse_frontmatter, highlight_assets, highlighter, includes};
+use crate::types::{BatchInput, BatchResult, Diagnostic, ExpandedMarkdown, HighlightAssetsInfo, LintResult, Node, RenderedDocument, TocEntry};
+use crate::adapters::pulldown_cmark::parser::{create_offset_parser, create_parser};
+use rayon::prelude::*;
+use std::path::{Path, PathBuf};
 
 
-pub fn render(input: String) -> String {
+pub fn render(input: String) -> AppResult<String> {
     let flags = RenderFlags {
         sanitize: true,
         ..RenderFlags::default()
@@ -12,27 +25,168 @@ pub fn render(input: String) -> String {
 }
 
 
-pub fn render_with_options(input: String, flags: RenderFlags) -> String {
+pub fn render_with_options(input: String, flags: RenderFlags) -> AppResult<String> {
+    let (input, _) = expand_source(input, &flags);
     render_to_html_string(&input, flags)
 }
 
-pub fn parse(input: String) -> String {
-        let flags = RenderFlags {
+/// Renders each document the way `render_with_options` does, in parallel on
+/// the rayon pool. Results are in the order of `inputs`, and a document that
+/// fails to render gets an error instead of failing the batch.
+pub fn render_batch(inputs: Vec<BatchInput>, flags: RenderFlags) -> Vec<BatchResult> {
+    inputs
+        .into_par_iter()
+        .map(|BatchInput { id, markdown }| {
+            let (markdown, mut diagnostics) = expand_source(markdown, &flags);
+            match render_page(&markdown, flags.clone()) {
+                Ok((html, _, found)) => {
+                    diagnostics.extend(found);
+                    BatchResult {
+                        id,
+                        html: Some(html),
+                        error: None,
+                        diagnostics,
+                    }
+                }
+                Err(e) => BatchResult {
+                    id,
+                    html: None,
+                    error: Some(e.to_string()),
+                    diagnostics,
+                },
+            }
+        })
+        .collect()
+}
+
+pub fn render_document(input: String, flags: RenderFlags) -> AppResult<RenderedDocument> {
+    let toc_flags = flags.toc_options.clone();
+    let (input, mut diagnostics) = expand_source(input, &flags);
+    let (html, summary, found) = render_page(&input, flags)?;
+    diagnostics.extend(found);
+    let frontmatter = summary
+        .frontmatter
+        .as_ref()
+        .map(|(kind, source)| parse_frontmatter(*kind, source))
+        .transpose()?;
+    Ok(RenderedDocument {
+        html,
+        frontmatter,
+        toc: build_toc(&summary.headings, &toc_flags),
+        word_count: summary.word_count,
+        reading_time: summary.reading_time(),
+        headings: summary.headings,
+        links: summary.links,
+        images: summary.images,
+        excerpt: summary.excerpt,
+        diagnostics,
+    })
+}
+
+/// The table of contents as a tree, without rendering the document.
+pub fn toc(input: String, flags: RenderFlags) -> Vec<TocEntry> {
+    let (input, _) = expand_source(input, &flags);
+    let mut collector = DocumentCollector::new(flags.slug.clone());
+    for event in create_parser(&input, &flags) {
+        collector.observe(&event);
+    }
+    build_toc(&collector.finish().headings, &flags.toc_options)
+}
+
+pub fn render_ast(ast_json: &str, flags: RenderFlags) -> AppResult<String> {
+    let ast: Node = serde_json::from_str(ast_json)?;
+    render_node(&ast, flags)
+}
+
+pub fn parse(input: String, options: ParseFlags) -> AppResult<String> {
+    let flags = options.render_flags();
+    let ast = if options.positions {
+        let mut parser = create_offset_parser(&input, &flags);
+        build_ast_with_positions(&input, &mut parser)
+    } else {
+        let mut parser = create_parser(&input, &flags);
+        build_ast(&mut parser)
+    };
+    serde_json::to_string(&ast).map_err(|e| AppError::Serialize(e.to_string()))
+}
+
+pub fn stringify(ast_json: &str, flags: FormatFlags) -> AppResult<String> {
+    let ast: Node = serde_json::from_str(ast_json)?;
+    Ok(stringify_node(&ast, &flags))
+}
+
+// Parses with the syntax extensions enabled so that nothing the source uses
+// is flattened into plain text on the way back out. Definition lists stay off:
+// their `:` marker would swallow admonition fences and directives. Sanitizing
+// stays off too, as it would turn bracketed text into links.
+pub fn format(input: String, flags: FormatFlags) -> String {
+    let parse_flags = ParseFlags {
+        positions: false,
         gfm: true,
         footnotes: true,
-        ..Default::default()
+        math: true,
+        definition_lists: false,
+        superscript: true,
+        subscript: true,
+        frontmatter: true,
+    };
+    let render_flags = RenderFlags {
+        sanitize: false,
+        ..parse_flags.render_flags()
     };
-    let mut parser = create_parser(&input, flags);
+    let mut parser = create_parser(&input, &render_flags);
     let ast = build_ast(&mut parser);
-    serde_json::to_string(&ast).unwrap_or_else(|_| "null".to_string())
+    stringify_node(&ast, &flags)
+}
+
+/// The markdown with its includes expanded, whether or not `includes` is set.
+pub fn expand_includes(input: String, flags: RenderFlags) -> ExpandedMarkdown {
+    let (markdown, diagnostics) = includes::expand_includes(&input, flags.resolver.as_ref(), &flags.slug);
+    ExpandedMarkdown { markdown, diagnostics }
+}
+
+/// The stylesheet for `highlightMode: "classes"` output with `theme`.
+pub fn theme_css(theme: &str, class_prefix: &str) -> AppResult<String> {
+    highlighter::theme_css(theme, class_prefix)
+}
+
+/// The stylesheet for `highlightMode: "dual"` output.
+pub fn dual_theme_css(class_prefix: &str, dark_selector: Option<&str>) -> String {
+    highlighter::dual_theme_css(class_prefix, dark_selector)
+}
+
+/// Extends the highlighting syntaxes and themes with the files in `dir`.
+pub fn register_highlight_assets(dir: &str, cache_dir: Option<&str>) -> AppResult<HighlightAssetsInfo> {
+    let cache_dir = match cache_dir {
+        Some(cache_dir) => PathBuf::from(cache_dir),
+        None => std::env::temp_dir().join(DEFAULT_ASSETS_CACHE_DIR),
+    };
+    highlight_assets::register_directory(Path::new(dir), &cache_dir)
+}
+
+pub fn reset_highlight_assets() {
+    highlight_assets::reset();
+}
+
+// Includes are expanded on the source, before any parsing, so included
+// markdown takes part in everything the document's own markdown does.
+fn expand_source(input: String, flags: &RenderFlags) -> (String, Vec<Diagnostic>) {
+    if !flags.includes {
+        return (input, Vec::new());
+    }
+    includes::expand_includes(&input, flags.resolver.as_ref(), &flags.slug)
+}
+
+pub fn lint(input: String, flags: LintFlags) -> LintResult {
+    lint::lint(&input, &flags)
 }
 
 // Exposed for benchmarks and tests
-pub fn render_unsafe(input: &str) -> String {
+pub fn render_unsafe(input: &str) -> AppResult<String> {
     render_to_html_string(input, RenderFlags::default())
 }
 
-pub fn render_unsafe_no_highlight(input: &str) -> String {
+pub fn render_unsafe_no_highlight(input: &str) -> AppResult<String> {
     let flags = RenderFlags {
         syntax_highlight: false,
         ..Default::default()
@@ -1 +1,2 @@
 pub mod markdown_orchestrator;
+pub mod tasks;
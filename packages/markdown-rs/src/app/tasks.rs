@@ -0,0 +1,59 @@
+use crate::app::markdown_orchestrator;
+use crate::config::{ParseFlags, RenderFlags};
+use crate::types::RenderedDocument;
+use napi::{Env, Task};
+
+/// Renders on the libuv thread pool, resolving to what `renderDocument`
+/// returns, diagnostics included.
+pub struct RenderTask {
+    input: String,
+    flags: RenderFlags,
+}
+
+impl RenderTask {
+    pub fn new(input: String, flags: RenderFlags) -> Self {
+        Self { input, flags }
+    }
+}
+
+impl Task for RenderTask {
+    type Output = RenderedDocument;
+    type JsValue = RenderedDocument;
+
+    fn compute(&mut self) -> napi::Result<Self::Output> {
+        let input = std::mem::take(&mut self.input);
+        markdown_orchestrator::render_document(input, self.flags.clone())
+            .map_err(|e| napi::Error::from_reason(e.to_string()))
+    }
+
+    fn resolve(&mut self, _env: Env, output: Self::Output) -> napi::Result<Self::JsValue> {
+        Ok(output)
+    }
+}
+
+/// Parses on the libuv thread pool, resolving to what `parse` returns.
+pub struct ParseTask {
+    input: String,
+    flags: ParseFlags,
+}
+
+impl ParseTask {
+    pub fn new(input: String, flags: ParseFlags) -> Self {
+        Self { input, flags }
+    }
+}
+
+impl Task for ParseTask {
+    type Output = String;
+    type JsValue = String;
+
+    fn compute(&mut self) -> napi::Result<Self::Output> {
+        let input = std::mem::take(&mut self.input);
+        markdown_orchestrator::parse(input, self.flags)
+            .map_err(|e| napi::Error::from_reason(e.to_string()))
+    }
+
+    fn resolve(&mut self, _env: Env, output: Self::Output) -> napi::Result<Self::JsValue> {
+        Ok(output)
+    }
+}
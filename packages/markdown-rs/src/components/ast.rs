@@ -1,5 +1,7 @@
-use crate::types::{Alignment, Node, NodeType};
-use pulldown_cmark::{CodeBlockKind, Event, Tag};
+use crate::types::{Alignment, BlockQuoteKind, LinkType, MetadataBlockKind, Node, NodeType, Position};
+use crate::utils::line_index::LineIndex;
+use pulldown_cmark::{CodeBlockKind, CowStr, Event, Tag};
+use std::ops::Range;
 
 fn to_alignment(alignment: pulldown_cmark::Alignment) -> Alignment {
     match alignment {
@@ -10,33 +12,110 @@ fn to_alignment(alignment: pulldown_cmark::Alignment) -> Alignment {
     }
 }
 
+fn to_link_type(link_type: pulldown_cmark::LinkType) -> LinkType {
+    match link_type {
+        pulldown_cmark::LinkType::Inline => LinkType::Inline,
+        pulldown_cmark::LinkType::Reference => LinkType::Reference,
+        pulldown_cmark::LinkType::ReferenceUnknown => LinkType::ReferenceUnknown,
+        pulldown_cmark::LinkType::Collapsed => LinkType::Collapsed,
+        pulldown_cmark::LinkType::CollapsedUnknown => LinkType::CollapsedUnknown,
+        pulldown_cmark::LinkType::Shortcut => LinkType::Shortcut,
+        pulldown_cmark::LinkType::ShortcutUnknown => LinkType::ShortcutUnknown,
+        pulldown_cmark::LinkType::Autolink => LinkType::Autolink,
+        pulldown_cmark::LinkType::Email => LinkType::Email,
+        pulldown_cmark::LinkType::WikiLink { has_pothole } => LinkType::WikiLink { has_pothole },
+    }
+}
+
+fn to_block_quote_kind(kind: pulldown_cmark::BlockQuoteKind) -> BlockQuoteKind {
+    match kind {
+        pulldown_cmark::BlockQuoteKind::Note => BlockQuoteKind::Note,
+        pulldown_cmark::BlockQuoteKind::Tip => BlockQuoteKind::Tip,
+        pulldown_cmark::BlockQuoteKind::Important => BlockQuoteKind::Important,
+        pulldown_cmark::BlockQuoteKind::Warning => BlockQuoteKind::Warning,
+        pulldown_cmark::BlockQuoteKind::Caution => BlockQuoteKind::Caution,
+    }
+}
+
+pub(crate) fn to_metadata_block_kind(kind: pulldown_cmark::MetadataBlockKind) -> MetadataBlockKind {
+    match kind {
+        pulldown_cmark::MetadataBlockKind::YamlStyle => MetadataBlockKind::YamlStyle,
+        pulldown_cmark::MetadataBlockKind::PlusesStyle => MetadataBlockKind::PlusesStyle,
+    }
+}
+
+/// Splits a fence info string into the language and the remaining meta.
+pub fn split_info_string(info: &str) -> (Option<String>, Option<String>) {
+    let info = info.trim();
+    let (lang, meta) = match info.find(char::is_whitespace) {
+        Some(end) => (&info[..end], info[end..].trim_start()),
+        None => (info, ""),
+    };
+    let lang = (!lang.is_empty()).then(|| lang.to_string());
+    let meta = (!meta.is_empty()).then(|| meta.to_string());
+    (lang, meta)
+}
+
 pub fn build_ast<'a>(parser: &mut dyn Iterator<Item = Event<'a>>) -> Node {
+    build_tree(parser.map(|event| (event, None)))
+}
+
+/// Builds the AST with a source `position` on every node. `parser` must yield
+/// byte ranges into `input`, as pulldown-cmark's offset iterator does.
+pub fn build_ast_with_positions<'a>(
+    input: &str,
+    parser: &mut dyn Iterator<Item = (Event<'a>, Range<usize>)>,
+) -> Node {
+    let index = LineIndex::new(input);
+    let mut root = build_tree(parser.map(|(event, range)| (event, Some(index.position(range)))));
+    root.position = Some(index.position(0..input.len()));
+    root
+}
+
+fn build_tree<'a>(events: impl Iterator<Item = (Event<'a>, Option<Position>)>) -> Node {
     let root = Node {
         r#type: NodeType::Document,
         ..Default::default()
     };
     let mut stack: Vec<Node> = vec![root];
 
-    for event in parser {
+    for (event, position) in events {
         match event {
             Event::Start(tag) => {
                 let node_type = match tag {
                     Tag::Paragraph => NodeType::Paragraph,
-                    Tag::Heading { level, .. } => NodeType::Heading(level as u32),
-                    Tag::BlockQuote => NodeType::BlockQuote,
-                    Tag::CodeBlock(kind) => {
-                        let lang = if let CodeBlockKind::Fenced(lang) = kind {
-                            Some(lang.into_string())
-                        } else {
-                            None
-                        };
-                        NodeType::CodeBlock { lang }
-                    }
+                    Tag::Heading { level, id, classes, attrs } => NodeType::Heading {
+                        level: level as u32,
+                        id: id.map(CowStr::into_string),
+                        classes: classes.into_iter().map(CowStr::into_string).collect(),
+                        attrs: attrs
+                            .into_iter()
+                            .map(|(key, value)| (key.into_string(), value.map(CowStr::into_string)))
+                            .collect(),
+                    },
+                    Tag::BlockQuote(kind) => NodeType::BlockQuote {
+                        kind: kind.map(to_block_quote_kind),
+                    },
+                    Tag::CodeBlock(kind) => match kind {
+                        CodeBlockKind::Fenced(info) => {
+                            let (lang, meta) = split_info_string(&info);
+                            NodeType::CodeBlock { lang, meta, indented: false }
+                        }
+                        CodeBlockKind::Indented => NodeType::CodeBlock {
+                            lang: None,
+                            meta: None,
+                            indented: true,
+                        },
+                    },
+                    Tag::HtmlBlock => NodeType::HtmlBlock,
                     Tag::List(start) => NodeType::List { start },
                     Tag::Item => NodeType::Item,
                     Tag::FootnoteDefinition(label) => NodeType::FootnoteDefinition {
                         label: label.into_string(),
                     },
+                    Tag::DefinitionList => NodeType::DefinitionList,
+                    Tag::DefinitionListTitle => NodeType::DefinitionListTitle,
+                    Tag::DefinitionListDefinition => NodeType::DefinitionListDefinition,
                     Tag::Table(alignments) => {
                         NodeType::Table(alignments.into_iter().map(to_alignment).collect())
                     }
@@ -46,17 +125,27 @@ pub fn build_ast<'a>(parser: &mut dyn Iterator<Item = Event<'a>>) -> Node {
                     Tag::Emphasis => NodeType::Emph,
                     Tag::Strong => NodeType::Strong,
                     Tag::Strikethrough => NodeType::Strikethrough,
-                    Tag::Link { dest_url, title, .. } => NodeType::Link {
+                    Tag::Superscript => NodeType::Superscript,
+                    Tag::Subscript => NodeType::Subscript,
+                    Tag::Link { link_type, dest_url, title, id } => NodeType::Link {
+                        link_type: to_link_type(link_type),
                         dest_url: dest_url.into_string(),
                         title: title.into_string(),
+                        id: id.into_string(),
                     },
-                    Tag::Image { dest_url, title, .. } => NodeType::Image {
+                    Tag::Image { link_type, dest_url, title, id } => NodeType::Image {
+                        link_type: to_link_type(link_type),
                         dest_url: dest_url.into_string(),
                         title: title.into_string(),
+                        id: id.into_string(),
+                    },
+                    Tag::MetadataBlock(kind) => NodeType::MetadataBlock {
+                        kind: to_metadata_block_kind(kind),
                     },
                 };
                 let new_node = Node {
                     r#type: node_type,
+                    position,
                     ..Default::default()
                 };
                 stack.push(new_node);
@@ -74,6 +163,9 @@ pub fn build_ast<'a>(parser: &mut dyn Iterator<Item = Event<'a>>) -> Node {
                         if matches!(last_child.r#type, NodeType::Text) {
                             if let Some(content) = &mut last_child.content {
                                 content.push_str(&text);
+                                if let (Some(last), Some(position)) = (&mut last_child.position, position) {
+                                    last.end = position.end;
+                                }
                                 continue;
                             }
                         }
@@ -81,6 +173,7 @@ pub fn build_ast<'a>(parser: &mut dyn Iterator<Item = Event<'a>>) -> Node {
                     parent.children.push(Node {
                         r#type: NodeType::Text,
                         content: Some(text.into_string()),
+                        position,
                         ..Default::default()
                     });
                 }
@@ -90,15 +183,47 @@ pub fn build_ast<'a>(parser: &mut dyn Iterator<Item = Event<'a>>) -> Node {
                     parent.children.push(Node {
                         r#type: NodeType::Code,
                         content: Some(text.into_string()),
+                        position,
                         ..Default::default()
                     });
                 }
             }
-            Event::Html(html) => {
+            Event::InlineMath(text) => {
+                if let Some(parent) = stack.last_mut() {
+                    parent.children.push(Node {
+                        r#type: NodeType::InlineMath,
+                        content: Some(text.into_string()),
+                        position,
+                        ..Default::default()
+                    });
+                }
+            }
+            Event::DisplayMath(text) => {
+                if let Some(parent) = stack.last_mut() {
+                    parent.children.push(Node {
+                        r#type: NodeType::DisplayMath,
+                        content: Some(text.into_string()),
+                        position,
+                        ..Default::default()
+                    });
+                }
+            }
+            Event::Html(text) => {
                 if let Some(parent) = stack.last_mut() {
                     parent.children.push(Node {
                         r#type: NodeType::Html,
-                        content: Some(html.into_string()),
+                        content: Some(text.into_string()),
+                        position,
+                        ..Default::default()
+                    });
+                }
+            }
+            Event::InlineHtml(text) => {
+                if let Some(parent) = stack.last_mut() {
+                    parent.children.push(Node {
+                        r#type: NodeType::InlineHtml,
+                        content: Some(text.into_string()),
+                        position,
                         ..Default::default()
                     });
                 }
@@ -109,6 +234,7 @@ pub fn build_ast<'a>(parser: &mut dyn Iterator<Item = Event<'a>>) -> Node {
                         r#type: NodeType::FootnoteReference {
                             label: label.into_string(),
                         },
+                        position,
                         ..Default::default()
                     });
                 }
@@ -117,6 +243,7 @@ pub fn build_ast<'a>(parser: &mut dyn Iterator<Item = Event<'a>>) -> Node {
                 if let Some(parent) = stack.last_mut() {
                     parent.children.push(Node {
                         r#type: NodeType::SoftBreak,
+                        position,
                         ..Default::default()
                     });
                 }
@@ -125,6 +252,7 @@ pub fn build_ast<'a>(parser: &mut dyn Iterator<Item = Event<'a>>) -> Node {
                 if let Some(parent) = stack.last_mut() {
                     parent.children.push(Node {
                         r#type: NodeType::HardBreak,
+                        position,
                         ..Default::default()
                     });
                 }
@@ -133,11 +261,20 @@ pub fn build_ast<'a>(parser: &mut dyn Iterator<Item = Event<'a>>) -> Node {
                 if let Some(parent) = stack.last_mut() {
                     parent.children.push(Node {
                         r#type: NodeType::Rule,
+                        position,
+                        ..Default::default()
+                    });
+                }
+            }
+            Event::TaskListMarker(checked) => {
+                if let Some(parent) = stack.last_mut() {
+                    parent.children.push(Node {
+                        r#type: NodeType::TaskListMarker { checked },
+                        position,
                         ..Default::default()
                     });
                 }
             }
-            Event::TaskListMarker(_) => {}
         }
     }
     stack.remove(0)
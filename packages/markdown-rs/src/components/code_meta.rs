@@ -0,0 +1,243 @@
+use crate::components::ast::split_info_string;
+use lazy_static::lazy_static;
+use regex::Regex;
+use std::ops::RangeInclusive;
+
+lazy_static! {
+    // A trailing `// [!code highlight]`-style comment, in the comment syntaxes
+    // of the common languages.
+    static ref NOTATION_RE: Regex = Regex::new(
+        r"^(?P<code>.*?)\s*(?://|#|--|;|<!--|/\*)\s*\[!code\s+(?P<kind>highlight|hl|focus|\+\+|--)(?::(?P<count>\d+))?\]\s*(?:-->|\*/)?\s*$"
+    )
+    .unwrap();
+    static ref INLINE_LANG_RE: Regex = Regex::new(r"^(?P<code>.*\S)\{:(?P<lang>[\w#+.-]+)\}$").unwrap();
+}
+
+// Largest first line number `showLineNumbers{n}` accepts; larger ones start
+// at 1.
+const MAX_FIRST_LINE_NUMBER: usize = 1_000_000_000;
+
+/// 1-based line numbers, e.g. `{3,5-7}`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct LineRanges(Vec<RangeInclusive<usize>>);
+
+impl LineRanges {
+    /// Parses a comma separated list of lines and ranges, with or without the
+    /// surrounding braces. Malformed parts are skipped.
+    pub fn parse(spec: &str) -> Self {
+        let spec = spec.trim().trim_start_matches('{').trim_end_matches('}');
+        let ranges = spec
+            .split(',')
+            .filter_map(|part| {
+                let part = part.trim();
+                let (start, end) = part.split_once('-').unwrap_or((part, part));
+                let start = start.trim().parse::<usize>().ok()?;
+                let end = end.trim().parse::<usize>().ok()?;
+                (start <= end).then_some(start..=end)
+            })
+            .collect();
+        Self(ranges)
+    }
+
+    pub fn contains(&self, line: usize) -> bool {
+        self.0.iter().any(|range| range.contains(&line))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    fn push(&mut self, range: RangeInclusive<usize>) {
+        self.0.push(range);
+    }
+}
+
+/// What a fence info string such as
+/// `` rust title="main.rs" {3,5-7} showLineNumbers `` asks for.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct CodeMeta {
+    pub lang: Option<String>,
+    pub title: Option<String>,
+    pub highlight: LineRanges,
+    pub focus: LineRanges,
+    pub added: LineRanges,
+    pub removed: LineRanges,
+    /// Set by `diff-<lang>` fences: lines start with a `+`, `-` or space
+    /// marker and the rest is highlighted as `lang`.
+    pub diff: bool,
+    /// The number of the first line when line numbers are shown.
+    pub line_numbers: Option<usize>,
+}
+
+impl CodeMeta {
+    /// Understands the Shiki/rehype-pretty-code forms (`{1,3}`, `title="…"`,
+    /// `showLineNumbers{10}`, `focus{2}`) as well as VitePress's `rust{4}` and
+    /// `:line-numbers=10`. Unknown words are ignored.
+    pub fn parse(info: &str) -> Self {
+        let (lang, meta) = split_info_string(info);
+        let mut code_meta = CodeMeta::default();
+
+        if let Some(lang) = lang {
+            let end = lang.find(['{', ':']).unwrap_or(lang.len());
+            code_meta.apply(&lang[end..]);
+            let lang = &lang[..end];
+            match lang.strip_prefix("diff-").filter(|lang| !lang.is_empty()) {
+                Some(lang) => {
+                    code_meta.diff = true;
+                    code_meta.lang = Some(lang.to_string());
+                }
+                None => code_meta.lang = (!lang.is_empty()).then(|| lang.to_string()),
+            }
+        }
+        for token in tokenize(meta.as_deref().unwrap_or_default()) {
+            code_meta.apply(&token);
+        }
+        code_meta
+    }
+
+    fn apply(&mut self, token: &str) {
+        if token.is_empty() {
+            return;
+        }
+        // VitePress attaches these directly to the language.
+        if let Some(index) = token.find(":line-numbers").filter(|index| *index > 0) {
+            self.apply(&token[..index]);
+            self.apply(&token[index..]);
+            return;
+        }
+
+        let (key, value) = split_token(token);
+        match key {
+            "" => self.highlight = LineRanges::parse(value.unwrap_or_default()),
+            "title" | "filename" => self.title = value.map(str::to_string),
+            "showLineNumbers" | "lineNumbers" | ":line-numbers" => {
+                let start = value
+                    .and_then(|v| v.trim_matches(['{', '}']).parse().ok())
+                    .filter(|start| (1..=MAX_FIRST_LINE_NUMBER).contains(start));
+                self.line_numbers = Some(start.unwrap_or(1));
+            }
+            ":no-line-numbers" => self.line_numbers = None,
+            "focus" => self.focus = LineRanges::parse(value.unwrap_or_default()),
+            "highlight" | "hl" => self.highlight = LineRanges::parse(value.unwrap_or_default()),
+            _ => {}
+        }
+    }
+}
+
+// Splits `key="value"`, `key=value` and `key{value}` into the key and value.
+// A bare `{…}` has an empty key.
+fn split_token(token: &str) -> (&str, Option<&str>) {
+    if let Some((key, value)) = token.split_once('=') {
+        return (key, Some(unquote(value)));
+    }
+    if let Some(start) = token.find('{') {
+        return (&token[..start], Some(&token[start..]));
+    }
+    (token, None)
+}
+
+fn unquote(value: &str) -> &str {
+    for quote in ['"', '\''] {
+        if let Some(inner) = value.strip_prefix(quote).and_then(|v| v.strip_suffix(quote)) {
+            return inner;
+        }
+    }
+    value
+}
+
+// Splits on whitespace outside of quotes, keeping the quotes in the tokens.
+fn tokenize(meta: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut quote = None;
+
+    for c in meta.chars() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            None if c == '"' || c == '\'' => quote = Some(c),
+            None if c.is_whitespace() => {
+                if !current.is_empty() {
+                    tokens.push(std::mem::take(&mut current));
+                }
+                continue;
+            }
+            _ => {}
+        }
+        current.push(c);
+    }
+    if !current.is_empty() {
+        tokens.push(current);
+    }
+    tokens
+}
+
+/// Splits inline code such as `let x = 5{:rust}` into the code and its
+/// language.
+pub fn split_inline_lang(code: &str) -> Option<(&str, &str)> {
+    let captures = INLINE_LANG_RE.captures(code)?;
+    let code = captures.name("code")?.as_str();
+    let lang = captures.name("lang")?.as_str();
+    Some((code, lang))
+}
+
+/// Removes `// [!code highlight]`, `// [!code focus]`, `// [!code ++]` and
+/// `// [!code --]` comments from the code, adding the lines they mark
+/// (`[!code focus:3]` marks three) to `meta`.
+pub fn extract_notations(code: &str, meta: &mut CodeMeta) -> String {
+    let mut output = String::with_capacity(code.len());
+    let line_count = code.split_inclusive('\n').count();
+
+    for (index, line) in code.split_inclusive('\n').enumerate() {
+        let content = line.trim_end_matches(['\n', '\r']);
+        let Some(captures) = NOTATION_RE.captures(content) else {
+            output.push_str(line);
+            continue;
+        };
+
+        let line_number = index + 1;
+        let count = captures
+            .name("count")
+            .and_then(|count| count.as_str().parse::<usize>().ok())
+            .unwrap_or(1)
+            // A count past the end of the code marks the lines up to it.
+            .clamp(1, line_count - index);
+        let range = line_number..=line_number + count - 1;
+        match &captures["kind"] {
+            "focus" => meta.focus.push(range),
+            "++" => meta.added.push(range),
+            "--" => meta.removed.push(range),
+            _ => meta.highlight.push(range),
+        }
+
+        output.push_str(&captures["code"]);
+        output.push_str(&line[content.len()..]);
+    }
+    output
+}
+
+/// Marks the added and removed lines of a diff. `diff` fences keep their
+/// markers, which the diff syntax highlights; `diff-<lang>` fences have them
+/// removed so the code highlights as `lang`.
+pub fn extract_diff_markers(code: &str, meta: &mut CodeMeta) -> String {
+    let is_diff = meta.lang.as_deref() == Some("diff");
+    if !meta.diff && !is_diff {
+        return code.to_string();
+    }
+
+    let mut output = String::with_capacity(code.len());
+    for (index, line) in code.split_inclusive('\n').enumerate() {
+        let line_number = index + 1;
+        // `+++`/`---` are file headers in a unified diff.
+        let header = is_diff && (line.starts_with("+++") || line.starts_with("---"));
+        match line.chars().next() {
+            Some('+') if !header => meta.added.push(line_number..=line_number),
+            Some('-') if !header => meta.removed.push(line_number..=line_number),
+            _ => {}
+        }
+        match line.chars().next() {
+            Some('+' | '-' | ' ') if meta.diff => output.push_str(&line[1..]),
+            _ => output.push_str(line),
+        }
+    }
+    output
+}
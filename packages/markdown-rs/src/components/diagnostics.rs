@@ -0,0 +1,211 @@
+use crate::components::plugins::admonition::{closes_tight_item, container_markers, is_inline};
+use crate::components::plugins::attributes::{attributes_problem, paragraph_attributes};
+use crate::components::plugins::directive::directive_problem;
+use crate::components::plugins::heading_ids::heading_text;
+use crate::components::plugins::snippets::snippet_problems;
+use crate::config::RenderFlags;
+use crate::error::AppError;
+use crate::types::{Diagnostic, SourceRange};
+use crate::utils::line_index::LineIndex;
+use crate::utils::slug::Slugger;
+use pulldown_cmark::{Event, Tag, TagEnd};
+use std::ops::Range;
+
+/// Finds what rendering would silently pass over: containers left open,
+/// directives and attribute blocks that stay text, explicit heading ids that
+/// had to be changed, snippets that cannot be imported, and references
+/// without a definition. Only syntax whose plugin is enabled is checked.
+///
+/// Observes the parser's events, before any plugin, on their way to the
+/// renderer, so the document is parsed only once.
+pub struct DiagnosticsCollector<'a> {
+    lines: LineIndex<'a>,
+    flags: RenderFlags,
+    diagnostics: Vec<Diagnostic>,
+    // The type and first line of every open container.
+    containers: Vec<(String, Range<usize>)>,
+    // Hands out the same heading and paragraph ids as `HeadingIdsPlugin` and
+    // `AttributesPlugin`.
+    slugger: Option<Slugger>,
+    // Explicit id and text of the heading being read, and where it starts.
+    heading: Option<(Option<String>, String, Range<usize>)>,
+    paragraph: Option<OpenParagraph>,
+    // The lines of the tight list item being read.
+    item: Option<Vec<Event<'static>>>,
+}
+
+// The paragraph being read: its events with their ranges, and where it starts.
+struct OpenParagraph {
+    body: Vec<Event<'static>>,
+    ranges: Vec<Range<usize>>,
+    start: Range<usize>,
+}
+
+impl<'a> DiagnosticsCollector<'a> {
+    pub fn new(input: &'a str, flags: &RenderFlags) -> Self {
+        Self {
+            lines: LineIndex::new(input),
+            flags: flags.clone(),
+            diagnostics: Vec::new(),
+            containers: Vec::new(),
+            slugger: (flags.heading_ids || flags.toc).then(|| Slugger::new(flags.slug.clone())),
+            heading: None,
+            paragraph: None,
+            item: None,
+        }
+    }
+
+    pub fn observe(&mut self, event: &Event, range: Range<usize>) {
+        if let Some(item) = self.item.as_mut() {
+            if is_inline(event) {
+                item.push(event.clone().into_static());
+                return;
+            }
+            self.item_end();
+        }
+        if let Some((_, text, _)) = self.heading.as_mut() {
+            match event {
+                Event::End(TagEnd::Heading(_)) => self.heading_end(),
+                _ => text.push_str(heading_text(event).unwrap_or_default()),
+            }
+            return;
+        }
+        if let Some(paragraph) = self.paragraph.as_mut() {
+            match event {
+                Event::End(TagEnd::Paragraph) => self.paragraph_end(),
+                _ => {
+                    paragraph.body.push(event.clone().into_static());
+                    paragraph.ranges.push(range);
+                }
+            }
+            return;
+        }
+        match event {
+            Event::Start(Tag::Heading { id, .. }) if self.slugger.is_some() => {
+                self.heading = Some((id.as_ref().map(|id| id.to_string()), String::new(), range));
+            }
+            Event::Start(Tag::Item) if self.flags.admonitions => self.item = Some(Vec::new()),
+            Event::Start(Tag::Paragraph) => {
+                self.paragraph = Some(OpenParagraph {
+                    body: Vec::new(),
+                    ranges: Vec::new(),
+                    start: range,
+                });
+            }
+            _ => {}
+        }
+    }
+
+    /// The diagnostics in source order, with those for the references the
+    /// parser found without a definition.
+    pub fn finish(mut self, broken: &[(Range<usize>, String)]) -> Vec<Diagnostic> {
+        for (kind, range) in std::mem::take(&mut self.containers) {
+            let diagnostic = Diagnostic::error(&AppError::UnclosedContainer(kind), self.at(range));
+            self.diagnostics.push(diagnostic);
+        }
+        for (range, reference) in broken {
+            let problem = AppError::BrokenReference(reference.clone());
+            let diagnostic = Diagnostic::warning(&problem, self.at(range.clone()));
+            self.diagnostics.push(diagnostic);
+        }
+        let mut diagnostics = self.diagnostics;
+        diagnostics.sort_by_key(|diagnostic| diagnostic.range.map(|range| range.start.offset));
+        diagnostics
+    }
+
+    fn at(&self, range: Range<usize>) -> Option<SourceRange> {
+        Some(SourceRange::from(self.lines.position(range)))
+    }
+
+    fn heading_end(&mut self) {
+        let (Some((id, text, range)), Some(slugger)) = (self.heading.take(), self.slugger.as_mut()) else {
+            return;
+        };
+        match id {
+            Some(id) => {
+                let used = slugger.reserve(&id);
+                if used != id {
+                    let problem = AppError::DuplicateHeadingId { id, used };
+                    self.diagnostics.push(Diagnostic::warning(&problem, self.at(range)));
+                }
+            }
+            None => {
+                slugger.slug(text.trim());
+            }
+        }
+    }
+
+    fn item_end(&mut self) {
+        let Some(body) = self.item.take() else { return };
+        if closes_tight_item(&body, self.containers.len()) {
+            self.containers.pop();
+        }
+    }
+
+    fn paragraph_end(&mut self) {
+        let Some(OpenParagraph { body, ranges, start }) = self.paragraph.take() else { return };
+        let first = ranges.first().cloned().unwrap_or(start.clone());
+        let last = ranges.last().cloned().unwrap_or(start);
+
+        if self.flags.admonitions {
+            let (opener, closes) = container_markers(&body, self.containers.len());
+            if let Some((kind, _)) = opener {
+                self.containers.push((kind, first.clone()));
+            }
+            if closes {
+                self.containers.pop();
+            }
+        }
+        if self.flags.directives {
+            if let [Event::Text(text)] = body.as_slice() {
+                if let Some(problem) = directive_problem(text) {
+                    self.diagnostics.push(Diagnostic::warning(&problem, self.at(first.clone())));
+                }
+            }
+        }
+        if self.flags.snippets {
+            let lines = text_lines(&body, &ranges);
+            let texts: Vec<&str> = lines.iter().map(|(text, _)| text.as_str()).collect();
+            for (index, problem) in snippet_problems(&texts, self.flags.resolver.as_ref()) {
+                self.diagnostics.push(Diagnostic::error(&problem, self.at(lines[index].1.clone())));
+            }
+        }
+        if self.flags.attributes {
+            if let Some(Event::Text(text)) = body.last() {
+                if let Some(problem) = attributes_problem(text) {
+                    self.diagnostics.push(Diagnostic::warning(&problem, self.at(last.clone())));
+                }
+                let id = paragraph_attributes(text).and_then(|(id, _)| id);
+                if let (Some(id), Some(slugger)) = (id, self.slugger.as_mut()) {
+                    let used = slugger.reserve(&id);
+                    if used != id {
+                        let problem = AppError::DuplicateParagraphId { id, used };
+                        self.diagnostics.push(Diagnostic::warning(&problem, self.at(last)));
+                    }
+                }
+            }
+        }
+    }
+}
+
+// The lines of a paragraph made only of text, with their ranges. Empty if
+// anything else is in it.
+fn text_lines(body: &[Event], ranges: &[Range<usize>]) -> Vec<(String, Range<usize>)> {
+    let mut lines: Vec<(String, Range<usize>)> = Vec::new();
+    let mut new_line = true;
+    for (event, range) in body.iter().zip(ranges) {
+        match event {
+            Event::Text(text) if new_line => lines.push((text.to_string(), range.clone())),
+            Event::Text(text) => {
+                if let Some((line, line_range)) = lines.last_mut() {
+                    line.push_str(text);
+                    line_range.end = range.end;
+                }
+            }
+            Event::SoftBreak => {}
+            _ => return Vec::new(),
+        }
+        new_line = matches!(event, Event::SoftBreak);
+    }
+    lines
+}
@@ -0,0 +1,203 @@
+use crate::components::ast::to_metadata_block_kind;
+use crate::components::plugins::heading_ids::heading_text;
+use crate::types::{HeadingInfo, ImageInfo, LinkInfo, MetadataBlockKind};
+use crate::config::SlugFlags;
+use crate::utils::slug::Slugger;
+use pulldown_cmark::{Event, Tag, TagEnd};
+
+const WORDS_PER_MINUTE: u32 = 200;
+
+/// Page data gathered from the parser's events, alongside the HTML.
+#[derive(Debug, Default)]
+pub struct DocumentSummary {
+    pub frontmatter: Option<(MetadataBlockKind, String)>,
+    pub headings: Vec<HeadingInfo>,
+    pub links: Vec<LinkInfo>,
+    pub images: Vec<ImageInfo>,
+    pub word_count: u32,
+    pub excerpt: Option<String>,
+}
+
+impl DocumentSummary {
+    pub fn reading_time(&self) -> u32 {
+        self.word_count.div_ceil(WORDS_PER_MINUTE)
+    }
+}
+
+enum Target {
+    Link(LinkInfo),
+    Image(ImageInfo),
+}
+
+/// Observes events on their way to the HTML writer and builds a
+/// [`DocumentSummary`] without a second parse.
+pub struct DocumentCollector {
+    summary: DocumentSummary,
+    // Generates the same ids as `HeadingIdsPlugin` when it is not enabled.
+    slugger: Slugger,
+    metadata: Option<(MetadataBlockKind, String)>,
+    // Level, explicit id and text of the heading being read.
+    heading: Option<(u32, Option<String>, String)>,
+    targets: Vec<Target>,
+    paragraph: Option<String>,
+    code_depth: usize,
+    in_word: bool,
+}
+
+impl DocumentCollector {
+    pub fn new(slug: SlugFlags) -> Self {
+        Self {
+            summary: DocumentSummary::default(),
+            slugger: Slugger::new(slug),
+            metadata: None,
+            heading: None,
+            targets: Vec::new(),
+            paragraph: None,
+            code_depth: 0,
+            in_word: false,
+        }
+    }
+
+    pub fn observe(&mut self, event: &Event) {
+        match event {
+            Event::Start(tag) => self.start(tag),
+            Event::End(tag) => self.end(tag),
+            Event::Code(_) => self.text(heading_text(event).unwrap_or_default()),
+            Event::Text(text) | Event::InlineMath(text) | Event::DisplayMath(text) => {
+                self.text(text);
+            }
+            Event::SoftBreak | Event::HardBreak => self.text(" "),
+            _ => {}
+        }
+    }
+
+    pub fn finish(self) -> DocumentSummary {
+        self.summary
+    }
+
+    fn start(&mut self, tag: &Tag) {
+        if !is_inline(tag) {
+            self.in_word = false;
+        }
+        match tag {
+            Tag::MetadataBlock(kind) => {
+                self.metadata = Some((to_metadata_block_kind(*kind), String::new()));
+            }
+            Tag::CodeBlock(_) => self.code_depth += 1,
+            Tag::Heading { level, id, .. } => {
+                self.heading = Some((*level as u32, id.as_ref().map(|id| id.to_string()), String::new()));
+            }
+            Tag::Paragraph if self.summary.excerpt.is_none() => {
+                self.paragraph = Some(String::new());
+            }
+            Tag::Link { dest_url, title, .. } => self.targets.push(Target::Link(LinkInfo {
+                url: dest_url.to_string(),
+                title: title.to_string(),
+                text: String::new(),
+            })),
+            Tag::Image { dest_url, title, .. } => self.targets.push(Target::Image(ImageInfo {
+                url: dest_url.to_string(),
+                title: title.to_string(),
+                alt: String::new(),
+            })),
+            _ => {}
+        }
+    }
+
+    fn end(&mut self, tag: &TagEnd) {
+        if !is_inline_end(tag) {
+            self.in_word = false;
+        }
+        match tag {
+            TagEnd::MetadataBlock(_) => {
+                let metadata = self.metadata.take();
+                if self.summary.frontmatter.is_none() {
+                    self.summary.frontmatter = metadata;
+                }
+            }
+            TagEnd::CodeBlock => self.code_depth = self.code_depth.saturating_sub(1),
+            TagEnd::Heading(_) => {
+                if let Some((level, id, text)) = self.heading.take() {
+                    let text = text.trim().to_string();
+                    let slug = match id {
+                        Some(id) => self.slugger.reserve(&id),
+                        None => self.slugger.slug(&text),
+                    };
+                    self.summary.headings.push(HeadingInfo { level, text, slug });
+                }
+            }
+            TagEnd::Paragraph => {
+                if let Some(paragraph) = self.paragraph.take() {
+                    let excerpt = paragraph.trim();
+                    if !excerpt.is_empty() {
+                        self.summary.excerpt = Some(excerpt.to_string());
+                    }
+                }
+            }
+            TagEnd::Link | TagEnd::Image => match self.targets.pop() {
+                Some(Target::Link(link)) => self.summary.links.push(link),
+                Some(Target::Image(image)) => self.summary.images.push(image),
+                None => {}
+            },
+            _ => {}
+        }
+    }
+
+    fn text(&mut self, text: &str) {
+        if let Some((_, source)) = self.metadata.as_mut() {
+            source.push_str(text);
+            return;
+        }
+        if self.code_depth > 0 {
+            return;
+        }
+
+        for c in text.chars() {
+            if c.is_whitespace() {
+                self.in_word = false;
+            } else if !self.in_word {
+                self.in_word = true;
+                self.summary.word_count += 1;
+            }
+        }
+
+        if let Some((_, _, heading)) = self.heading.as_mut() {
+            heading.push_str(text);
+        }
+        if let Some(paragraph) = self.paragraph.as_mut() {
+            paragraph.push_str(text);
+        }
+        for target in &mut self.targets {
+            match target {
+                Target::Link(link) => link.text.push_str(text),
+                Target::Image(image) => image.alt.push_str(text),
+            }
+        }
+    }
+}
+
+fn is_inline(tag: &Tag) -> bool {
+    matches!(
+        tag,
+        Tag::Emphasis
+            | Tag::Strong
+            | Tag::Strikethrough
+            | Tag::Superscript
+            | Tag::Subscript
+            | Tag::Link { .. }
+            | Tag::Image { .. }
+    )
+}
+
+fn is_inline_end(tag: &TagEnd) -> bool {
+    matches!(
+        tag,
+        TagEnd::Emphasis
+            | TagEnd::Strong
+            | TagEnd::Strikethrough
+            | TagEnd::Superscript
+            | TagEnd::Subscript
+            | TagEnd::Link
+            | TagEnd::Image
+    )
+}
@@ -0,0 +1,140 @@
+use crate::types::{Alignment, BlockQuoteKind, LinkType, MetadataBlockKind, Node, NodeType};
+use pulldown_cmark::{CodeBlockKind, CowStr, Event, HeadingLevel, Tag, TagEnd};
+
+fn from_alignment(alignment: &Alignment) -> pulldown_cmark::Alignment {
+    match alignment {
+        Alignment::None => pulldown_cmark::Alignment::None,
+        Alignment::Left => pulldown_cmark::Alignment::Left,
+        Alignment::Center => pulldown_cmark::Alignment::Center,
+        Alignment::Right => pulldown_cmark::Alignment::Right,
+    }
+}
+
+fn from_link_type(link_type: LinkType) -> pulldown_cmark::LinkType {
+    match link_type {
+        LinkType::Inline => pulldown_cmark::LinkType::Inline,
+        LinkType::Reference => pulldown_cmark::LinkType::Reference,
+        LinkType::ReferenceUnknown => pulldown_cmark::LinkType::ReferenceUnknown,
+        LinkType::Collapsed => pulldown_cmark::LinkType::Collapsed,
+        LinkType::CollapsedUnknown => pulldown_cmark::LinkType::CollapsedUnknown,
+        LinkType::Shortcut => pulldown_cmark::LinkType::Shortcut,
+        LinkType::ShortcutUnknown => pulldown_cmark::LinkType::ShortcutUnknown,
+        LinkType::Autolink => pulldown_cmark::LinkType::Autolink,
+        LinkType::Email => pulldown_cmark::LinkType::Email,
+        LinkType::WikiLink { has_pothole } => pulldown_cmark::LinkType::WikiLink { has_pothole },
+    }
+}
+
+fn from_block_quote_kind(kind: BlockQuoteKind) -> pulldown_cmark::BlockQuoteKind {
+    match kind {
+        BlockQuoteKind::Note => pulldown_cmark::BlockQuoteKind::Note,
+        BlockQuoteKind::Tip => pulldown_cmark::BlockQuoteKind::Tip,
+        BlockQuoteKind::Important => pulldown_cmark::BlockQuoteKind::Important,
+        BlockQuoteKind::Warning => pulldown_cmark::BlockQuoteKind::Warning,
+        BlockQuoteKind::Caution => pulldown_cmark::BlockQuoteKind::Caution,
+    }
+}
+
+fn from_metadata_block_kind(kind: MetadataBlockKind) -> pulldown_cmark::MetadataBlockKind {
+    match kind {
+        MetadataBlockKind::YamlStyle => pulldown_cmark::MetadataBlockKind::YamlStyle,
+        MetadataBlockKind::PlusesStyle => pulldown_cmark::MetadataBlockKind::PlusesStyle,
+    }
+}
+
+fn heading_level(level: u32) -> HeadingLevel {
+    HeadingLevel::try_from(level.clamp(1, 6) as usize).unwrap_or(HeadingLevel::H6)
+}
+
+fn borrowed(value: &str) -> CowStr<'_> {
+    CowStr::Borrowed(value)
+}
+
+/// Flattens an AST back into the event stream it was built from, so it can be
+/// rendered by the same pipeline as parsed markdown.
+pub fn events_from_ast(node: &Node) -> Vec<Event<'_>> {
+    let mut events = Vec::new();
+    push_events(node, &mut events);
+    events
+}
+
+fn push_events<'a>(node: &'a Node, events: &mut Vec<Event<'a>>) {
+    let content = || borrowed(node.content.as_deref().unwrap_or_default());
+
+    let tag = match &node.r#type {
+        NodeType::Document => None,
+        NodeType::BlockQuote { kind } => Some(Tag::BlockQuote(kind.map(from_block_quote_kind))),
+        NodeType::List { start } => Some(Tag::List(*start)),
+        NodeType::Item => Some(Tag::Item),
+        NodeType::FootnoteDefinition { label } => Some(Tag::FootnoteDefinition(borrowed(label))),
+        NodeType::DefinitionList => Some(Tag::DefinitionList),
+        NodeType::DefinitionListTitle => Some(Tag::DefinitionListTitle),
+        NodeType::DefinitionListDefinition => Some(Tag::DefinitionListDefinition),
+        NodeType::Table(alignments) => Some(Tag::Table(alignments.iter().map(from_alignment).collect())),
+        NodeType::TableHead => Some(Tag::TableHead),
+        NodeType::TableRow => Some(Tag::TableRow),
+        NodeType::TableCell => Some(Tag::TableCell),
+        NodeType::Heading { level, id, classes, attrs } => Some(Tag::Heading {
+            level: heading_level(*level),
+            id: id.as_deref().map(borrowed),
+            classes: classes.iter().map(|class| borrowed(class)).collect(),
+            attrs: attrs
+                .iter()
+                .map(|(key, value)| (borrowed(key), value.as_deref().map(borrowed)))
+                .collect(),
+        }),
+        NodeType::CodeBlock { lang, meta, indented } => Some(Tag::CodeBlock(if *indented {
+            CodeBlockKind::Indented
+        } else {
+            let info = match (lang, meta) {
+                (Some(lang), Some(meta)) => CowStr::from(format!("{} {}", lang, meta)),
+                (Some(lang), None) => borrowed(lang),
+                (None, Some(meta)) => CowStr::from(format!(" {}", meta)),
+                (None, None) => borrowed(""),
+            };
+            CodeBlockKind::Fenced(info)
+        })),
+        NodeType::Paragraph => Some(Tag::Paragraph),
+        NodeType::HtmlBlock => Some(Tag::HtmlBlock),
+        NodeType::MetadataBlock { kind } => Some(Tag::MetadataBlock(from_metadata_block_kind(*kind))),
+        NodeType::Strong => Some(Tag::Strong),
+        NodeType::Emph => Some(Tag::Emphasis),
+        NodeType::Strikethrough => Some(Tag::Strikethrough),
+        NodeType::Superscript => Some(Tag::Superscript),
+        NodeType::Subscript => Some(Tag::Subscript),
+        NodeType::Link { link_type, dest_url, title, id } => Some(Tag::Link {
+            link_type: from_link_type(*link_type),
+            dest_url: borrowed(dest_url),
+            title: borrowed(title),
+            id: borrowed(id),
+        }),
+        NodeType::Image { link_type, dest_url, title, id } => Some(Tag::Image {
+            link_type: from_link_type(*link_type),
+            dest_url: borrowed(dest_url),
+            title: borrowed(title),
+            id: borrowed(id),
+        }),
+
+        // Leaves
+        NodeType::TaskListMarker { checked } => return events.push(Event::TaskListMarker(*checked)),
+        NodeType::Rule => return events.push(Event::Rule),
+        NodeType::Html => return events.push(Event::Html(content())),
+        NodeType::Text => return events.push(Event::Text(content())),
+        NodeType::Code => return events.push(Event::Code(content())),
+        NodeType::InlineHtml => return events.push(Event::InlineHtml(content())),
+        NodeType::InlineMath => return events.push(Event::InlineMath(content())),
+        NodeType::DisplayMath => return events.push(Event::DisplayMath(content())),
+        NodeType::SoftBreak => return events.push(Event::SoftBreak),
+        NodeType::HardBreak => return events.push(Event::HardBreak),
+        NodeType::FootnoteReference { label } => {
+            return events.push(Event::FootnoteReference(borrowed(label)))
+        }
+    };
+
+    let end: Option<TagEnd> = tag.as_ref().map(Tag::to_end);
+    events.extend(tag.map(Event::Start));
+    for child in &node.children {
+        push_events(child, events);
+    }
+    events.extend(end.map(Event::End));
+}
@@ -0,0 +1,62 @@
+use crate::components::lint::rule::{Finding, LintContext, Rule};
+use crate::types::{Node, NodeType};
+use lazy_static::lazy_static;
+use regex::Regex;
+
+lazy_static! {
+    static ref URL_RE: Regex = Regex::new(r"https?://[^\s<>()\[\]]+").unwrap();
+}
+
+/// URLs in text are written as links or autolinks (`<https://…>`), since
+/// plain text only becomes a link where linkify is enabled.
+pub struct NoBareUrls;
+
+impl Rule for NoBareUrls {
+    fn name(&self) -> &'static str {
+        "no-bare-urls"
+    }
+
+    fn check(&self, context: &LintContext) -> Vec<Finding> {
+        let mut findings = Vec::new();
+        check_node(context, context.ast, &mut findings);
+        findings
+    }
+}
+
+fn check_node(context: &LintContext, node: &Node, findings: &mut Vec<Finding>) {
+    match node.r#type {
+        // Code, HTML and frontmatter are not prose.
+        NodeType::Link { .. }
+        | NodeType::Image { .. }
+        | NodeType::CodeBlock { .. }
+        | NodeType::HtmlBlock
+        | NodeType::MetadataBlock { .. } => {}
+        NodeType::Text => {
+            let Some(range) = context.range(node) else {
+                return;
+            };
+            // Text content has escapes resolved, so the URLs are looked up
+            // again in the source they came from.
+            let source = &context.source[range.clone()];
+            let mut from = 0;
+            for url in URL_RE.find_iter(node.content.as_deref().unwrap_or_default()) {
+                let url = url.as_str().trim_end_matches(['.', ',', ';', ':', '!', '?', '\'', '"']);
+                let Some(index) = source[from..].find(url) else {
+                    continue;
+                };
+                let start = range.start + from + index;
+                let end = start + url.len();
+                from += index + url.len();
+                findings.push(
+                    Finding::new(format!("Bare URL {url}"), start..end)
+                        .with_fix(start..end, format!("<{url}>")),
+                );
+            }
+        }
+        _ => {
+            for child in &node.children {
+                check_node(context, child, findings);
+            }
+        }
+    }
+}
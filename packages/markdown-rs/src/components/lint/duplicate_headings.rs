@@ -0,0 +1,38 @@
+use crate::components::lint::rule::{text_content, walk, Finding, LintContext, Rule};
+use crate::types::NodeType;
+use std::collections::HashMap;
+
+/// No two headings have the same text, so their anchors stay distinct.
+pub struct NoDuplicateHeadings;
+
+impl Rule for NoDuplicateHeadings {
+    fn name(&self) -> &'static str {
+        "no-duplicate-headings"
+    }
+
+    fn check(&self, context: &LintContext) -> Vec<Finding> {
+        let mut findings = Vec::new();
+        // Heading text to the line it first appeared on.
+        let mut seen: HashMap<String, usize> = HashMap::new();
+
+        walk(context.ast, &mut |node| {
+            if !matches!(node.r#type, NodeType::Heading { .. }) {
+                return;
+            }
+            let (Some(range), Some(position)) = (context.range(node), node.position) else {
+                return;
+            };
+            let text = text_content(node).trim().to_string();
+            match seen.get(&text) {
+                Some(line) => findings.push(Finding::new(
+                    format!("Duplicate heading \"{text}\", first used on line {line}"),
+                    range,
+                )),
+                None => {
+                    seen.insert(text, position.start.line);
+                }
+            }
+        });
+        findings
+    }
+}
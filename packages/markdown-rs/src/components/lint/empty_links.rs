@@ -0,0 +1,34 @@
+use crate::components::lint::rule::{text_content, walk, Finding, LintContext, Rule};
+use crate::types::NodeType;
+
+/// Links lead somewhere and say where: no `[text]()`, `[text](#)` or
+/// `[](url)`.
+pub struct NoEmptyLinks;
+
+impl Rule for NoEmptyLinks {
+    fn name(&self) -> &'static str {
+        "no-empty-links"
+    }
+
+    fn check(&self, context: &LintContext) -> Vec<Finding> {
+        let mut findings = Vec::new();
+        walk(context.ast, &mut |node| {
+            let NodeType::Link { dest_url, .. } = &node.r#type else {
+                return;
+            };
+            let Some(range) = context.range(node) else {
+                return;
+            };
+            let has_image = node
+                .children
+                .iter()
+                .any(|child| matches!(child.r#type, NodeType::Image { .. }));
+            if dest_url.trim().is_empty() || dest_url == "#" {
+                findings.push(Finding::new("Link has no destination".to_string(), range));
+            } else if !has_image && text_content(node).trim().is_empty() {
+                findings.push(Finding::new(format!("Link to {dest_url} has no text"), range));
+            }
+        });
+        findings
+    }
+}
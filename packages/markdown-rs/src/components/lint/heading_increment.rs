@@ -0,0 +1,41 @@
+use crate::components::lint::rule::{walk, Finding, LintContext, Rule};
+use crate::types::NodeType;
+
+/// Heading levels go down one at a time: `###` may not follow `#`.
+pub struct HeadingIncrement;
+
+impl Rule for HeadingIncrement {
+    fn name(&self) -> &'static str {
+        "heading-increment"
+    }
+
+    fn check(&self, context: &LintContext) -> Vec<Finding> {
+        let mut findings = Vec::new();
+        let mut previous = None;
+
+        walk(context.ast, &mut |node| {
+            let NodeType::Heading { level, .. } = node.r#type else {
+                return;
+            };
+            let Some(range) = context.range(node) else {
+                return;
+            };
+            if let Some(previous) = previous.filter(|previous| level > previous + 1) {
+                let expected = previous + 1;
+                let message = format!("Heading level {level} follows level {previous}; expected {expected}");
+                let mut finding = Finding::new(message, range.clone());
+                // Only ATX markers can be rewritten; setext headings stop at 2.
+                let marker_start = range.start
+                    + context.source[range.clone()].len()
+                    - context.source[range.clone()].trim_start().len();
+                if context.source[marker_start..].starts_with(&"#".repeat(level as usize)) {
+                    let marker = marker_start..marker_start + level as usize;
+                    finding = finding.with_fix(marker, "#".repeat(expected as usize));
+                }
+                findings.push(finding);
+            }
+            previous = Some(level);
+        });
+        findings
+    }
+}
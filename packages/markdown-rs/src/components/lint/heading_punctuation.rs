@@ -0,0 +1,40 @@
+use crate::components::lint::rule::{walk, Finding, LintContext, Rule};
+use crate::types::NodeType;
+
+/// Headings are titles, not sentences, so they do not end in punctuation.
+pub struct HeadingPunctuation;
+
+impl Rule for HeadingPunctuation {
+    fn name(&self) -> &'static str {
+        "heading-punctuation"
+    }
+
+    fn check(&self, context: &LintContext) -> Vec<Finding> {
+        let punctuation = &context.flags.heading_punctuation;
+        let mut findings = Vec::new();
+
+        walk(context.ast, &mut |node| {
+            if !matches!(node.r#type, NodeType::Heading { .. }) {
+                return;
+            }
+            // Headings ending in code or a link end in whatever that holds.
+            let Some(last) = node.children.last().filter(|last| last.r#type == NodeType::Text) else {
+                return;
+            };
+            let Some(range) = context.range(last) else {
+                return;
+            };
+            let text = context.source[range.clone()].trim_end();
+            let Some(mark) = text.chars().last().filter(|c| punctuation.contains(*c)) else {
+                return;
+            };
+            let end = range.start + text.len();
+            let start = end - mark.len_utf8();
+            findings.push(
+                Finding::new(format!("Heading ends with \"{mark}\""), start..end)
+                    .with_fix(start..end, String::new()),
+            );
+        });
+        findings
+    }
+}
@@ -0,0 +1,27 @@
+use crate::components::lint::rule::{text_content, walk, Finding, LintContext, Rule};
+use crate::types::NodeType;
+
+/// Images describe themselves for screen readers and broken loads.
+pub struct ImageAltText;
+
+impl Rule for ImageAltText {
+    fn name(&self) -> &'static str {
+        "image-alt-text"
+    }
+
+    fn check(&self, context: &LintContext) -> Vec<Finding> {
+        let mut findings = Vec::new();
+        walk(context.ast, &mut |node| {
+            let NodeType::Image { dest_url, .. } = &node.r#type else {
+                return;
+            };
+            if !text_content(node).trim().is_empty() {
+                return;
+            }
+            if let Some(range) = context.range(node) {
+                findings.push(Finding::new(format!("Image {dest_url} has no alt text"), range));
+            }
+        });
+        findings
+    }
+}
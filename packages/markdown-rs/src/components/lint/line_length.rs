@@ -0,0 +1,51 @@
+use crate::components::lint::rule::{walk, Finding, LintContext, Rule};
+use crate::types::NodeType;
+
+/// Lines stay within `maxLineLength` characters. Code, HTML and frontmatter
+/// are left alone, as are lines whose overflow has no space to break at,
+/// such as long URLs.
+pub struct LineLength;
+
+impl Rule for LineLength {
+    fn name(&self) -> &'static str {
+        "line-length"
+    }
+
+    fn check(&self, context: &LintContext) -> Vec<Finding> {
+        let max = context.flags.max_line_length;
+        let mut skipped = Vec::new();
+        walk(context.ast, &mut |node| {
+            if matches!(
+                node.r#type,
+                NodeType::CodeBlock { .. } | NodeType::HtmlBlock | NodeType::MetadataBlock { .. }
+            ) {
+                // Block ranges end past their last newline, on the next line.
+                if let Some(range) = context.range(node) {
+                    let last = context.lines.point(range.end.saturating_sub(1).max(range.start));
+                    skipped.push(context.lines.point(range.start).line..=last.line);
+                }
+            }
+        });
+
+        let mut findings = Vec::new();
+        let mut line_start = 0;
+        for (index, line) in context.source.split_inclusive('\n').enumerate() {
+            let start = line_start;
+            line_start += line.len();
+            let line = line.trim_end_matches(['\n', '\r']);
+            let length = line.chars().count();
+            if length <= max || skipped.iter().any(|lines| lines.contains(&(index + 1))) {
+                continue;
+            }
+            let overflow = line.char_indices().nth(max).map_or(line.len(), |(offset, _)| offset);
+            if !line[overflow..].contains(char::is_whitespace) {
+                continue;
+            }
+            findings.push(Finding::new(
+                format!("Line is {length} characters long, more than {max}"),
+                start + overflow..start + line.len(),
+            ));
+        }
+        findings
+    }
+}
@@ -0,0 +1,41 @@
+use crate::components::lint::rule::{walk, Finding, LintContext, Rule};
+use crate::types::NodeType;
+
+/// Bullet lists use one marker throughout the document.
+pub struct ListMarkerStyle;
+
+impl Rule for ListMarkerStyle {
+    fn name(&self) -> &'static str {
+        "list-marker-style"
+    }
+
+    fn check(&self, context: &LintContext) -> Vec<Finding> {
+        let mut findings = Vec::new();
+        let mut expected = context.flags.list_marker;
+
+        walk(context.ast, &mut |node| {
+            if !matches!(node.r#type, NodeType::List { start: None }) {
+                return;
+            }
+            for item in &node.children {
+                let Some(range) = context.range(item) else {
+                    continue;
+                };
+                let source = &context.source[range.start..];
+                let start = range.start + source.len() - source.trim_start().len();
+                let Some(marker) = context.source[start..].chars().next().filter(|c| "-*+".contains(*c))
+                else {
+                    continue;
+                };
+                let expected = *expected.get_or_insert(marker);
+                if marker != expected {
+                    findings.push(
+                        Finding::new(format!("List marker {marker} should be {expected}"), start..start + 1)
+                            .with_fix(start..start + 1, expected.to_string()),
+                    );
+                }
+            }
+        });
+        findings
+    }
+}
@@ -0,0 +1,174 @@
+pub mod bare_urls;
+pub mod duplicate_headings;
+pub mod empty_links;
+pub mod heading_increment;
+pub mod heading_punctuation;
+pub mod image_alt_text;
+pub mod line_length;
+pub mod list_marker_style;
+pub mod rule;
+
+use crate::adapters::pulldown_cmark::parser::create_offset_parser;
+use crate::components::ast::build_ast_with_positions;
+use crate::config::{LintFlags, LintSeverity, ParseFlags, RenderFlags};
+use crate::types::{Diagnostic, Fix, LintResult, Node, NodeType, SourceRange};
+use crate::utils::line_index::LineIndex;
+use lazy_static::lazy_static;
+use pulldown_cmark::TextMergeWithOffset;
+use regex::Regex;
+use rule::{walk, LintContext, Rule};
+use std::ops::Range;
+
+lazy_static! {
+    // `<!-- lint-disable no-bare-urls line-length -->`; no names means every rule.
+    static ref LINT_COMMENT_RE: Regex = Regex::new(
+        r"^<!--\s*lint-(?P<kind>disable-next-line|disable|enable)(?P<rules>(?:\s+[\w-]+)*)\s*-->"
+    )
+    .unwrap();
+}
+
+fn all_rules() -> Vec<Box<dyn Rule>> {
+    vec![
+        Box::new(heading_increment::HeadingIncrement),
+        Box::new(duplicate_headings::NoDuplicateHeadings),
+        Box::new(image_alt_text::ImageAltText),
+        Box::new(bare_urls::NoBareUrls),
+        Box::new(empty_links::NoEmptyLinks),
+        Box::new(list_marker_style::ListMarkerStyle),
+        Box::new(heading_punctuation::HeadingPunctuation),
+        Box::new(line_length::LineLength),
+    ]
+}
+
+/// Checks `input` against every rule that is not turned off. Findings
+/// covered by a `lint-disable` comment are dropped; `fixed` is `input` with
+/// the fixes of the remaining ones applied.
+pub fn lint(input: &str, flags: &LintFlags) -> LintResult {
+    let lines = LineIndex::new(input);
+    // Without sanitizing, so bracketed text is not read as a link.
+    let render_flags = RenderFlags {
+        sanitize: false,
+        ..ParseFlags::default().render_flags()
+    };
+    let mut parser = TextMergeWithOffset::new(create_offset_parser(input, &render_flags));
+    let ast = build_ast_with_positions(input, &mut parser);
+    let context = LintContext {
+        source: input,
+        ast: &ast,
+        lines: &lines,
+        flags,
+    };
+    let comments = lint_comments(&context);
+
+    let mut diagnostics = Vec::new();
+    let mut fixes = Vec::new();
+    for rule in all_rules() {
+        let severity = match flags.severity(rule.name()) {
+            LintSeverity::Off => continue,
+            LintSeverity::Warning => "warning",
+            LintSeverity::Error => "error",
+        };
+        for finding in rule.check(&context) {
+            if is_disabled(&comments, rule.name(), &finding.range, &lines) {
+                continue;
+            }
+            let at = |range: Range<usize>| SourceRange::from(lines.position(range));
+            if let Some(fix) = &finding.fix {
+                fixes.push(fix.clone());
+            }
+            diagnostics.push(Diagnostic {
+                severity: severity.to_string(),
+                message: finding.message,
+                code: rule.name().to_string(),
+                range: Some(at(finding.range)),
+                fix: finding.fix.map(|(range, text)| Fix { range: at(range), text }),
+            });
+        }
+    }
+    diagnostics.sort_by_key(|diagnostic| {
+        diagnostic.range.map(|range| (range.start.offset, range.end.offset))
+    });
+
+    let count = |severity: &str| diagnostics.iter().filter(|d| d.severity == severity).count() as u32;
+    LintResult {
+        error_count: count("error"),
+        warning_count: count("warning"),
+        fixed: apply_fixes(input, fixes),
+        diagnostics,
+    }
+}
+
+enum CommentKind {
+    Disable,
+    Enable,
+    DisableNextLine,
+}
+
+struct LintComment {
+    kind: CommentKind,
+    // Empty for every rule.
+    rules: Vec<String>,
+    offset: usize,
+    line: usize,
+}
+
+fn lint_comments(context: &LintContext) -> Vec<LintComment> {
+    let mut comments = Vec::new();
+    walk(context.ast, &mut |node: &Node| {
+        if !matches!(node.r#type, NodeType::Html | NodeType::InlineHtml) {
+            return;
+        }
+        let (Some(content), Some(position)) = (&node.content, node.position) else {
+            return;
+        };
+        let Some(captures) = LINT_COMMENT_RE.captures(content.trim()) else {
+            return;
+        };
+        comments.push(LintComment {
+            kind: match &captures["kind"] {
+                "disable" => CommentKind::Disable,
+                "enable" => CommentKind::Enable,
+                _ => CommentKind::DisableNextLine,
+            },
+            rules: captures["rules"].split_whitespace().map(str::to_string).collect(),
+            offset: context.lines.byte_offset(position.start),
+            line: position.start.line,
+        });
+    });
+    comments
+}
+
+// A finding is disabled by the last `lint-disable` or `lint-enable` before it
+// that names its rule, or by a `lint-disable-next-line` on the line above.
+fn is_disabled(comments: &[LintComment], rule: &str, range: &Range<usize>, lines: &LineIndex) -> bool {
+    let line = lines.point(range.start).line;
+    let mut disabled = false;
+    let applies = |comment: &&LintComment| comment.rules.is_empty() || comment.rules.iter().any(|r| r == rule);
+    for comment in comments.iter().filter(applies) {
+        match comment.kind {
+            CommentKind::DisableNextLine if comment.line + 1 == line => return true,
+            CommentKind::DisableNextLine => {}
+            CommentKind::Disable if comment.offset < range.start => disabled = true,
+            CommentKind::Enable if comment.offset < range.start => disabled = false,
+            CommentKind::Disable | CommentKind::Enable => {}
+        }
+    }
+    disabled
+}
+
+// Fixes are applied back to front so earlier offsets stay valid. A fix that
+// overlaps one before it is left for the next run.
+fn apply_fixes(input: &str, mut fixes: Vec<(Range<usize>, String)>) -> String {
+    fixes.sort_by_key(|(range, _)| (range.start, range.end));
+    let mut applied: Vec<(Range<usize>, String)> = Vec::new();
+    for fix in fixes {
+        if applied.last().is_none_or(|(last, _)| last.end <= fix.0.start) {
+            applied.push(fix);
+        }
+    }
+    let mut fixed = input.to_string();
+    for (range, text) in applied.into_iter().rev() {
+        fixed.replace_range(range, &text);
+    }
+    fixed
+}
@@ -0,0 +1,69 @@
+use crate::config::LintFlags;
+use crate::types::{Node, NodeType};
+use crate::utils::line_index::LineIndex;
+use std::ops::Range;
+
+/// What a rule sees: the source, its AST with positions, and the options.
+pub struct LintContext<'a> {
+    pub source: &'a str,
+    pub ast: &'a Node,
+    pub lines: &'a LineIndex<'a>,
+    pub flags: &'a LintFlags,
+}
+
+impl LintContext<'_> {
+    /// The byte range of `node` in the source.
+    pub fn range(&self, node: &Node) -> Option<Range<usize>> {
+        let position = node.position?;
+        Some(self.lines.byte_offset(position.start)..self.lines.byte_offset(position.end))
+    }
+}
+
+/// A problem found by a rule, with byte ranges into the source.
+pub struct Finding {
+    pub message: String,
+    pub range: Range<usize>,
+    /// Replaces the range with the text.
+    pub fix: Option<(Range<usize>, String)>,
+}
+
+impl Finding {
+    pub fn new(message: String, range: Range<usize>) -> Self {
+        Self {
+            message,
+            range,
+            fix: None,
+        }
+    }
+
+    pub fn with_fix(mut self, range: Range<usize>, text: String) -> Self {
+        self.fix = Some((range, text));
+        self
+    }
+}
+
+pub trait Rule {
+    /// The name used in the configuration and in `lint-disable` comments.
+    fn name(&self) -> &'static str;
+
+    fn check(&self, context: &LintContext) -> Vec<Finding>;
+}
+
+/// Calls `visit` on `node` and every node below it, in document order.
+pub fn walk<'n>(node: &'n Node, visit: &mut impl FnMut(&'n Node)) {
+    visit(node);
+    for child in &node.children {
+        walk(child, visit);
+    }
+}
+
+/// The text of a node as a reader sees it, including code and image alt text.
+pub fn text_content(node: &Node) -> String {
+    let mut text = String::new();
+    walk(node, &mut |node| {
+        if matches!(node.r#type, NodeType::Text | NodeType::Code | NodeType::InlineMath) {
+            text.push_str(node.content.as_deref().unwrap_or_default());
+        }
+    });
+    text
+}
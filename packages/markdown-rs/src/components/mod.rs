@@ -1,3 +1,10 @@
 pub mod ast;
+pub mod code_meta;
+pub mod diagnostics;
+pub mod document;
+pub mod events;
+pub mod lint;
 pub mod render;
 pub mod plugins;
+pub mod stringify;
+pub mod toc;
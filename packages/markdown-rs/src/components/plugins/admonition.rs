@@ -1,63 +1,228 @@
-use pulldown_cmark::{Event, Tag, TagEnd};
 use crate::components::plugins::Plugin;
-use crate::config::RenderFlags;
+use lazy_static::lazy_static;
+use pulldown_cmark::{Event, Tag, TagEnd};
+use regex::Regex;
+use std::collections::VecDeque;
+use v_htmlescape::escape;
+
+lazy_static! {
+    static ref OPEN_RE: Regex = Regex::new(r"^:::\s*([A-Za-z][\w-]*)(?:\s+(.+?))?\s*$").unwrap();
+}
 
+/// Turns `::: type [title]` ... `:::` fences into `<div class="admonition type">` containers.
 pub struct AdmonitionPlugin;
 
 impl Plugin for AdmonitionPlugin {
-    fn process<'a>(&self, _flags: RenderFlags, events: &mut Vec<Event<'a>>) -> bool {
-        let mut old_events = std::mem::take(events);
-        let mut new_events = Vec::with_capacity(old_events.len());
-        let mut iter = old_events.drain(..).peekable();
-        let mut changed = false;
-
-        while let Some(event) = iter.next() {
-            if let Event::Start(Tag::Paragraph) = &event {
-                if let Some(Event::Text(text)) = iter.peek() {
-                    if text.starts_with(":::") {
-                        let parts: Vec<&str> = text.split_whitespace().collect();
-                        if parts.len() > 1 {
-                            let admonition_type = parts[1].to_string();
-                            iter.next(); // Consume text
-                            iter.next(); // Consume End(Paragraph)
-
-                            changed = true;
-                            new_events.push(Event::Html(format!("<div class=\"admonition {}\">", admonition_type).into()));
-
-                            let mut nesting = 0;
-                            while let Some(inner_event) = iter.next() {
-                                if let Event::Start(Tag::Paragraph) = &inner_event {
-                                    if let Some(Event::Text(inner_text)) = iter.peek() {
-                                        if inner_text.starts_with(":::") {
-                                            nesting += 1;
-                                        }
-                                    }
-                                }
-
-                                if let Event::End(TagEnd::Paragraph) = &inner_event {
-                                    if let Some(Event::Text(inner_text)) = new_events.last() {
-                                        if inner_text.trim() == ":::" {
-                                            if nesting == 0 {
-                                                new_events.pop(); // Remove the ::: text
-                                                break;
-                                            } else {
-                                                nesting -= 1;
-                                            }
-                                        }
-                                    }
-                                }
-                                new_events.push(inner_event);
-                            }
-                            new_events.push(Event::Html("</div>".into()));
-                            continue;
+    fn process<'a>(
+        &self,
+        events: Box<dyn Iterator<Item = Event<'a>> + 'a>,
+    ) -> Box<dyn Iterator<Item = Event<'a>> + 'a> {
+        Box::new(AdmonitionStream {
+            inner: events,
+            pending: VecDeque::new(),
+            next: None,
+            open: Vec::new(),
+            lists: 0,
+            deferred: Vec::new(),
+        })
+    }
+}
+
+struct AdmonitionStream<'a> {
+    inner: Box<dyn Iterator<Item = Event<'a>> + 'a>,
+    pending: VecDeque<Event<'a>>,
+    // The event that ended the lines of a tight list item.
+    next: Option<Event<'a>>,
+    // The list depth at which each open container was opened.
+    open: Vec<usize>,
+    lists: usize,
+    // Containers closed inside lists they enclose, by the list depth they
+    // were opened at; they end once those lists do.
+    deferred: Vec<usize>,
+}
+
+impl<'a> Iterator for AdmonitionStream<'a> {
+    type Item = Event<'a>;
+
+    fn next(&mut self) -> Option<Event<'a>> {
+        loop {
+            if let Some(event) = self.pending.pop_front() {
+                return Some(event);
+            }
+
+            match self.next.take().or_else(|| self.inner.next()) {
+                Some(Event::Start(Tag::Paragraph)) => {
+                    let mut body = Vec::new();
+                    for event in self.inner.by_ref() {
+                        if let Event::End(TagEnd::Paragraph) = event {
+                            break;
                         }
+                        body.push(event);
                     }
+                    self.rewrite_paragraph(body);
+                }
+                Some(event @ Event::Start(Tag::List(_))) => {
+                    self.lists += 1;
+                    return Some(event);
                 }
+                Some(event @ Event::End(TagEnd::List(_))) => {
+                    self.lists -= 1;
+                    self.pending.push_back(event);
+                    let lists = self.lists;
+                    let ended = self.deferred.iter().filter(|&&opened| opened >= lists).count();
+                    self.deferred.retain(|&opened| opened < lists);
+                    for _ in 0..ended {
+                        self.pending.push_back(Event::Html("</div>\n".into()));
+                    }
+                }
+                Some(event @ Event::Start(Tag::Item)) => {
+                    self.pending.push_back(event);
+                    self.rewrite_tight_item();
+                }
+                Some(event) => return Some(event),
+                None => {
+                    // Close containers left open at the end of the document.
+                    self.open.pop()?;
+                    return Some(Event::Html("</div>\n".into()));
+                }
+            }
+        }
+    }
+}
+
+impl<'a> AdmonitionStream<'a> {
+    // Items of tight lists hold their lines without a paragraph, so a `:::`
+    // folded into the last line of one is looked for here.
+    fn rewrite_tight_item(&mut self) {
+        let mut body = Vec::new();
+        for event in self.inner.by_ref() {
+            if !is_inline(&event) {
+                self.next = Some(event);
+                break;
             }
-            new_events.push(event);
+            body.push(event);
         }
 
-        *events = new_events;
-        changed
+        if closes_tight_item(&body, self.open.len()) {
+            body.truncate(body.len() - 2);
+            self.pending.extend(body);
+            self.close();
+        } else {
+            self.pending.extend(body);
+        }
     }
+
+    fn close(&mut self) {
+        let Some(lists) = self.open.pop() else {
+            return;
+        };
+        if lists < self.lists {
+            self.deferred.push(lists);
+        } else {
+            self.pending.push_back(Event::Html("</div>\n".into()));
+        }
+    }
+
+    fn rewrite_paragraph(&mut self, mut body: Vec<Event<'a>>) {
+        let (opener, closes) = container_markers(&body, self.open.len());
+        if opener.is_some() {
+            body.drain(..body.len().min(2));
+        }
+        if closes {
+            let keep = body.len().saturating_sub(2);
+            body.truncate(keep);
+        }
+
+        if let Some((kind, title)) = opener {
+            self.open.push(self.lists);
+            self.pending.push_back(Event::Html(
+                format!("<div class=\"admonition {}\">\n", kind).into(),
+            ));
+            if let Some(title) = title {
+                self.pending.push_back(Event::Html(
+                    format!("<p class=\"admonition-title\">{}</p>\n", escape(&title)).into(),
+                ));
+            }
+        }
+
+        if !body.is_empty() {
+            self.pending.push_back(Event::Start(Tag::Paragraph));
+            self.pending.extend(body);
+            self.pending.push_back(Event::End(TagEnd::Paragraph));
+        }
+
+        if closes {
+            self.close();
+        }
+    }
+}
+
+/// Whether the lines of a tight list item end with a `:::` folded into them
+/// while `depth` containers are open. A `:::` on the first line is an item of
+/// its own, not a closing fence.
+pub(crate) fn closes_tight_item(body: &[Event<'_>], depth: usize) -> bool {
+    body.len() > 2 && container_markers(body, depth).1
+}
+
+/// Whether `event` belongs to the lines of a block rather than being a block.
+pub(crate) fn is_inline(event: &Event<'_>) -> bool {
+    match event {
+        Event::Start(tag) => matches!(
+            tag,
+            Tag::Emphasis
+                | Tag::Strong
+                | Tag::Strikethrough
+                | Tag::Superscript
+                | Tag::Subscript
+                | Tag::Link { .. }
+                | Tag::Image { .. }
+        ),
+        Event::End(tag) => matches!(
+            tag,
+            TagEnd::Emphasis
+                | TagEnd::Strong
+                | TagEnd::Strikethrough
+                | TagEnd::Superscript
+                | TagEnd::Subscript
+                | TagEnd::Link
+                | TagEnd::Image
+        ),
+        _ => true,
+    }
+}
+
+/// The type and title of the container a paragraph opens, and whether it
+/// closes one while `depth` containers are open. A paragraph may open a
+/// container on its first line and close one on its last line, since `:::`
+/// markers without blank lines are folded into the surrounding paragraph by
+/// the parser.
+pub(crate) fn container_markers(
+    body: &[Event<'_>],
+    depth: usize,
+) -> (Option<(String, Option<String>)>, bool) {
+    let mut opener = None;
+    if let Some(Event::Text(text)) = body.first() {
+        let first_line_ends = body.len() == 1 || matches!(body.get(1), Some(Event::SoftBreak));
+        if first_line_ends {
+            if let Some(caps) = OPEN_RE.captures(text) {
+                opener = Some((
+                    caps[1].to_string(),
+                    caps.get(2).map(|m| m.as_str().to_string()),
+                ));
+            }
+        }
+    }
+    let rest = if opener.is_some() { &body[body.len().min(2)..] } else { body };
+
+    let open_depth = depth + usize::from(opener.is_some());
+    let closes = open_depth > 0
+        && match rest.last() {
+            Some(Event::Text(text)) => {
+                let last_line_starts =
+                    rest.len() == 1 || matches!(rest.get(rest.len() - 2), Some(Event::SoftBreak));
+                last_line_starts && text.trim() == ":::"
+            }
+            _ => false,
+        };
+    (opener, closes)
 }
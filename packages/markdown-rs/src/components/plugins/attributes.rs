@@ -1,73 +1,129 @@
 use crate::components::plugins::Plugin;
-use crate::config::RenderFlags;
+use crate::error::AppError;
+use crate::utils::slug::Slugger;
 use lazy_static::lazy_static;
-use pulldown_cmark::{Event, Tag};
+use pulldown_cmark::{CowStr, Event, Tag, TagEnd};
 use regex::Regex;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use v_htmlescape::escape;
 
 lazy_static! {
-    static ref ATTR_RE: Regex = Regex::new(r"\s*\{\s*([#.].*?)\s*\}\s*$").unwrap();
+    static ref ATTR_RE: Regex = Regex::new(r"\s*\{\s*([#.][^{}]*?)\s*\}\s*$").unwrap();
 }
 
-pub struct AttributesPlugin;
+/// Applies a trailing `{#id .class}` block to the enclosing paragraph.
+/// Headings get the same syntax from the parser itself. With a `slugger`,
+/// the one that hands out heading ids, an id already taken by a heading or
+/// an earlier paragraph gets a numeric suffix.
+pub struct AttributesPlugin {
+    pub slugger: Option<Arc<Mutex<Slugger>>>,
+}
 
 impl Plugin for AttributesPlugin {
-    fn process<'a>(&self, _flags: RenderFlags, events: &mut Vec<Event<'a>>) -> bool {
-        let mut changed = false;
-        let mut new_events = Vec::with_capacity(events.len());
-        let mut last_start_tag_idx: Option<usize> = None;
-
-        for event in events.drain(..) {
-            if let Event::Text(text) = &event {
-                if let Some(captures) = ATTR_RE.captures(text) {
-                    if let Some(attrs_str) = captures.get(1) {
-                        if let Some(start_idx) = last_start_tag_idx {
-                            let mut id = "";
-                            let mut classes = Vec::new();
-
-                            for part in attrs_str.as_str().split_whitespace() {
-                                if part.starts_with('#') {
-                                    id = &part[1..];
-                                } else if part.starts_with('.') {
-                                    classes.push(&part[1..]);
-                                }
-                            }
-
-                            if let Event::Start(tag) = &new_events[start_idx] {
-                                let new_tag_html = match tag {
-                                    Tag::Paragraph => format!("<p id=\"{}\" class=\"{}\">", id, classes.join(" ")),
-                                    Tag::Heading { level, .. } => format!("<h{} id=\"{}\" class=\"{}\">", level, id, classes.join(" ")),
-                                    _ => "".to_string(),
-                                };
-
-                                if !new_tag_html.is_empty() {
-                                    new_events[start_idx] = Event::Html(new_tag_html.into());
-                                    let original_text = text.trim_end_matches(captures.get(0).unwrap().as_str());
-                                    if !original_text.is_empty() {
-                                        new_events.push(Event::Text(original_text.to_string().into()));
-                                    }
-                                    changed = true;
-                                    last_start_tag_idx = None; // Consume the attribute
-                                    continue;
-                                }
-                            }
-                        }
-                    }
-                }
-            }
+    fn process<'a>(
+        &self,
+        events: Box<dyn Iterator<Item = Event<'a>> + 'a>,
+    ) -> Box<dyn Iterator<Item = Event<'a>> + 'a> {
+        Box::new(AttributesStream {
+            inner: events,
+            pending: VecDeque::new(),
+            slugger: self.slugger.clone(),
+        })
+    }
+}
+
+struct AttributesStream<'a> {
+    inner: Box<dyn Iterator<Item = Event<'a>> + 'a>,
+    pending: VecDeque<Event<'a>>,
+    slugger: Option<Arc<Mutex<Slugger>>>,
+}
+
+impl<'a> Iterator for AttributesStream<'a> {
+    type Item = Event<'a>;
 
-            if let Event::Start(_) = &event {
-                last_start_tag_idx = Some(new_events.len());
-            } else if let Event::End(_) = &event {
-                last_start_tag_idx = None;
+    fn next(&mut self) -> Option<Event<'a>> {
+        if let Some(event) = self.pending.pop_front() {
+            return Some(event);
+        }
+
+        let event = self.inner.next()?;
+        if !matches!(event, Event::Start(Tag::Paragraph)) {
+            return Some(event);
+        }
+
+        let mut body = Vec::new();
+        for inner in self.inner.by_ref() {
+            if let Event::End(TagEnd::Paragraph) = inner {
+                break;
             }
+            body.push(inner);
+        }
 
-            new_events.push(event);
+        let open_tag = match body.last_mut() {
+            Some(Event::Text(text)) => take_attributes(text, self.slugger.as_deref()),
+            _ => None,
+        };
+        if matches!(body.last(), Some(Event::Text(text)) if text.is_empty()) {
+            body.pop();
         }
 
-        if changed {
-            *events = new_events;
+        self.pending.extend(body);
+        self.pending.push_back(Event::End(TagEnd::Paragraph));
+        match open_tag {
+            Some(html) => Some(Event::Html(html.into())),
+            None => Some(event),
         }
+    }
+}
+
+/// The id and classes of a trailing `{#id .class}` block in `text`, the last
+/// text of a paragraph.
+pub(crate) fn paragraph_attributes(text: &str) -> Option<(Option<String>, Vec<String>)> {
+    let caps = ATTR_RE.captures(text)?;
+    let mut id = None;
+    let mut classes = Vec::new();
+
+    for part in caps[1].split_whitespace() {
+        if let Some(value) = part.strip_prefix('#') {
+            id = Some(value.to_string());
+        } else if let Some(value) = part.strip_prefix('.') {
+            classes.push(value.to_string());
+        } else {
+            return None;
+        }
+    }
+    Some((id, classes))
+}
 
-        changed
+// Strips the attribute block from `text` and returns the rewritten `<p>` tag.
+fn take_attributes(text: &mut CowStr<'_>, slugger: Option<&Mutex<Slugger>>) -> Option<String> {
+    let (mut id, classes) = paragraph_attributes(text)?;
+    let caps = ATTR_RE.captures(text)?;
+    if let (Some(written), Some(slugger)) = (&id, slugger) {
+        id = Some(slugger.lock().unwrap_or_else(|e| e.into_inner()).reserve(written));
     }
+
+    let mut html = String::from("<p");
+    if let Some(id) = id {
+        html.push_str(&format!(" id=\"{}\"", escape(&id)));
+    }
+    if !classes.is_empty() {
+        html.push_str(&format!(" class=\"{}\"", escape(&classes.join(" "))));
+    }
+    html.push('>');
+
+    let start = caps.get(0).map_or(text.len(), |m| m.start());
+    *text = text[..start].to_string().into();
+    Some(html)
+}
+
+/// Why a trailing attribute block in `text` is left as text: the first part
+/// that is neither an `#id` nor a `.class`.
+pub(crate) fn attributes_problem(text: &str) -> Option<AppError> {
+    let caps = ATTR_RE.captures(text)?;
+    caps[1]
+        .split_whitespace()
+        .find(|part| !part.starts_with(['#', '.']))
+        .map(|part| AppError::InvalidAttributes(part.to_string()))
 }
@@ -1,51 +1,112 @@
 use crate::components::plugins::Plugin;
-use crate::config::RenderFlags;
+use crate::error::AppError;
+use lazy_static::lazy_static;
 use pulldown_cmark::{CowStr, Event, Tag, TagEnd};
+use regex::Regex;
+use std::collections::VecDeque;
+use v_htmlescape::escape;
 
+lazy_static! {
+    static ref DIRECTIVE_RE: Regex = Regex::new(r"^::([A-Za-z][\w-]*)\[(.*)\]\s*$").unwrap();
+    static ref VIDEO_ID_RE: Regex = Regex::new(r"^[A-Za-z0-9_-]+$").unwrap();
+}
+
+/// Replaces paragraphs consisting of a single `::name[content]` leaf directive.
+/// Unknown directives are left as text.
 pub struct DirectivePlugin;
 
 impl Plugin for DirectivePlugin {
-    fn process<'a>(&self, flags: RenderFlags, events: &mut Vec<Event<'a>>) -> bool {
-        if !flags.directives {
-            return false;
+    fn process<'a>(
+        &self,
+        events: Box<dyn Iterator<Item = Event<'a>> + 'a>,
+    ) -> Box<dyn Iterator<Item = Event<'a>> + 'a> {
+        Box::new(DirectiveStream {
+            inner: events,
+            pending: VecDeque::new(),
+        })
+    }
+}
+
+// The directives `render_directive` knows.
+const DIRECTIVE_NAMES: &[&str] = &["youtube", "note"];
+
+// The sanitizer lets these through on the elements of a `::youtube` embed.
+pub(crate) const YOUTUBE_EMBED_URL: &str = "https://www.youtube.com/embed/";
+pub(crate) const YOUTUBE_WRAPPER_STYLE: &str =
+    "position: relative; padding-bottom: 56.25%; height: 0; overflow: hidden;";
+pub(crate) const YOUTUBE_FRAME_STYLE: &str =
+    "position: absolute; top: 0; left: 0; width: 100%; height: 100%;";
+
+fn render_directive(name: &str, content: &str) -> Option<String> {
+    match name {
+        "youtube" if VIDEO_ID_RE.is_match(content) => Some(format!(
+            r#"<div style="{YOUTUBE_WRAPPER_STYLE}"><iframe src="{YOUTUBE_EMBED_URL}{content}" style="{YOUTUBE_FRAME_STYLE}" allowfullscreen frameborder="0"></iframe></div>"#
+        )),
+        "note" => Some(format!("<div class=\"note\">{}</div>", escape(content))),
+        _ => None,
+    }
+}
+
+struct DirectiveStream<'a> {
+    inner: Box<dyn Iterator<Item = Event<'a>> + 'a>,
+    pending: VecDeque<Event<'a>>,
+}
+
+impl<'a> Iterator for DirectiveStream<'a> {
+    type Item = Event<'a>;
+
+    fn next(&mut self) -> Option<Event<'a>> {
+        if let Some(event) = self.pending.pop_front() {
+            return Some(event);
         }
-        
-        let mut changed = false;
-        let mut i = 0;
-        while i < events.len() {
-            if i + 2 < events.len() {
-                if let (
-                    Event::Start(Tag::Paragraph),
-                    Event::Text(text),
-                    Event::End(TagEnd::Paragraph),
-                ) = (&events[i], &events[i + 1], &events[i + 2]) {
-                    if text.starts_with("::") {
-                        if let Some(end_directive_pos) = text.find('[') {
-                            let name = &text[2..end_directive_pos];
-                            if let Some(end_content_pos) = text.rfind(']') {
-                                let content = &text[end_directive_pos + 1..end_content_pos];
-                                let html = match name {
-                                    "youtube" => Some(format!(
-                                        r#"<div style="position: relative; padding-bottom: 56.25%; height: 0; overflow: hidden;"><iframe src="https://www.youtube.com/embed/{}" style="position: absolute; top: 0; left: 0; width: 100%; height: 100%;" allowfullscreen frameborder="0"></iframe></div>"#,
-                                        content
-                                    )),
-                                    "note" => Some(format!("<div class=\"note\">{}</div>", content)),
-                                    _ => None,
-                                };
-
-                                if let Some(html_content) = html {
-                                    let directive_event = Event::Html(CowStr::from(html_content));
-                                    events.splice(i..=i + 2, std::iter::once(directive_event));
-                                    changed = true;
-                                    continue;
-                                }
-                            }
-                        }
-                    }
-                }
+
+        let event = self.inner.next()?;
+        if !matches!(event, Event::Start(Tag::Paragraph)) {
+            return Some(event);
+        }
+
+        // Only `Start(Paragraph), Text, End(Paragraph)` can be a directive, so
+        // look at most two events ahead.
+        let text = match self.inner.next() {
+            Some(Event::Text(text)) => text,
+            Some(other) => {
+                self.pending.push_back(other);
+                return Some(event);
+            }
+            None => return Some(event),
+        };
+        let end = self.inner.next();
+
+        if matches!(end, Some(Event::End(TagEnd::Paragraph))) {
+            if let Some(html) = directive_html(&text) {
+                return Some(Event::Html(CowStr::from(html)));
             }
-            i += 1;
         }
-        changed
+
+        self.pending.push_back(Event::Text(text));
+        self.pending.extend(end);
+        Some(event)
     }
 }
+
+fn directive_html(text: &str) -> Option<String> {
+    let caps = DIRECTIVE_RE.captures(text)?;
+    render_directive(&caps[1], &caps[2]).map(|html| html + "\n")
+}
+
+/// Why a paragraph written as a `::name[content]` directive is left as text.
+pub(crate) fn directive_problem(text: &str) -> Option<AppError> {
+    let caps = DIRECTIVE_RE.captures(text)?;
+    let (name, content) = (&caps[1], &caps[2]);
+    if render_directive(name, content).is_some() {
+        return None;
+    }
+    Some(if DIRECTIVE_NAMES.contains(&name) {
+        AppError::InvalidDirective {
+            name: name.to_string(),
+            content: content.to_string(),
+        }
+    } else {
+        AppError::UnknownDirective(name.to_string())
+    })
+}
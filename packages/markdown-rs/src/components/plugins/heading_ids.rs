@@ -0,0 +1,80 @@
+use crate::components::code_meta::split_inline_lang;
+use crate::components::plugins::Plugin;
+use crate::utils::slug::Slugger;
+use pulldown_cmark::{CowStr, Event, Tag, TagEnd};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+/// Returns the part of `event` that counts as heading text. Inline code
+/// keeps its code but not a `{:lang}` suffix, which highlighting removes.
+pub fn heading_text<'e>(event: &'e Event) -> Option<&'e str> {
+    match event {
+        Event::Code(code) => Some(split_inline_lang(code).map_or(code.as_ref(), |(code, _)| code)),
+        Event::Text(text) | Event::InlineMath(text) => Some(text),
+        _ => None,
+    }
+}
+
+/// Gives every heading an `id`, keeping explicit `{#id}` attributes and
+/// generating unique slugs for the rest. The slugger is shared with
+/// `AttributesPlugin`, so paragraph ids are never handed out twice either.
+pub struct HeadingIdsPlugin {
+    pub slugger: Arc<Mutex<Slugger>>,
+}
+
+impl Plugin for HeadingIdsPlugin {
+    fn process<'a>(
+        &self,
+        events: Box<dyn Iterator<Item = Event<'a>> + 'a>,
+    ) -> Box<dyn Iterator<Item = Event<'a>> + 'a> {
+        Box::new(HeadingIdsStream {
+            inner: events,
+            pending: VecDeque::new(),
+            slugger: Arc::clone(&self.slugger),
+        })
+    }
+}
+
+struct HeadingIdsStream<'a> {
+    inner: Box<dyn Iterator<Item = Event<'a>> + 'a>,
+    pending: VecDeque<Event<'a>>,
+    slugger: Arc<Mutex<Slugger>>,
+}
+
+impl<'a> Iterator for HeadingIdsStream<'a> {
+    type Item = Event<'a>;
+
+    fn next(&mut self) -> Option<Event<'a>> {
+        if let Some(event) = self.pending.pop_front() {
+            return Some(event);
+        }
+
+        let event = self.inner.next()?;
+        let Event::Start(Tag::Heading { level, id, classes, attrs }) = event else {
+            return Some(event);
+        };
+
+        // The slug depends on the full heading text, so buffer up to its end.
+        let mut text = String::new();
+        for inner in self.inner.by_ref() {
+            text.push_str(heading_text(&inner).unwrap_or_default());
+            let is_end = matches!(inner, Event::End(TagEnd::Heading(_)));
+            self.pending.push_back(inner);
+            if is_end {
+                break;
+            }
+        }
+
+        let mut slugger = self.slugger.lock().unwrap_or_else(|e| e.into_inner());
+        let id = match id {
+            Some(id) => slugger.reserve(&id),
+            None => slugger.slug(text.trim()),
+        };
+        Some(Event::Start(Tag::Heading {
+            level,
+            id: Some(CowStr::from(id)),
+            classes,
+            attrs,
+        }))
+    }
+}
@@ -1,46 +1,98 @@
 use crate::components::plugins::Plugin;
-use crate::config::RenderFlags;
-use pulldown_cmark::{Event, LinkType, Tag, TagEnd};
+use lazy_static::lazy_static;
+use pulldown_cmark::{CowStr, Event, LinkType, Tag, TagEnd};
 use regex::Regex;
 
+lazy_static! {
+    static ref URL_RE: Regex = Regex::new(r#"https?://[^\s<>"'`]+"#).unwrap();
+    static ref ANCHOR_RE: Regex = Regex::new(r"(?i)^<(/?)a(\s|/?>)").unwrap();
+}
+
+/// Turns bare `http(s)://` URLs in text into autolinks. Text inside links,
+/// images, code blocks and raw `<a>` elements is left alone.
 pub struct LinkifyPlugin;
 
 impl Plugin for LinkifyPlugin {
-    fn process<'a>(&self, flags: RenderFlags, events: &mut Vec<Event<'a>>) -> bool {
-        if !flags.linkify {
-            return false;
-        }
-
-        let mut new_events = Vec::new();
-        let re = Regex::new(r"(https?://[\w\d\.\-/?=&]+)").unwrap();
+    fn process<'a>(
+        &self,
+        events: Box<dyn Iterator<Item = Event<'a>> + 'a>,
+    ) -> Box<dyn Iterator<Item = Event<'a>> + 'a> {
+        let mut skip_depth = 0usize;
 
-        for event in events.drain(..) {
-            if let Event::Text(text) = event {
-                let mut last_end = 0;
-                for mat in re.find_iter(&text) {
-                    if mat.start() > last_end {
-                        new_events.push(Event::Text(text[last_end..mat.start()].to_string().into()));
+        Box::new(events.flat_map(move |event| {
+            match &event {
+                Event::Start(
+                    Tag::Link { .. } | Tag::Image { .. } | Tag::CodeBlock(_) | Tag::MetadataBlock(_),
+                ) => {
+                    skip_depth += 1;
+                }
+                Event::End(
+                    TagEnd::Link | TagEnd::Image | TagEnd::CodeBlock | TagEnd::MetadataBlock(_),
+                ) => {
+                    skip_depth = skip_depth.saturating_sub(1);
+                }
+                Event::InlineHtml(html) => {
+                    if let Some(caps) = ANCHOR_RE.captures(html) {
+                        if html.trim_end().ends_with("/>") {
+                            // Self-closing, so there is nothing inside it.
+                        } else if caps[1].is_empty() {
+                            skip_depth += 1;
+                        } else {
+                            skip_depth = skip_depth.saturating_sub(1);
+                        }
                     }
-                    let link_text = &text[mat.start()..mat.end()];
-                    new_events.push(Event::Start(Tag::Link {
-                        link_type: LinkType::Inline,
-                        dest_url: link_text.to_string().into(),
-                        title: "".into(),
-                        id: "".into(),
-                    }));
-                    new_events.push(Event::Text(link_text.to_string().into()));
-                    new_events.push(Event::End(TagEnd::Link));
-                    last_end = mat.end();
                 }
-                if last_end < text.len() {
-                    new_events.push(Event::Text(text[last_end..].to_string().into()));
+                Event::Text(text) if skip_depth == 0 && URL_RE.is_match(text) => {
+                    return linkify(text);
                 }
-            } else {
-                new_events.push(event);
+                _ => {}
             }
+            vec![event]
+        }))
+    }
+}
+
+fn linkify<'a>(text: &str) -> Vec<Event<'a>> {
+    let mut events = Vec::new();
+    let mut last_end = 0;
+
+    for mat in URL_RE.find_iter(text) {
+        let url = trim_trailing_punctuation(mat.as_str());
+        if mat.start() > last_end {
+            events.push(Event::Text(text[last_end..mat.start()].to_string().into()));
         }
+        events.push(Event::Start(Tag::Link {
+            link_type: LinkType::Autolink,
+            dest_url: CowStr::from(url.to_string()),
+            title: "".into(),
+            id: "".into(),
+        }));
+        events.push(Event::Text(url.to_string().into()));
+        events.push(Event::End(TagEnd::Link));
+        last_end = mat.start() + url.len();
+    }
 
-        *events = new_events;
-        true
+    if last_end < text.len() {
+        events.push(Event::Text(text[last_end..].to_string().into()));
+    }
+    events
+}
+
+// Sentence punctuation and unbalanced closing parentheses are not part of the URL.
+fn trim_trailing_punctuation(url: &str) -> &str {
+    let mut url = url;
+    loop {
+        let trimmed = url.trim_end_matches(['.', ',', ':', ';', '!', '?', '\'', '"']);
+        let trimmed = if trimmed.ends_with(')')
+            && trimmed.matches(')').count() > trimmed.matches('(').count()
+        {
+            &trimmed[..trimmed.len() - 1]
+        } else {
+            trimmed
+        };
+        if trimmed.len() == url.len() {
+            return url;
+        }
+        url = trimmed;
     }
 }
@@ -1,12 +1,91 @@
-// pub mod directive;
-// pub mod admonition;
-// pub mod attributes;
-// pub mod linkify;
+pub mod directive;
+pub mod admonition;
+pub mod attributes;
+pub mod heading_ids;
+pub mod linkify;
 pub mod plugin;
-// pub mod spoiler;
+pub mod raw_html;
+pub mod snippets;
+pub mod spoiler;
 pub mod syntax_highlighting;
 pub mod toc;
-// pub mod typographer;
-// pub mod math;
+pub mod typographer;
+pub mod url_policy;
+pub mod math;
 
 pub use plugin::Plugin;
+
+use crate::config::{RawHtmlMode, RenderFlags};
+use crate::utils::slug::Slugger;
+use std::sync::{Arc, Mutex};
+
+/// Returns the plugins enabled by `flags`, in the order they are applied.
+///
+/// Block containers are resolved first so that later plugins see their
+/// contents as ordinary paragraphs; text rewrites run last so they never touch
+/// directive or attribute syntax. Code is rendered after all of them by
+/// `code_plugin`. Heading and paragraph ids are handed out by `slugger`.
+pub fn enabled_plugins(flags: &RenderFlags, slugger: &Arc<Mutex<Slugger>>) -> Vec<Box<dyn Plugin>> {
+    let mut plugins: Vec<Box<dyn Plugin>> = Vec::new();
+    let heading_ids = flags.heading_ids || flags.toc;
+
+    // Only HTML written in the markdown, not HTML emitted by plugins.
+    if flags.raw_html != RawHtmlMode::Allow {
+        plugins.push(Box::new(raw_html::RawHtmlPlugin {
+            mode: flags.raw_html.clone(),
+            url_policy: flags.url_policy.clone(),
+            keep_spoilers: flags.spoiler,
+        }));
+    }
+    // Imported code goes through the same highlighting as written code.
+    if flags.snippets {
+        plugins.push(Box::new(snippets::SnippetsPlugin {
+            resolver: flags.resolver.clone(),
+        }));
+    }
+    if flags.admonitions {
+        plugins.push(Box::new(admonition::AdmonitionPlugin));
+    }
+    if flags.directives {
+        plugins.push(Box::new(directive::DirectivePlugin));
+    }
+    if flags.attributes {
+        plugins.push(Box::new(attributes::AttributesPlugin {
+            slugger: heading_ids.then(|| Arc::clone(slugger)),
+        }));
+    }
+    if flags.math {
+        plugins.push(Box::new(math::MathPlugin));
+    }
+    if flags.spoiler {
+        plugins.push(Box::new(spoiler::SpoilerPlugin));
+    }
+    // The table of contents links to the heading ids, so it needs them too.
+    if heading_ids {
+        plugins.push(Box::new(heading_ids::HeadingIdsPlugin {
+            slugger: Arc::clone(slugger),
+        }));
+    }
+    if flags.toc {
+        plugins.push(Box::new(toc::TocPlugin {
+            flags: flags.toc_options.clone(),
+        }));
+    }
+    if flags.linkify {
+        plugins.push(Box::new(linkify::LinkifyPlugin));
+    }
+    if flags.smart_punctuation {
+        plugins.push(Box::new(typographer::TypographerPlugin));
+    }
+    plugins
+}
+
+/// Renders code: highlighted when `syntax_highlight` is on, and otherwise
+/// plain but with the fence meta applied. The renderer runs it after every
+/// other plugin, whether or not any are enabled.
+pub fn code_plugin(flags: &RenderFlags) -> syntax_highlighting::SyntaxHighlightingPlugin {
+    syntax_highlighting::SyntaxHighlightingPlugin {
+        flags: flags.highlight.clone(),
+        highlight: flags.syntax_highlight,
+    }
+}
@@ -0,0 +1,237 @@
+use crate::components::plugins::Plugin;
+use crate::config::{RawHtmlMode, UrlPolicyFlags};
+use crate::constants::RAW_HTML_ATTRIBUTES;
+use lazy_static::lazy_static;
+use pulldown_cmark::{CowStr, Event, Tag, TagEnd};
+use regex::{Captures, Regex};
+use std::collections::VecDeque;
+
+lazy_static! {
+    // A script or style element with its contents, a tag, a comment, a
+    // declaration or a processing instruction.
+    static ref MARKUP_RE: Regex = Regex::new(
+        r#"(?is)<script\b.*?</script\s*>|<style\b.*?</style\s*>|<!--.*?-->|<![^>]*>|<\?.*?\?>|<(?P<close>/)?(?P<name>[a-zA-Z][a-zA-Z0-9-]*)(?P<attrs>(?:\s+[^\s"'>/=]+(?:\s*=\s*(?:"[^"]*"|'[^']*'|[^\s"'=<>`]+))?)*)\s*(?P<void>/)?>"#
+    )
+    .unwrap();
+    static ref ATTRIBUTE_RE: Regex = Regex::new(
+        r#"(?P<name>[^\s"'>/=]+)(?:\s*=\s*(?:"(?P<double>[^"]*)"|'(?P<single>[^']*)'|(?P<bare>[^\s"'=<>`]+)))?"#
+    )
+    .unwrap();
+    static ref CHARACTER_REFERENCE_RE: Regex = Regex::new(
+        r"&(?:#[xX](?P<hex>[0-9a-fA-F]{1,6});?|#(?P<decimal>[0-9]{1,7});?|(?P<named>[a-zA-Z]+);)"
+    )
+    .unwrap();
+}
+
+/// Applies the `rawHtml` mode to HTML written in the markdown. It runs before
+/// the other plugins, so the HTML they emit is left alone; the `<spoiler>`
+/// tags the spoiler plugin reads are kept when it is enabled.
+pub struct RawHtmlPlugin {
+    pub mode: RawHtmlMode,
+    pub url_policy: UrlPolicyFlags,
+    pub keep_spoilers: bool,
+}
+
+impl Plugin for RawHtmlPlugin {
+    fn process<'a>(
+        &self,
+        events: Box<dyn Iterator<Item = Event<'a>> + 'a>,
+    ) -> Box<dyn Iterator<Item = Event<'a>> + 'a> {
+        Box::new(RawHtmlStream {
+            inner: events,
+            pending: VecDeque::new(),
+            mode: self.mode.clone(),
+            url_policy: self.url_policy.clone(),
+            keep_spoilers: self.keep_spoilers,
+        })
+    }
+}
+
+struct RawHtmlStream<'a> {
+    inner: Box<dyn Iterator<Item = Event<'a>> + 'a>,
+    pending: VecDeque<Event<'a>>,
+    mode: RawHtmlMode,
+    url_policy: UrlPolicyFlags,
+    keep_spoilers: bool,
+}
+
+impl<'a> Iterator for RawHtmlStream<'a> {
+    type Item = Event<'a>;
+
+    fn next(&mut self) -> Option<Event<'a>> {
+        loop {
+            if let Some(event) = self.pending.pop_front() {
+                return Some(event);
+            }
+            match self.inner.next()? {
+                Event::Start(Tag::HtmlBlock) => {
+                    // A tag may span several lines, so the block is handled
+                    // as a whole.
+                    let mut html = String::new();
+                    for inner in self.inner.by_ref() {
+                        match inner {
+                            Event::End(TagEnd::HtmlBlock) => break,
+                            Event::Html(text) | Event::Text(text) => html.push_str(&text),
+                            _ => {}
+                        }
+                    }
+                    self.block(html);
+                }
+                Event::Html(html) => self.block(html.to_string()),
+                Event::InlineHtml(html) => self.inline(html),
+                event => return Some(event),
+            }
+        }
+    }
+}
+
+impl<'a> RawHtmlStream<'a> {
+    fn is_spoiler(&self, html: &str) -> bool {
+        self.keep_spoilers && matches!(html.trim(), "<spoiler>" | "</spoiler>")
+    }
+
+    fn block(&mut self, html: String) {
+        if self.mode == RawHtmlMode::Allow || self.is_spoiler(&html) {
+            self.push_block(html);
+            return;
+        }
+        match &self.mode {
+            RawHtmlMode::Escape => {
+                self.pending.push_back(Event::Start(Tag::Paragraph));
+                for (index, line) in html.trim_end().lines().enumerate() {
+                    if index > 0 {
+                        self.pending.push_back(Event::SoftBreak);
+                    }
+                    self.pending.push_back(Event::Text(line.to_string().into()));
+                }
+                self.pending.push_back(Event::End(TagEnd::Paragraph));
+            }
+            RawHtmlMode::Allowlist(tags) => {
+                let filtered = filter_html(&html, tags, &self.url_policy);
+                if !filtered.trim().is_empty() {
+                    self.push_block(filtered);
+                }
+            }
+            RawHtmlMode::Strip | RawHtmlMode::Allow => {}
+        }
+    }
+
+    fn push_block(&mut self, html: String) {
+        self.pending.push_back(Event::Start(Tag::HtmlBlock));
+        self.pending.push_back(Event::Html(html.into()));
+        self.pending.push_back(Event::End(TagEnd::HtmlBlock));
+    }
+
+    fn inline(&mut self, html: CowStr<'a>) {
+        if self.is_spoiler(&html) {
+            self.pending.push_back(Event::InlineHtml(html));
+            return;
+        }
+        match &self.mode {
+            RawHtmlMode::Allow => self.pending.push_back(Event::InlineHtml(html)),
+            RawHtmlMode::Escape => self.pending.push_back(Event::Text(html)),
+            RawHtmlMode::Allowlist(tags) => {
+                let filtered = filter_html(&html, tags, &self.url_policy);
+                if !filtered.is_empty() {
+                    self.pending.push_back(Event::InlineHtml(filtered.into()));
+                }
+            }
+            RawHtmlMode::Strip => {}
+        }
+    }
+}
+
+// Keeps the tags named in `tags`, rebuilt with only their allowed attributes,
+// and drops all other markup. Text between tags is kept with stray angle
+// brackets escaped.
+fn filter_html(html: &str, tags: &[String], url_policy: &UrlPolicyFlags) -> String {
+    let mut output = String::with_capacity(html.len());
+    let mut last = 0;
+    for captures in MARKUP_RE.captures_iter(html) {
+        let Some(whole) = captures.get(0) else { continue };
+        push_text(&mut output, &html[last..whole.start()]);
+        last = whole.end();
+        let Some(name) = captures.name("name") else { continue };
+        let name = name.as_str().to_ascii_lowercase();
+        if tags.contains(&name) {
+            output.push_str(&rebuild_tag(&name, &captures, url_policy));
+        }
+    }
+    push_text(&mut output, &html[last..]);
+    output
+}
+
+fn push_text(output: &mut String, text: &str) {
+    output.push_str(&text.replace('<', "&lt;").replace('>', "&gt;"));
+}
+
+fn rebuild_tag(name: &str, captures: &Captures, url_policy: &UrlPolicyFlags) -> String {
+    if captures.name("close").is_some() {
+        return format!("</{name}>");
+    }
+    let mut tag = format!("<{name}");
+    let attributes = captures.name("attrs").map_or("", |m| m.as_str());
+    for attribute in ATTRIBUTE_RE.captures_iter(attributes) {
+        let attribute_name = attribute["name"].to_ascii_lowercase();
+        if !RAW_HTML_ATTRIBUTES.contains(&attribute_name.as_str()) {
+            continue;
+        }
+        let value = ["double", "single", "bare"]
+            .iter()
+            .find_map(|group| attribute.name(group))
+            .map(|m| decode_references(m.as_str()));
+        if let Some(value) = &value {
+            let is_url = matches!(attribute_name.as_str(), "href" | "src" | "cite");
+            if is_url && !url_policy.allows(value, attribute_name == "src") {
+                continue;
+            }
+        }
+        match value {
+            Some(value) => tag.push_str(&format!(" {attribute_name}=\"{}\"", escape_attribute(&value))),
+            None => tag.push_str(&format!(" {attribute_name}")),
+        }
+    }
+    if captures.name("void").is_some() {
+        tag.push_str(" /");
+    }
+    tag.push('>');
+    tag
+}
+
+fn escape_attribute(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('"', "&quot;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+// Decodes numeric character references and the named ones that can hide a
+// URL scheme, so the URL policy sees what the browser would.
+fn decode_references(value: &str) -> String {
+    CHARACTER_REFERENCE_RE
+        .replace_all(value, |captures: &Captures| {
+            let code = if let Some(hex) = captures.name("hex") {
+                u32::from_str_radix(hex.as_str(), 16).ok()
+            } else if let Some(decimal) = captures.name("decimal") {
+                decimal.as_str().parse().ok()
+            } else {
+                match &captures["named"] {
+                    "amp" => Some('&' as u32),
+                    "lt" => Some('<' as u32),
+                    "gt" => Some('>' as u32),
+                    "quot" => Some('"' as u32),
+                    "apos" => Some('\'' as u32),
+                    "colon" => Some(':' as u32),
+                    "Tab" => Some('\t' as u32),
+                    "NewLine" => Some('\n' as u32),
+                    _ => None,
+                }
+            };
+            match code.and_then(char::from_u32) {
+                Some(c) => c.to_string(),
+                None => captures[0].to_string(),
+            }
+        })
+        .into_owned()
+}
@@ -0,0 +1,230 @@
+use crate::components::plugins::Plugin;
+use crate::error::AppError;
+use crate::services::resolver::SourceResolver;
+use lazy_static::lazy_static;
+use pulldown_cmark::{CodeBlockKind, CowStr, Event, Tag, TagEnd};
+use regex::Regex;
+use std::collections::VecDeque;
+use std::path::Path;
+use std::sync::Arc;
+
+lazy_static! {
+    // `<<< @/path/file.rs#region{2-4 rust} [title]`
+    static ref SNIPPET_RE: Regex = Regex::new(
+        r"^<<<\s+(?P<path>[^\s#{\[]+)(?:#(?P<region>[\w-]+))?(?:\{(?P<meta>[^}]*)\})?(?:\s+\[(?P<title>[^\]]+)\])?\s*$"
+    )
+    .unwrap();
+    // `#region name` / `#endregion` markers in the usual comment syntaxes. A
+    // comment prefix or `#` is required, so code such as `region = 1` is not
+    // taken for a marker.
+    static ref REGION_RE: Regex = Regex::new(
+        r"^\s*(?://|#|--|;|<!--|/\*)\s*#?(?P<kind>region|endregion)\b\s*(?P<name>[\w-]*)"
+    )
+    .unwrap();
+    static ref LINE_RANGES_RE: Regex = Regex::new(r"^[\d,\s-]+$").unwrap();
+}
+
+/// Replaces paragraphs made of `<<< path` lines with code blocks holding the
+/// imported files. The language comes from the file extension unless the
+/// braces name one, and `#region` cuts out a named region. A line whose file
+/// cannot be read stays as written; `snippet_problems` reports it.
+pub struct SnippetsPlugin {
+    pub resolver: Arc<dyn SourceResolver>,
+}
+
+impl Plugin for SnippetsPlugin {
+    fn process<'a>(
+        &self,
+        events: Box<dyn Iterator<Item = Event<'a>> + 'a>,
+    ) -> Box<dyn Iterator<Item = Event<'a>> + 'a> {
+        Box::new(SnippetsStream {
+            inner: events,
+            pending: VecDeque::new(),
+            resolver: Arc::clone(&self.resolver),
+        })
+    }
+}
+
+struct SnippetsStream<'a> {
+    inner: Box<dyn Iterator<Item = Event<'a>> + 'a>,
+    pending: VecDeque<Event<'a>>,
+    resolver: Arc<dyn SourceResolver>,
+}
+
+impl<'a> Iterator for SnippetsStream<'a> {
+    type Item = Event<'a>;
+
+    fn next(&mut self) -> Option<Event<'a>> {
+        if let Some(event) = self.pending.pop_front() {
+            return Some(event);
+        }
+
+        let event = self.inner.next()?;
+        if !matches!(event, Event::Start(Tag::Paragraph)) {
+            return Some(event);
+        }
+
+        // Buffer the paragraph; it is replaced only if every line imports.
+        let mut paragraph = vec![event];
+        let mut lines = vec![String::new()];
+        let mut plain = true;
+        for inner in self.inner.by_ref() {
+            match &inner {
+                Event::Text(text) => {
+                    if let Some(line) = lines.last_mut() {
+                        line.push_str(text);
+                    }
+                }
+                Event::SoftBreak => lines.push(String::new()),
+                Event::End(TagEnd::Paragraph) => {}
+                _ => plain = false,
+            }
+            let is_end = matches!(inner, Event::End(TagEnd::Paragraph));
+            paragraph.push(inner);
+            if is_end {
+                break;
+            }
+        }
+
+        let snippets: Option<Vec<Snippet>> = plain
+            .then(|| lines.iter().map(|line| Snippet::parse(line)).collect())
+            .flatten();
+        match snippets {
+            Some(snippets) => {
+                for (snippet, line) in snippets.iter().zip(lines) {
+                    self.pending.extend(snippet.events(line, self.resolver.as_ref()));
+                }
+            }
+            None => self.pending.extend(paragraph),
+        }
+        self.pending.pop_front()
+    }
+}
+
+/// The imports that cannot be read in a paragraph made of `<<< path` lines,
+/// with the index of the line each is on. Empty unless every line imports.
+pub fn snippet_problems(lines: &[&str], resolver: &dyn SourceResolver) -> Vec<(usize, AppError)> {
+    let Some(snippets) = lines.iter().map(|line| Snippet::parse(line)).collect::<Option<Vec<_>>>() else {
+        return Vec::new();
+    };
+    snippets
+        .iter()
+        .enumerate()
+        .filter_map(|(index, snippet)| snippet.read(resolver).err().map(|e| (index, e)))
+        .collect()
+}
+
+struct Snippet {
+    path: String,
+    region: Option<String>,
+    lang: Option<String>,
+    lines: Option<String>,
+    title: Option<String>,
+}
+
+impl Snippet {
+    fn parse(line: &str) -> Option<Self> {
+        let captures = SNIPPET_RE.captures(line.trim())?;
+        let mut snippet = Snippet {
+            path: captures["path"].to_string(),
+            region: captures.name("region").map(|m| m.as_str().to_string()),
+            lang: None,
+            lines: None,
+            title: captures.name("title").map(|m| m.as_str().trim().to_string()),
+        };
+        // The braces hold line ranges, a language, or both: `{2-4 rust}`.
+        for part in captures.name("meta").map_or("", |m| m.as_str()).split_whitespace() {
+            if LINE_RANGES_RE.is_match(part) {
+                snippet.lines = Some(part.to_string());
+            } else {
+                snippet.lang = Some(part.to_string());
+            }
+        }
+        if snippet.lang.is_none() {
+            snippet.lang = Path::new(&snippet.path)
+                .extension()
+                .map(|ext| ext.to_string_lossy().to_string());
+        }
+        Some(snippet)
+    }
+
+    fn info_string(&self) -> String {
+        let mut info = self.lang.clone().unwrap_or_default();
+        if let Some(lines) = &self.lines {
+            info.push_str(&format!(" {{{lines}}}"));
+        }
+        if let Some(title) = &self.title {
+            info.push_str(&format!(" title=\"{}\"", title.replace('"', "")));
+        }
+        info
+    }
+
+    fn events<'a>(&self, line: String, resolver: &dyn SourceResolver) -> Vec<Event<'a>> {
+        match self.read(resolver) {
+            Ok(code) => vec![
+                Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(CowStr::from(self.info_string())))),
+                Event::Text(CowStr::from(code)),
+                Event::End(TagEnd::CodeBlock),
+            ],
+            Err(_) => vec![
+                Event::Start(Tag::Paragraph),
+                Event::Text(CowStr::from(line)),
+                Event::End(TagEnd::Paragraph),
+            ],
+        }
+    }
+
+    fn read(&self, resolver: &dyn SourceResolver) -> Result<String, AppError> {
+        let source = resolver.read(&self.path).map_err(AppError::for_snippet)?;
+        let code = match &self.region {
+            Some(region) => extract_region(&source, region).ok_or_else(|| AppError::RegionNotFound {
+                region: region.clone(),
+                path: self.path.clone(),
+            })?,
+            None => source,
+        };
+        let mut code = code.trim_end_matches('\n').to_string();
+        code.push('\n');
+        Ok(code)
+    }
+}
+
+// The lines between `#region name` and its `#endregion`, without the markers
+// of regions nested inside and dedented to the least indented line.
+fn extract_region(source: &str, name: &str) -> Option<String> {
+    let mut lines = Vec::new();
+    let mut depth = 0;
+    let mut found = false;
+
+    for line in source.lines() {
+        let marker = REGION_RE.captures(line);
+        let kind = marker.as_ref().map(|m| m.name("kind").map_or("", |k| k.as_str()));
+        if !found {
+            if kind == Some("region") && marker.as_ref().is_some_and(|m| &m["name"] == name) {
+                found = true;
+            }
+            continue;
+        }
+        match kind {
+            Some("region") => depth += 1,
+            Some("endregion") if depth == 0 => return Some(dedent(&lines)),
+            Some("endregion") => depth -= 1,
+            _ => lines.push(line),
+        }
+    }
+    found.then(|| dedent(&lines))
+}
+
+fn dedent(lines: &[&str]) -> String {
+    let indent = lines
+        .iter()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| line.len() - line.trim_start().len())
+        .min()
+        .unwrap_or(0);
+    lines
+        .iter()
+        .map(|line| line.get(indent..).unwrap_or_else(|| line.trim_start()))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
@@ -1,34 +1,36 @@
-use pulldown_cmark::{Event, Tag, TagEnd};
 use crate::components::plugins::Plugin;
-use crate::config::RenderFlags;
+use pulldown_cmark::Event;
 
+/// Renders `<spoiler>` blocks as collapsed `<details>` elements and inline
+/// `<spoiler>` tags as `<span class="spoiler">`.
 pub struct SpoilerPlugin;
 
 impl Plugin for SpoilerPlugin {
-    fn process<'a>(&self, _flags: RenderFlags, events: &mut Vec<Event<'a>>) -> bool {
-        let mut new_events = Vec::with_capacity(events.len());
-        let mut in_spoiler = false;
-        let mut changed = false;
+    fn process<'a>(
+        &self,
+        events: Box<dyn Iterator<Item = Event<'a>> + 'a>,
+    ) -> Box<dyn Iterator<Item = Event<'a>> + 'a> {
+        let mut block_depth = 0usize;
+        let mut inline_depth = 0usize;
 
-        for event in events.drain(..) {
-            match &event {
-                Event::Html(html) if html.trim() == "<spoiler>" => {
-                    in_spoiler = true;
-                    changed = true;
-                    new_events.push(Event::Start(Tag::HtmlBlock));
-                    new_events.push(Event::Html("<details><summary>Spoiler</summary>".into()));
-                }
-                Event::Html(html) if html.trim() == "</spoiler>" && in_spoiler => {
-                    in_spoiler = false;
-                    new_events.push(Event::Html("</details>".into()));
-                    new_events.push(Event::End(TagEnd::HtmlBlock));
-                }
-                _ => {
-                    new_events.push(event);
-                }
+        Box::new(events.map(move |event| match event {
+            Event::Html(ref html) if html.trim() == "<spoiler>" => {
+                block_depth += 1;
+                Event::Html("<details class=\"spoiler\"><summary>Spoiler</summary>\n".into())
             }
-        }
-        *events = new_events;
-        changed
+            Event::Html(ref html) if html.trim() == "</spoiler>" && block_depth > 0 => {
+                block_depth -= 1;
+                Event::Html("</details>\n".into())
+            }
+            Event::InlineHtml(ref html) if html.as_ref() == "<spoiler>" => {
+                inline_depth += 1;
+                Event::InlineHtml("<span class=\"spoiler\">".into())
+            }
+            Event::InlineHtml(ref html) if html.as_ref() == "</spoiler>" && inline_depth > 0 => {
+                inline_depth -= 1;
+                Event::InlineHtml("</span>".into())
+            }
+            _ => event,
+        }))
     }
 }
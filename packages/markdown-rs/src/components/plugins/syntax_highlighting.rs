@@ -1,48 +1,91 @@
-use pulldown_cmark::{Event, Tag, CodeBlockKind, TagEnd};
-use syntect::parsing::SyntaxSet;
-use syntect::highlighting::{ThemeSet};
-use syntect::html::{ClassedHTMLGenerator, ClassStyle};
-use lazy_static::lazy_static;
+use pulldown_cmark::{CodeBlockKind, Event, Tag, TagEnd};
+use std::collections::VecDeque;
+use crate::components::code_meta::{split_inline_lang, CodeMeta};
 use crate::components::plugins::Plugin;
+use crate::config::HighlightFlags;
+use crate::services::highlighter::{
+    highlight_code_block, highlight_inline_code, plain_code_block, plain_inline_code,
+};
 
-lazy_static! {
-    static ref SYNTAX_SET: SyntaxSet = SyntaxSet::load_defaults_newlines();
-    static ref THEME_SET: ThemeSet = ThemeSet::load_defaults();
+/// Replaces fenced code blocks with highlighted HTML, applying the title,
+/// line numbers and line highlights of the info string. Indented code blocks
+/// have no language and are left to the HTML writer. Inline code ending in a
+/// `{:lang}` suffix is highlighted as `lang` and loses the suffix.
+///
+/// Without `highlight`, code keeps its plain text: the info string still
+/// applies, but blocks that ask for none of it are left to the HTML writer.
+pub struct SyntaxHighlightingPlugin {
+    pub flags: HighlightFlags,
+    pub highlight: bool,
 }
 
-pub struct SyntaxHighlightingPlugin;
-
 impl Plugin for SyntaxHighlightingPlugin {
     fn process<'a>(
         &self,
         events: Box<dyn Iterator<Item = Event<'a>> + 'a>,
     ) -> Box<dyn Iterator<Item = Event<'a>> + 'a> {
-        let mut in_code_block = false;
-        let mut lang = String::new();
-
-        Box::new(events.flat_map(move |event| {
-            match event {
-                Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(ref lang_str))) => {
-                    in_code_block = true;
-                    lang = lang_str.to_string();
-                    vec![event]
-                }
-                Event::End(TagEnd::CodeBlock) => {
-                    in_code_block = false;
-                    vec![event]
-                }
-                Event::Text(text) if in_code_block => {
-                    let syntax = SYNTAX_SET.find_syntax_by_token(&lang).unwrap_or_else(|| SYNTAX_SET.find_syntax_plain_text());
-                    let mut html_generator = ClassedHTMLGenerator::new_with_class_style(syntax, &SYNTAX_SET, ClassStyle::Spaced);
+        Box::new(HighlightStream {
+            inner: events,
+            pending: VecDeque::new(),
+            flags: self.flags.clone(),
+            highlight: self.highlight,
+        })
+    }
+}
+
+struct HighlightStream<'a> {
+    inner: Box<dyn Iterator<Item = Event<'a>> + 'a>,
+    pending: VecDeque<Event<'a>>,
+    flags: HighlightFlags,
+    highlight: bool,
+}
 
-                    for line in text.lines() {
-                        let _ = html_generator.parse_html_for_line_which_includes_newline(line);
-                    }
+impl<'a> Iterator for HighlightStream<'a> {
+    type Item = Event<'a>;
 
-                    vec![Event::Html(html_generator.finalize().into())]
+    fn next(&mut self) -> Option<Event<'a>> {
+        if let Some(event) = self.pending.pop_front() {
+            return Some(event);
+        }
+
+        let event = self.inner.next()?;
+        if let Event::Code(code) = &event {
+            return Some(match split_inline_lang(code) {
+                Some((code, lang)) if self.highlight => {
+                    Event::InlineHtml(highlight_inline_code(lang, code, &self.flags).into())
                 }
-                _ => vec![event],
+                Some((code, lang)) => Event::InlineHtml(plain_inline_code(lang, code).into()),
+                None => event,
+            });
+        }
+        let Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(info))) = &event else {
+            return Some(event);
+        };
+        let meta = CodeMeta::parse(info);
+
+        let mut code = String::new();
+        for inner in self.inner.by_ref() {
+            let is_end = matches!(inner, Event::End(TagEnd::CodeBlock));
+            if let Event::Text(text) = &inner {
+                code.push_str(text);
+            }
+            if !self.highlight {
+                self.pending.push_back(inner);
+            }
+            if is_end {
+                break;
+            }
+        }
+
+        if self.highlight {
+            return Some(Event::Html(highlight_code_block(&meta, &code, &self.flags).into()));
+        }
+        match plain_code_block(&meta, &code, &self.flags) {
+            Some(html) => {
+                self.pending.clear();
+                Some(Event::Html(html.into()))
             }
-        }))
+            None => Some(event),
+        }
     }
 }
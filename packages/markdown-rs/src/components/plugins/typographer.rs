@@ -1,27 +1,109 @@
 use crate::components::plugins::Plugin;
-use crate::config::RenderFlags;
-use pulldown_cmark::Event;
+use pulldown_cmark::{CowStr, Event, LinkType, Tag, TagEnd};
 
+/// Replaces straight quotes, dashes and ellipses with their typographic forms.
+/// Code, raw HTML and autolinked URLs are left untouched.
 pub struct TypographerPlugin;
 
 impl Plugin for TypographerPlugin {
-    fn process<'a>(&self, flags: RenderFlags, events: &mut Vec<Event<'a>>) -> bool {
-        if !flags.smart_punctuation {
-            return false;
-        }
+    fn process<'a>(
+        &self,
+        events: Box<dyn Iterator<Item = Event<'a>> + 'a>,
+    ) -> Box<dyn Iterator<Item = Event<'a>> + 'a> {
+        let mut skip_depth = 0usize;
+        let mut autolinks: Vec<bool> = Vec::new();
+        // Last character seen in the current block, used to tell opening
+        // quotes from closing ones across inline boundaries.
+        let mut prev: Option<char> = None;
 
-        for event in events.iter_mut() {
-            if let Event::Text(text) = event {
-                let mut new_text = text.to_string();
-                new_text = new_text.replace("---", "—");
-                new_text = new_text.replace("--", "–");
-                new_text = new_text.replace("...", "…");
-                new_text = new_text.replace("'", "’");
-                new_text = new_text.replace("\"", "”");
-                *text = new_text.into();
+        Box::new(events.map(move |event| {
+            match &event {
+                Event::Start(Tag::CodeBlock(_) | Tag::MetadataBlock(_)) => skip_depth += 1,
+                Event::End(TagEnd::CodeBlock | TagEnd::MetadataBlock(_)) => {
+                    skip_depth = skip_depth.saturating_sub(1)
+                }
+                Event::Start(Tag::Link { link_type, .. }) => {
+                    let is_autolink = matches!(link_type, LinkType::Autolink | LinkType::Email);
+                    autolinks.push(is_autolink);
+                    if is_autolink {
+                        skip_depth += 1;
+                    }
+                }
+                Event::End(TagEnd::Link) => {
+                    let was_autolink = autolinks.pop() == Some(true);
+                    skip_depth = skip_depth.saturating_sub(usize::from(was_autolink));
+                }
+                Event::Start(
+                    Tag::Paragraph
+                    | Tag::Heading { .. }
+                    | Tag::Item
+                    | Tag::TableCell
+                    | Tag::BlockQuote(_),
+                ) => prev = None,
+                Event::Code(code) => prev = code.chars().last(),
+                Event::SoftBreak | Event::HardBreak => prev = Some(' '),
+                Event::Text(text) if skip_depth == 0 => {
+                    let replaced = smarten(text, &mut prev);
+                    return Event::Text(CowStr::from(replaced));
+                }
+                _ => {}
             }
-        }
+            event
+        }))
+    }
+}
+
+fn smarten(text: &str, prev: &mut Option<char>) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        let replacement = match c {
+            '.' if chars.peek() == Some(&'.') => {
+                let mut rest = chars.clone();
+                rest.next();
+                if rest.peek() == Some(&'.') {
+                    chars.next();
+                    chars.next();
+                    '…'
+                } else {
+                    c
+                }
+            }
+            '-' if chars.peek() == Some(&'-') => {
+                chars.next();
+                if chars.peek() == Some(&'-') {
+                    chars.next();
+                    '—'
+                } else {
+                    '–'
+                }
+            }
+            '"' => {
+                if opens_quote(*prev) {
+                    '“'
+                } else {
+                    '”'
+                }
+            }
+            '\'' => {
+                if opens_quote(*prev) {
+                    '‘'
+                } else {
+                    '’'
+                }
+            }
+            _ => c,
+        };
+        out.push(replacement);
+        *prev = Some(replacement);
+    }
+    out
+}
 
-        true
+fn opens_quote(prev: Option<char>) -> bool {
+    match prev {
+        None => true,
+        Some(c) => c.is_whitespace() || matches!(c, '(' | '[' | '{' | '–' | '—' | '“' | '‘'),
     }
 }
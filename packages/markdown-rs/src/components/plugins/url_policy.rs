@@ -0,0 +1,91 @@
+use crate::components::plugins::Plugin;
+use crate::config::UrlPolicyFlags;
+use pulldown_cmark::{Event, LinkType, Tag, TagEnd};
+
+/// Checks the destination of every link and image against the URL policy.
+/// A link that fails it is unwrapped to its text, an image replaced by its
+/// alt text.
+pub struct UrlPolicyPlugin {
+    pub policy: UrlPolicyFlags,
+}
+
+impl Plugin for UrlPolicyPlugin {
+    fn process<'a>(
+        &self,
+        events: Box<dyn Iterator<Item = Event<'a>> + 'a>,
+    ) -> Box<dyn Iterator<Item = Event<'a>> + 'a> {
+        let policy = self.policy.clone();
+        // Whether each open link or image was kept, innermost last.
+        let mut open: Vec<bool> = Vec::new();
+
+        Box::new(events.filter(move |event| match event {
+            Event::Start(Tag::Link { link_type, dest_url, .. }) => {
+                // The HTML writer adds the `mailto:` of email autolinks.
+                let allowed = if *link_type == LinkType::Email {
+                    policy.allows_scheme("mailto")
+                } else {
+                    policy.allows(dest_url, false)
+                };
+                open.push(allowed);
+                allowed
+            }
+            Event::Start(Tag::Image { dest_url, .. }) => {
+                let allowed = policy.allows(dest_url, true);
+                open.push(allowed);
+                allowed
+            }
+            Event::End(TagEnd::Link | TagEnd::Image) => open.pop().unwrap_or(true),
+            _ => true,
+        }))
+    }
+}
+
+impl UrlPolicyFlags {
+    /// Whether `url` may be linked to, or used as an image source if `image`.
+    pub fn allows(&self, url: &str, image: bool) -> bool {
+        let url = normalize_url(url);
+        let Some(scheme) = scheme(&url) else {
+            // Protocol-relative URLs leave the site as much as absolute ones,
+            // and browsers read a backslash among the leading pair as a slash.
+            let protocol_relative = url.len() >= 2 && url.bytes().take(2).all(|b| matches!(b, b'/' | b'\\'));
+            return !(self.relative_only && protocol_relative);
+        };
+        if image && scheme == "data" && self.allows_data_image(&url) {
+            return true;
+        }
+        self.allows_scheme(&scheme)
+    }
+
+    fn allows_scheme(&self, scheme: &str) -> bool {
+        !self.relative_only && self.allowed_schemes.iter().any(|allowed| allowed == scheme)
+    }
+
+    fn allows_data_image(&self, url: &str) -> bool {
+        let media_type = url["data:".len()..]
+            .split([';', ','])
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_ascii_lowercase();
+        self.data_images.contains(&media_type)
+    }
+}
+
+// Browsers drop leading and trailing control characters and spaces, and tabs
+// and newlines anywhere, before reading the scheme: `java\tscript:` is
+// `javascript:`.
+fn normalize_url(url: &str) -> String {
+    url.trim_matches(|c: char| c <= ' ')
+        .chars()
+        .filter(|c| !matches!(c, '\t' | '\n' | '\r'))
+        .collect()
+}
+
+// The lowercased scheme of an absolute URL, `None` for a relative one.
+fn scheme(url: &str) -> Option<String> {
+    let end = url.find(':')?;
+    let scheme = &url[..end];
+    let valid = scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+        && scheme.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'));
+    valid.then(|| scheme.to_ascii_lowercase())
+}
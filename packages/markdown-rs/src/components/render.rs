@@ -1,32 +1,145 @@
-use pulldown_cmark::html;
+use pulldown_cmark::{html, Event, Tag, TagEnd, TextMergeStream, TextMergeWithOffset};
 use crate::config::RenderFlags;
-use crate::adapters::pulldown_cmark::parser::create_parser;
+use crate::adapters::pulldown_cmark::parser::{create_checked_parser, create_parser};
+use crate::components::diagnostics::DiagnosticsCollector;
+use crate::components::document::{DocumentCollector, DocumentSummary};
+use crate::error::AppResult;
+use crate::components::events::events_from_ast;
+use crate::components::plugins::attributes::paragraph_attributes;
+use crate::components::plugins::{code_plugin, enabled_plugins};
+use crate::components::plugins::url_policy::UrlPolicyPlugin;
 use crate::components::plugins::Plugin;
+use crate::services::sanitizer::{sanitize, DocumentMarkup};
+use crate::types::{Diagnostic, Node};
+use crate::utils::slug::Slugger;
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
 
-pub fn render_to_html_string(input: &str, flags: RenderFlags) -> String {
-    let mut html_output = String::with_capacity(input.len() * 2);
-    let parser = create_parser(input, flags);
+pub fn render_to_html_string(input: &str, flags: RenderFlags) -> AppResult<String> {
+    let parser = create_parser(input, &flags);
+    render_events(parser, input.len() * 2, flags, |_| {})
+}
 
-    // Fast path for when no plugins are enabled and no sanitization is needed
-    if !flags.syntax_highlight && !flags.toc && !flags.directives && !flags.sanitize {
-        html::push_html(&mut html_output, parser);
-        return html_output;
-    }
+/// Renders markdown and collects the page data described by
+/// [`DocumentSummary`] and the diagnostics from the same event stream.
+pub fn render_document(input: &str, flags: RenderFlags) -> AppResult<(String, DocumentSummary, Vec<Diagnostic>)> {
+    let mut collector = DocumentCollector::new(flags.slug.clone());
+    let mut diagnostics = DiagnosticsCollector::new(input, &flags);
+    let (parser, broken) = create_checked_parser(input, &flags);
+    let events = TextMergeWithOffset::new(parser).map(|(event, range)| {
+        diagnostics.observe(&event, range);
+        event
+    });
+    let html = render_events(events, input.len() * 2, flags, |event| collector.observe(event))?;
+    let diagnostics = diagnostics.finish(&broken.borrow());
+    Ok((html, collector.finish(), diagnostics))
+}
+
+/// Renders an AST, e.g. one returned by `parse()` and modified by the caller,
+/// through the same plugins and sanitization as markdown input.
+pub fn render_node(node: &Node, flags: RenderFlags) -> AppResult<String> {
+    let events = events_from_ast(node);
+    let capacity = events.len() * 16;
+    render_events(events.into_iter(), capacity, flags, |_| {})
+}
 
-    // Slower path: process events through a streaming pipeline
-    let mut stream: Box<dyn Iterator<Item = _>> = Box::new(parser);
+fn render_events<'a>(
+    events: impl Iterator<Item = Event<'a>> + 'a,
+    capacity: usize,
+    flags: RenderFlags,
+    observe: impl FnMut(&Event<'a>) + 'a,
+) -> AppResult<String> {
+    let mut html_output = String::with_capacity(capacity);
+    let slugger = Arc::new(Mutex::new(Slugger::new(flags.slug.clone())));
+    let plugins = enabled_plugins(&flags, &slugger);
+    // Code and links are rendered after every plugin, so the code and links
+    // they add are handled too.
+    let code = code_plugin(&flags);
+    let url_policy = UrlPolicyPlugin {
+        policy: flags.url_policy.clone(),
+    };
 
-    if flags.toc {
-        let plugin = crate::components::plugins::toc::TocPlugin;
-        stream = plugin.process(stream);
+    // Adjacent text events are merged first so plugins can match syntax the
+    // parser splits up, which only matters when there are plugins to run.
+    let mut stream: Box<dyn Iterator<Item = _>> = if plugins.is_empty() && !flags.sanitize {
+        Box::new(events)
+    } else {
+        Box::new(TextMergeStream::new(events))
+    };
+
+    // The ids and classes the markdown sets, which the sanitizer keeps.
+    // `{#id .class}` blocks are read before the attributes plugin turns them
+    // into HTML, heading ids after they are generated.
+    let markup = Rc::new(RefCell::new(MarkupCollector::default()));
+    if flags.sanitize && flags.attributes {
+        let markup = Rc::clone(&markup);
+        stream = Box::new(stream.inspect(move |event| markup.borrow_mut().observe_paragraph(event)));
     }
 
-    if flags.syntax_highlight {
-        let plugin = crate::components::plugins::syntax_highlighting::SyntaxHighlightingPlugin;
+    for plugin in plugins {
         stream = plugin.process(stream);
     }
+    // Code rendering may turn inline code into HTML, so observers see inline
+    // code just before it.
+    let observe = Rc::new(RefCell::new(observe));
+    {
+        let observe = Rc::clone(&observe);
+        stream = Box::new(stream.inspect(move |event| {
+            if let Event::Code(_) = event {
+                (observe.borrow_mut())(event);
+            }
+        }));
+    }
+    stream = code.process(stream);
+    stream = url_policy.process(stream);
 
-    html::push_html(&mut html_output, stream);
+    // Observers see the final events, including those added by plugins.
+    let headings = Rc::clone(&markup);
+    html::push_html(
+        &mut html_output,
+        stream.inspect(move |event| {
+            headings.borrow_mut().observe_heading(event);
+            if !matches!(event, Event::Code(_)) {
+                (observe.borrow_mut())(event);
+            }
+        }),
+    );
 
-    html_output
+    // Sanitization always runs last so nothing emitted by a plugin escapes it.
+    // Paragraph ids may have been changed to keep them unique.
+    let mut markup = markup.take().markup;
+    let slugger = slugger.lock().unwrap_or_else(|e| e.into_inner());
+    markup.ids.extend(slugger.ids().map(str::to_string));
+    sanitize(html_output, &flags, markup)
+}
+
+#[derive(Default)]
+struct MarkupCollector {
+    markup: DocumentMarkup,
+    // The last text of the paragraph being read, if nothing else came after it.
+    paragraph_end: Option<String>,
+}
+
+impl MarkupCollector {
+    fn observe_paragraph(&mut self, event: &Event) {
+        match event {
+            Event::Text(text) => self.paragraph_end = Some(text.to_string()),
+            Event::End(TagEnd::Paragraph) => {
+                let attributes = self.paragraph_end.take().and_then(|text| paragraph_attributes(&text));
+                if let Some((id, classes)) = attributes {
+                    self.markup.ids.extend(id);
+                    self.markup.classes.extend(classes);
+                }
+            }
+            _ => self.paragraph_end = None,
+        }
+    }
+
+    fn observe_heading(&mut self, event: &Event) {
+        if let Event::Start(Tag::Heading { id, classes, .. }) = event {
+            self.markup.ids.extend(id.as_ref().map(|id| id.to_string()));
+            self.markup.classes.extend(classes.iter().map(|class| class.to_string()));
+        }
+    }
 }
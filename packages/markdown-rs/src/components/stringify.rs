@@ -0,0 +1,989 @@
+use crate::config::{FormatFlags, HeadingStyle};
+use crate::types::{Alignment, BlockQuoteKind, LinkType, MetadataBlockKind, Node, NodeType};
+use lazy_static::lazy_static;
+use regex::Regex;
+use std::collections::HashSet;
+use std::iter;
+use std::ops::Range;
+
+lazy_static! {
+    // Words that would start a block construct if wrapped onto a new line.
+    static ref BLOCK_START_RE: Regex =
+        Regex::new(r"^(#{1,6}|[-+*]|\d{1,9}[.)]|=+|-+|>.*|:|\[[^\]]*\]:.*|\+{3}.*)$").unwrap();
+    static ref HEADING_START_RE: Regex = Regex::new(r"^#{1,6}(\s|$)").unwrap();
+    static ref BULLET_START_RE: Regex = Regex::new(r"^[-+*](\s|$)").unwrap();
+    static ref ORDERED_START_RE: Regex = Regex::new(r"^\d{1,9}[.)](\s|$)").unwrap();
+    static ref SETEXT_LINE_RE: Regex = Regex::new(r"^(=+|-+)\s*$").unwrap();
+    static ref DEFINITION_START_RE: Regex = Regex::new(r"^\[[^\]]*\]:").unwrap();
+    static ref ENTITY_RE: Regex =
+        Regex::new(r"^&(#[0-9]{1,7}|#[xX][0-9a-fA-F]{1,6}|[A-Za-z][A-Za-z0-9]{1,31});").unwrap();
+    // Admonition fences and leaf directives stay on their own lines.
+    static ref PROTECTED_LINE_RE: Regex = Regex::new(r"^(:::|::[A-Za-z])").unwrap();
+}
+
+/// Serializes an AST back into CommonMark/GFM text.
+pub fn stringify(node: &Node, flags: &FormatFlags) -> String {
+    let mut writer = Writer::new(flags);
+    writer.collect_definitions(node);
+
+    let blocks = match node.r#type {
+        NodeType::Document => node.children.as_slice(),
+        _ => std::slice::from_ref(node),
+    };
+    let mut out = writer.blocks(blocks, flags.line_width, false);
+
+    let definitions = writer.definitions();
+    if !definitions.is_empty() {
+        if !out.is_empty() {
+            out.push_str("\n\n");
+        }
+        out.push_str(&definitions);
+    }
+    if !out.is_empty() {
+        out.push('\n');
+    }
+    out
+}
+
+#[derive(Clone, Copy, Default)]
+struct InlineContext {
+    // Soft breaks may be reflowed by the paragraph wrapper.
+    wrap: bool,
+    // Line breaks are not allowed (headings and table cells).
+    single_line: bool,
+    in_table: bool,
+}
+
+// Where the paragraph being wrapped may and may not break, as sorted byte
+// offsets of spaces in its text. They are kept beside the text so that no
+// character of the input can be mistaken for one.
+#[derive(Default)]
+struct Breaks {
+    // Soft line breaks, which the wrapper reflows.
+    soft: Vec<usize>,
+    // Spaces that must not become line breaks.
+    kept: Vec<usize>,
+}
+
+impl Breaks {
+    // Takes the breaks from `at` on, relative to `at`.
+    fn split_off(&mut self, at: usize) -> Breaks {
+        let split = |offsets: &mut Vec<usize>| {
+            let index = offsets.partition_point(|&offset| offset < at);
+            offsets.split_off(index).into_iter().map(|offset| offset - at).collect()
+        };
+        Breaks {
+            soft: split(&mut self.soft),
+            kept: split(&mut self.kept),
+        }
+    }
+
+    // Adds `other`, whose text starts at `at`.
+    fn append(&mut self, other: &Breaks, at: usize) {
+        self.soft.extend(other.soft.iter().map(|offset| offset + at));
+        self.kept.extend(other.kept.iter().map(|offset| offset + at));
+    }
+
+    // Pushes `text`, none of whose spaces may break.
+    fn push_kept(&mut self, out: &mut String, text: &str) {
+        let start = out.len();
+        self.kept
+            .extend(text.match_indices(' ').map(|(offset, _)| start + offset));
+        out.push_str(text);
+    }
+}
+
+struct Writer<'a> {
+    flags: &'a FormatFlags,
+    // Reference definitions in first-use order, with their lowercase labels.
+    definitions: Vec<(String, String, String)>,
+    labels: HashSet<String>,
+    breaks: Breaks,
+}
+
+impl<'a> Writer<'a> {
+    fn new(flags: &'a FormatFlags) -> Self {
+        Self {
+            flags,
+            definitions: Vec::new(),
+            labels: HashSet::new(),
+            breaks: Breaks::default(),
+        }
+    }
+
+    fn collect_definitions(&mut self, node: &Node) {
+        if let NodeType::Link { link_type, dest_url, title, id }
+        | NodeType::Image { link_type, dest_url, title, id } = &node.r#type
+        {
+            let is_reference = matches!(
+                link_type,
+                LinkType::Reference | LinkType::Collapsed | LinkType::Shortcut
+            );
+            if is_reference && !id.is_empty() && self.labels.insert(id.to_lowercase()) {
+                self.definitions.push((id.clone(), dest_url.clone(), title.clone()));
+            }
+        }
+        for child in &node.children {
+            self.collect_definitions(child);
+        }
+    }
+
+    fn definitions(&self) -> String {
+        self.definitions
+            .iter()
+            .map(|(label, dest, title)| {
+                let mut line = format!("[{}]: {}", label, destination(dest));
+                if !title.is_empty() {
+                    line.push(' ');
+                    line.push_str(&link_title(title));
+                }
+                line
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    // Serializes a sequence of sibling blocks. Runs of inline nodes (as found
+    // in tight list items) are written as a single paragraph.
+    fn blocks(&mut self, nodes: &[Node], width: Option<usize>, tight: bool) -> String {
+        let mut parts = Vec::new();
+        let mut previous_list: Option<(bool, char)> = None;
+        let mut i = 0;
+
+        while i < nodes.len() {
+            if !is_block(&nodes[i].r#type) {
+                let start = i;
+                while i < nodes.len() && !is_block(&nodes[i].r#type) {
+                    i += 1;
+                }
+                parts.push(self.paragraph(&nodes[start..i], width));
+                previous_list = None;
+                continue;
+            }
+
+            let node = &nodes[i];
+            if let NodeType::List { start } = node.r#type {
+                let ordered = start.is_some();
+                let mut marker = if ordered {
+                    self.flags.ordered_delimiter
+                } else {
+                    self.flags.bullet
+                };
+                // Adjacent lists with the same marker would merge into one.
+                if previous_list == Some((ordered, marker)) {
+                    marker = alternate_marker(marker);
+                }
+                previous_list = Some((ordered, marker));
+                parts.push(self.list(node, start, marker, width));
+            } else {
+                previous_list = None;
+                parts.push(self.block(node, width));
+            }
+            i += 1;
+        }
+
+        parts.join(if tight { "\n" } else { "\n\n" })
+    }
+
+    fn block(&mut self, node: &Node, width: Option<usize>) -> String {
+        match &node.r#type {
+            NodeType::Paragraph => self.paragraph(&node.children, width),
+            NodeType::Heading { level, id, classes, attrs } => {
+                let context = InlineContext {
+                    single_line: true,
+                    ..Default::default()
+                };
+                let mut content = self.inline(&node.children, context);
+                let attributes = heading_attributes(id.as_deref(), classes, attrs);
+                if !attributes.is_empty() {
+                    content.push(' ');
+                    content.push_str(&attributes);
+                }
+
+                if self.flags.heading_style == HeadingStyle::Setext
+                    && *level <= 2
+                    && !content.is_empty()
+                {
+                    let underline = if *level == 1 { '=' } else { '-' };
+                    let length = content.chars().count().max(3);
+                    return format!("{}\n{}", content, underline.to_string().repeat(length));
+                }
+
+                if attributes.is_empty() && content.ends_with('#') {
+                    content.pop();
+                    content.push_str("\\#");
+                }
+                let hashes = "#".repeat((*level).clamp(1, 6) as usize);
+                if content.is_empty() {
+                    hashes
+                } else {
+                    format!("{} {}", hashes, content)
+                }
+            }
+            NodeType::BlockQuote { kind } => {
+                let mut content = self.blocks(&node.children, narrow(width, 2), false);
+                if let Some(kind) = kind {
+                    let marker = format!("[!{}]", block_quote_kind(*kind));
+                    content = if content.is_empty() {
+                        marker
+                    } else {
+                        format!("{}\n{}", marker, content)
+                    };
+                }
+                prefix_lines(&content, "> ", ">")
+            }
+            NodeType::List { start } => {
+                let marker = if start.is_some() {
+                    self.flags.ordered_delimiter
+                } else {
+                    self.flags.bullet
+                };
+                self.list(node, *start, marker, width)
+            }
+            NodeType::CodeBlock { lang, meta, indented } => {
+                let content = text_content(&node.children);
+                if *indented {
+                    return indent_lines(content.trim_end_matches('\n'), "    ", "    ");
+                }
+                code_fence(&content, lang.as_deref(), meta.as_deref())
+            }
+            NodeType::HtmlBlock | NodeType::Html => {
+                let content = match node.content.as_deref() {
+                    Some(content) => content.to_string(),
+                    None => text_content(&node.children),
+                };
+                content.trim_end_matches('\n').to_string()
+            }
+            NodeType::Rule => "---".to_string(),
+            NodeType::Table(alignments) => self.table(node, alignments),
+            NodeType::FootnoteDefinition { label } => {
+                let content = self.blocks(&node.children, narrow(width, 4), false);
+                indent_lines(&content, &format!("[^{}]: ", label), "    ")
+            }
+            NodeType::DefinitionList => self.definition_list(node, width),
+            NodeType::MetadataBlock { kind } => {
+                let fence = match kind {
+                    MetadataBlockKind::YamlStyle => "---",
+                    MetadataBlockKind::PlusesStyle => "+++",
+                };
+                let mut content = text_content(&node.children);
+                if !content.is_empty() && !content.ends_with('\n') {
+                    content.push('\n');
+                }
+                format!("{}\n{}{}", fence, content, fence)
+            }
+            _ => self.blocks(&node.children, width, false),
+        }
+    }
+
+    fn list(&mut self, node: &Node, start: Option<u64>, marker: char, width: Option<usize>) -> String {
+        // pulldown-cmark only wraps item content in paragraphs for loose lists.
+        let loose = node.children.iter().any(|item| {
+            item.children
+                .iter()
+                .any(|child| matches!(child.r#type, NodeType::Paragraph))
+        });
+
+        let items: Vec<String> = node
+            .children
+            .iter()
+            .enumerate()
+            .map(|(index, item)| {
+                let bullet = match start {
+                    Some(start) => format!("{}{}", start + index as u64, marker),
+                    None => marker.to_string(),
+                };
+                let indent = " ".repeat(bullet.len() + 1);
+
+                let (task, children) = match item.children.first().map(|c| &c.r#type) {
+                    Some(NodeType::TaskListMarker { checked }) => (
+                        Some(if *checked { "[x]" } else { "[ ]" }),
+                        &item.children[1..],
+                    ),
+                    _ => (None, item.children.as_slice()),
+                };
+
+                let mut content = self.blocks(children, narrow(width, indent.len()), !loose);
+                if let Some(task) = task {
+                    content = if content.is_empty() {
+                        task.to_string()
+                    } else {
+                        format!("{} {}", task, content)
+                    };
+                }
+                if content.is_empty() {
+                    return bullet;
+                }
+                indent_lines(&content, &format!("{} ", bullet), &indent)
+            })
+            .collect();
+
+        items.join(if loose { "\n\n" } else { "\n" })
+    }
+
+    fn definition_list(&mut self, node: &Node, width: Option<usize>) -> String {
+        let mut out = String::new();
+        let mut previous_was_definition = false;
+
+        for child in &node.children {
+            match child.r#type {
+                NodeType::DefinitionListTitle => {
+                    if !out.is_empty() {
+                        out.push_str(if previous_was_definition { "\n\n" } else { "\n" });
+                    }
+                    let context = InlineContext {
+                        single_line: true,
+                        ..Default::default()
+                    };
+                    out.push_str(&self.inline(&child.children, context));
+                    previous_was_definition = false;
+                }
+                _ => {
+                    if !out.is_empty() {
+                        out.push('\n');
+                    }
+                    let content = self.blocks(&child.children, narrow(width, 2), true);
+                    out.push_str(&indent_lines(&content, ": ", "  "));
+                    previous_was_definition = true;
+                }
+            }
+        }
+        out
+    }
+
+    fn table(&mut self, node: &Node, alignments: &[Alignment]) -> String {
+        let context = InlineContext {
+            single_line: true,
+            in_table: true,
+            ..Default::default()
+        };
+        let rows: Vec<Vec<String>> = node
+            .children
+            .iter()
+            .map(|row| {
+                row.children
+                    .iter()
+                    .map(|cell| self.inline(&cell.children, context))
+                    .collect()
+            })
+            .collect();
+
+        let columns = rows
+            .iter()
+            .map(Vec::len)
+            .max()
+            .unwrap_or(0)
+            .max(alignments.len());
+        let alignment = |column: usize| alignments.get(column).copied().unwrap_or(Alignment::None);
+        let widths: Vec<usize> = (0..columns)
+            .map(|column| {
+                if !self.flags.align_tables {
+                    return 3;
+                }
+                rows.iter()
+                    .filter_map(|row| row.get(column))
+                    .map(|cell| cell.chars().count())
+                    .max()
+                    .unwrap_or(0)
+                    .max(3)
+            })
+            .collect();
+
+        let format_row = |row: &[String]| {
+            let cells: Vec<String> = (0..columns)
+                .map(|column| {
+                    let cell = row.get(column).map(String::as_str).unwrap_or("");
+                    if self.flags.align_tables {
+                        pad_cell(cell, widths[column], alignment(column))
+                    } else {
+                        cell.to_string()
+                    }
+                })
+                .collect();
+            format!("| {} |", cells.join(" | "))
+        };
+
+        let delimiter: Vec<String> = (0..columns)
+            .map(|column| {
+                let width = widths[column];
+                match alignment(column) {
+                    Alignment::None => "-".repeat(width),
+                    Alignment::Left => format!(":{}", "-".repeat(width - 1)),
+                    Alignment::Right => format!("{}:", "-".repeat(width - 1)),
+                    Alignment::Center => format!(":{}:", "-".repeat(width - 2)),
+                }
+            })
+            .collect();
+
+        let mut lines = Vec::with_capacity(rows.len() + 1);
+        if let Some(head) = rows.first() {
+            lines.push(format_row(head));
+        }
+        lines.push(format!("| {} |", delimiter.join(" | ")));
+        for row in rows.iter().skip(1) {
+            lines.push(format_row(row));
+        }
+        lines.join("\n")
+    }
+
+    fn paragraph(&mut self, nodes: &[Node], width: Option<usize>) -> String {
+        let context = InlineContext {
+            wrap: width.is_some(),
+            ..Default::default()
+        };
+        self.breaks = Breaks::default();
+        let text = self.inline(nodes, context);
+        match width {
+            Some(width) => wrap_paragraph(&text, &std::mem::take(&mut self.breaks), width),
+            None => text,
+        }
+    }
+
+    fn inline(&mut self, nodes: &[Node], context: InlineContext) -> String {
+        let mut out = String::new();
+        self.inline_into(nodes, &mut out, context);
+        out
+    }
+
+    fn inline_into(&mut self, nodes: &[Node], out: &mut String, context: InlineContext) {
+        let merged;
+        let nodes = if context.wrap {
+            merged = merge_soft_breaks(nodes);
+            &merged
+        } else {
+            nodes
+        };
+        for (index, node) in nodes.iter().enumerate() {
+            let next = nodes.get(index + 1).and_then(first_char);
+            let content = node.content.as_deref().unwrap_or_default();
+
+            match &node.r#type {
+                NodeType::Text => {
+                    escape_text(content, out, next, context, &self.labels, &mut self.breaks)
+                }
+                NodeType::Code if context.wrap => self.breaks.push_kept(out, &code_span(content)),
+                NodeType::Code => out.push_str(&code_span(content)),
+                NodeType::Emph | NodeType::Strong => {
+                    let preferred = if matches!(node.r#type, NodeType::Emph) {
+                        self.flags.emphasis
+                    } else {
+                        self.flags.strong
+                    };
+                    // Underscores do not work inside words.
+                    let touches_word = out.chars().last().is_some_and(char::is_alphanumeric)
+                        || next.is_some_and(char::is_alphanumeric);
+                    let marker = if preferred == '_' && touches_word { '*' } else { preferred };
+                    let delimiter = if matches!(node.r#type, NodeType::Emph) {
+                        marker.to_string()
+                    } else {
+                        marker.to_string().repeat(2)
+                    };
+                    out.push_str(&delimiter);
+                    self.inline_into(&node.children, out, context);
+                    out.push_str(&delimiter);
+                }
+                NodeType::Strikethrough => self.wrap_inline("~~", node, out, context),
+                NodeType::Superscript => self.wrap_inline("^", node, out, context),
+                NodeType::Subscript => self.wrap_inline("~", node, out, context),
+                NodeType::Link { .. } | NodeType::Image { .. } => self.link(node, out, context),
+                NodeType::InlineHtml | NodeType::Html => out.push_str(content),
+                NodeType::InlineMath => out.push_str(&format!("${}$", content)),
+                NodeType::DisplayMath => out.push_str(&format!("$${}$$", content)),
+                NodeType::SoftBreak => {
+                    if context.wrap {
+                        self.breaks.soft.push(out.len());
+                    }
+                    out.push(if context.single_line || context.wrap { ' ' } else { '\n' })
+                }
+                NodeType::HardBreak => {
+                    out.push_str(if context.single_line { " " } else { "\\\n" })
+                }
+                NodeType::FootnoteReference { label } => out.push_str(&format!("[^{}]", label)),
+                NodeType::TaskListMarker { checked } => {
+                    out.push_str(if *checked { "[x] " } else { "[ ] " })
+                }
+                _ => self.inline_into(&node.children, out, context),
+            }
+        }
+    }
+
+    fn wrap_inline(&mut self, delimiter: &str, node: &Node, out: &mut String, context: InlineContext) {
+        out.push_str(delimiter);
+        self.inline_into(&node.children, out, context);
+        out.push_str(delimiter);
+    }
+
+    fn link(&mut self, node: &Node, out: &mut String, context: InlineContext) {
+        let (is_image, link_type, dest_url, title, id) = match &node.r#type {
+            NodeType::Link { link_type, dest_url, title, id } => (false, link_type, dest_url, title, id),
+            NodeType::Image { link_type, dest_url, title, id } => (true, link_type, dest_url, title, id),
+            _ => return,
+        };
+        // The text is written in place so its breaks are recorded, then
+        // taken back out to be put where the link form needs it.
+        let start = out.len();
+        self.inline_into(&node.children, out, context);
+        let text = out.split_off(start);
+        let text_breaks = self.breaks.split_off(start);
+        let push_text = |out: &mut String, breaks: &mut Breaks| {
+            breaks.append(&text_breaks, out.len());
+            out.push_str(&text);
+        };
+        let bang = if is_image { "!" } else { "" };
+        let has_definition = !id.is_empty() && self.labels.contains(&id.to_lowercase());
+
+        match link_type {
+            LinkType::Autolink | LinkType::Email if !is_image && text == *dest_url => {
+                out.push_str(&format!("<{}>", dest_url));
+            }
+            LinkType::Reference | LinkType::Collapsed | LinkType::Shortcut if has_definition => {
+                out.push_str(&format!("{}[", bang));
+                push_text(out, &mut self.breaks);
+                out.push(']');
+                match link_type {
+                    LinkType::Reference => out.push_str(&format!("[{}]", id)),
+                    LinkType::Collapsed => out.push_str("[]"),
+                    _ => {}
+                }
+            }
+            LinkType::WikiLink { has_pothole } => {
+                if *has_pothole {
+                    out.push_str(&format!("{}[[{}|", bang, dest_url));
+                    push_text(out, &mut self.breaks);
+                    out.push_str("]]");
+                } else {
+                    out.push_str(&format!("{}[[{}]]", bang, dest_url));
+                }
+            }
+            _ => {
+                out.push_str(&format!("{}[", bang));
+                push_text(out, &mut self.breaks);
+                out.push_str("](");
+                let dest = destination(dest_url);
+                if context.wrap {
+                    self.breaks.push_kept(out, &dest);
+                } else {
+                    out.push_str(&dest);
+                }
+                if !title.is_empty() {
+                    out.push(' ');
+                    out.push_str(&link_title(title));
+                }
+                out.push(')');
+            }
+        }
+    }
+}
+
+fn is_block(node_type: &NodeType) -> bool {
+    matches!(
+        node_type,
+        NodeType::Document
+            | NodeType::BlockQuote { .. }
+            | NodeType::List { .. }
+            | NodeType::Item
+            | NodeType::FootnoteDefinition { .. }
+            | NodeType::DefinitionList
+            | NodeType::DefinitionListTitle
+            | NodeType::DefinitionListDefinition
+            | NodeType::Table(_)
+            | NodeType::TableHead
+            | NodeType::TableRow
+            | NodeType::TableCell
+            | NodeType::Heading { .. }
+            | NodeType::CodeBlock { .. }
+            | NodeType::Paragraph
+            | NodeType::Rule
+            | NodeType::HtmlBlock
+            | NodeType::Html
+            | NodeType::MetadataBlock { .. }
+    )
+}
+
+fn first_char(node: &Node) -> Option<char> {
+    match node.r#type {
+        NodeType::Text => node.content.as_deref().and_then(|text| text.chars().next()),
+        NodeType::SoftBreak => Some('\n'),
+        _ => None,
+    }
+}
+
+// Joins text across soft breaks, which the wrapper reflows anyway, so that
+// escaping does not depend on where the source broke its lines. Breaks next
+// to lines the wrapper keeps (admonition fences, directives) stay.
+fn merge_soft_breaks(nodes: &[Node]) -> Vec<Node> {
+    let mut merged: Vec<Node> = Vec::with_capacity(nodes.len());
+    let mut index = 0;
+    while index < nodes.len() {
+        let node = &nodes[index];
+        if let (Some(previous), Some(following)) = (merged.last_mut(), nodes.get(index + 1)) {
+            let mergeable = |node: &Node| {
+                matches!(node.r#type, NodeType::Text)
+                    && !PROTECTED_LINE_RE.is_match(node.content.as_deref().unwrap_or_default())
+            };
+            if matches!(node.r#type, NodeType::SoftBreak) && mergeable(previous) && mergeable(following) {
+                let content = previous.content.get_or_insert_with(String::new);
+                content.push(' ');
+                content.push_str(following.content.as_deref().unwrap_or_default());
+                index += 2;
+                continue;
+            }
+        }
+        merged.push(node.clone());
+        index += 1;
+    }
+    merged
+}
+
+fn text_content(nodes: &[Node]) -> String {
+    nodes
+        .iter()
+        .filter_map(|node| node.content.as_deref())
+        .collect()
+}
+
+fn alternate_marker(marker: char) -> char {
+    match marker {
+        '-' => '*',
+        '.' => ')',
+        ')' => '.',
+        _ => '-',
+    }
+}
+
+fn block_quote_kind(kind: BlockQuoteKind) -> &'static str {
+    match kind {
+        BlockQuoteKind::Note => "NOTE",
+        BlockQuoteKind::Tip => "TIP",
+        BlockQuoteKind::Important => "IMPORTANT",
+        BlockQuoteKind::Warning => "WARNING",
+        BlockQuoteKind::Caution => "CAUTION",
+    }
+}
+
+fn narrow(width: Option<usize>, by: usize) -> Option<usize> {
+    width.map(|width| width.saturating_sub(by).max(20))
+}
+
+// Prefixes the first line with `first` and every other non-empty line with `rest`.
+fn indent_lines(content: &str, first: &str, rest: &str) -> String {
+    content
+        .split('\n')
+        .enumerate()
+        .map(|(index, line)| {
+            if index == 0 {
+                format!("{}{}", first, line).trim_end().to_string()
+            } else if line.is_empty() {
+                String::new()
+            } else {
+                format!("{}{}", rest, line)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn prefix_lines(content: &str, prefix: &str, empty: &str) -> String {
+    if content.is_empty() {
+        return empty.to_string();
+    }
+    content
+        .split('\n')
+        .map(|line| {
+            if line.is_empty() {
+                empty.to_string()
+            } else {
+                format!("{}{}", prefix, line)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn longest_run(text: &str, c: char) -> usize {
+    let mut longest = 0;
+    let mut current = 0;
+    for ch in text.chars() {
+        if ch == c {
+            current += 1;
+            longest = longest.max(current);
+        } else {
+            current = 0;
+        }
+    }
+    longest
+}
+
+fn code_fence(content: &str, lang: Option<&str>, meta: Option<&str>) -> String {
+    let info = match (lang, meta) {
+        (Some(lang), Some(meta)) => format!("{} {}", lang, meta),
+        (Some(lang), None) => lang.to_string(),
+        (None, Some(meta)) => format!(" {}", meta),
+        (None, None) => String::new(),
+    };
+    // Backtick fences cannot carry backticks in their info string.
+    let fence_char = if info.contains('`') { '~' } else { '`' };
+    let fence = fence_char
+        .to_string()
+        .repeat((longest_run(content, fence_char) + 1).max(3));
+
+    let mut out = format!("{}{}\n{}", fence, info, content);
+    if !content.is_empty() && !content.ends_with('\n') {
+        out.push('\n');
+    }
+    out.push_str(&fence);
+    out
+}
+
+fn code_span(content: &str) -> String {
+    let ticks = "`".repeat(longest_run(content, '`') + 1);
+    let needs_padding = content.starts_with('`')
+        || content.ends_with('`')
+        || (content.starts_with(' ') && content.ends_with(' ') && !content.trim().is_empty());
+    let content = if needs_padding {
+        format!(" {} ", content)
+    } else {
+        content.to_string()
+    };
+    format!("{}{}{}", ticks, content, ticks)
+}
+
+fn destination(dest: &str) -> String {
+    let balanced = dest.matches('(').count() == dest.matches(')').count();
+    if dest.is_empty() || dest.contains(char::is_whitespace) || dest.contains(['<', '>']) || !balanced {
+        format!("<{}>", dest.replace('<', "\\<").replace('>', "\\>"))
+    } else {
+        dest.to_string()
+    }
+}
+
+fn link_title(title: &str) -> String {
+    format!("\"{}\"", title.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn heading_attributes(id: Option<&str>, classes: &[String], attrs: &[(String, Option<String>)]) -> String {
+    let mut parts = Vec::new();
+    if let Some(id) = id {
+        parts.push(format!("#{}", id));
+    }
+    parts.extend(classes.iter().map(|class| format!(".{}", class)));
+    parts.extend(attrs.iter().map(|(key, value)| match value {
+        Some(value) => format!("{}={}", key, value),
+        None => key.clone(),
+    }));
+    if parts.is_empty() {
+        String::new()
+    } else {
+        format!("{{{}}}", parts.join(" "))
+    }
+}
+
+fn pad_cell(cell: &str, width: usize, alignment: Alignment) -> String {
+    let padding = width.saturating_sub(cell.chars().count());
+    match alignment {
+        Alignment::Right => format!("{}{}", " ".repeat(padding), cell),
+        Alignment::Center => {
+            let left = padding / 2;
+            format!("{}{}{}", " ".repeat(left), cell, " ".repeat(padding - left))
+        }
+        Alignment::Left | Alignment::None => format!("{}{}", cell, " ".repeat(padding)),
+    }
+}
+
+// Escapes characters in `text` that would otherwise be read as markdown syntax.
+// `next` is the first character of the following sibling, if known.
+fn escape_text(
+    text: &str,
+    out: &mut String,
+    next: Option<char>,
+    context: InlineContext,
+    labels: &HashSet<String>,
+    breaks: &mut Breaks,
+) {
+    let chars: Vec<(usize, char)> = text.char_indices().collect();
+    // When wrapping, lines only start where the wrapper puts them, so
+    // `wrap_line` escapes them instead.
+    let at_line_start = !context.wrap && (out.is_empty() || out.ends_with('\n'));
+
+    for (index, &(offset, c)) in chars.iter().enumerate() {
+        let previous = if index == 0 {
+            out.chars().last()
+        } else {
+            Some(chars[index - 1].1)
+        };
+        let following = chars.get(index + 1).map(|(_, c)| *c).or(next);
+        let rest = &text[offset..];
+
+        if index == 0 && at_line_start {
+            match block_start_escape(rest) {
+                Some(0) => {
+                    out.push('\\');
+                    out.push(c);
+                    continue;
+                }
+                Some(digits) => {
+                    out.push_str(&rest[..digits]);
+                    out.push('\\');
+                    escape_ordered_tail(&rest[digits..], out, next, context, labels, breaks);
+                    return;
+                }
+                None => {}
+            }
+        }
+
+        let spaced = previous.is_some_and(char::is_whitespace) && following.is_some_and(char::is_whitespace);
+        let escape = match c {
+            // A backslash before a line break, which wrapping may put after
+            // any space, would be a hard break.
+            '\\' => following.is_none_or(|c| c.is_ascii_punctuation() || c.is_whitespace()),
+            '`' => true,
+            '*' | '~' => !spaced,
+            '_' => {
+                let intraword = previous.is_some_and(char::is_alphanumeric)
+                    && following.is_some_and(char::is_alphanumeric);
+                !spaced && !intraword
+            }
+            '$' | '^' => !spaced && text.matches(c).count() > 1,
+            '<' => following.is_some_and(|c| c.is_ascii_alphabetic() || matches!(c, '/' | '!' | '?')),
+            '&' => ENTITY_RE.is_match(rest),
+            '[' => opens_link(rest, labels),
+            '|' => context.in_table,
+            _ => false,
+        };
+        if escape {
+            out.push('\\');
+        }
+        if c == ' ' && context.wrap && index > 0 && chars[index - 1].1 == ' ' {
+            breaks.kept.push(out.len());
+        }
+        out.push(c);
+    }
+}
+
+// Where a backslash keeps `line` from starting a block construct, if it would:
+// before the first character, or before the delimiter of an ordered list item.
+fn block_start_escape(line: &str) -> Option<usize> {
+    if HEADING_START_RE.is_match(line)
+        || BULLET_START_RE.is_match(line)
+        || SETEXT_LINE_RE.is_match(line)
+        || DEFINITION_START_RE.is_match(line)
+        || line.starts_with('>')
+        || line.starts_with("+++")
+    {
+        Some(0)
+    } else if ORDERED_START_RE.is_match(line) {
+        line.find(|c: char| !c.is_ascii_digit())
+    } else {
+        None
+    }
+}
+
+fn escape_ordered_tail(
+    tail: &str,
+    out: &mut String,
+    next: Option<char>,
+    context: InlineContext,
+    labels: &HashSet<String>,
+    breaks: &mut Breaks,
+) {
+    let mut chars = tail.chars();
+    if let Some(delimiter) = chars.next() {
+        out.push(delimiter);
+        // Continue escaping mid-line, so the line-start rules no longer apply.
+        let remainder = chars.as_str();
+        if !remainder.is_empty() {
+            escape_text(remainder, out, next, context, labels, breaks);
+        }
+    }
+}
+
+// A `[` needs escaping when it could start a link: an inline or full
+// reference link in the same text, or a label that matches a definition.
+fn opens_link(rest: &str, labels: &HashSet<String>) -> bool {
+    let Some(close) = rest.find(']') else {
+        return false;
+    };
+    let label = &rest[1..close];
+    let after = &rest[close + 1..];
+    after.starts_with('(') || after.starts_with('[') || labels.contains(&label.to_lowercase())
+}
+
+// Re-wraps a paragraph at `width`, keeping hard breaks and lines that carry
+// block-level syntax of this crate (admonition fences, directives).
+fn wrap_paragraph(text: &str, breaks: &Breaks, width: usize) -> String {
+    let mut lines = Vec::new();
+    let mut start = 0;
+
+    for hard_line in text.split('\n') {
+        let end = start + hard_line.len();
+        let soft_breaks = breaks.soft.iter().copied().filter(|&at| at >= start && at < end);
+        let mut pending: Vec<&str> = Vec::new();
+        let mut segment_start = start;
+        for segment_end in soft_breaks.chain(iter::once(end)) {
+            let segment = &text[segment_start..segment_end];
+            if PROTECTED_LINE_RE.is_match(segment) {
+                if !pending.is_empty() {
+                    lines.push(wrap_line(&pending, width));
+                    pending.clear();
+                }
+                lines.push(segment.to_string());
+            } else {
+                pending.extend(words(text, segment_start..segment_end, &breaks.kept));
+            }
+            segment_start = segment_end + 1;
+        }
+        if !pending.is_empty() {
+            lines.push(wrap_line(&pending, width));
+        }
+        start = end + 1;
+    }
+
+    lines.join("\n")
+}
+
+// The words of `text[range]`, split at every space not in `kept`.
+fn words<'t>(text: &'t str, range: Range<usize>, kept: &[usize]) -> Vec<&'t str> {
+    let mut words = Vec::new();
+    let mut word_start = range.start;
+    for (offset, _) in text[range.clone()].match_indices(' ') {
+        let at = range.start + offset;
+        if kept.binary_search(&at).is_err() {
+            words.push(&text[word_start..at]);
+            word_start = at + 1;
+        }
+    }
+    words.push(&text[word_start..range.end]);
+    words
+}
+
+fn wrap_line(words: &[&str], width: usize) -> String {
+    let mut lines: Vec<String> = Vec::new();
+    let mut current = String::new();
+    let mut current_width = 0;
+
+    for &word in words {
+        let word_width = word.chars().count();
+        let fits = current_width + 1 + word_width <= width;
+        let can_break = !word.is_empty() && !current.trim().is_empty() && !BLOCK_START_RE.is_match(word);
+
+        if current.is_empty() && lines.is_empty() {
+            current.push_str(word);
+            current_width = word_width;
+        } else if !fits && can_break {
+            lines.push(std::mem::take(&mut current));
+            current.push_str(word);
+            current_width = word_width;
+        } else {
+            current.push(' ');
+            current.push_str(word);
+            current_width += 1 + word_width;
+        }
+    }
+    lines.push(current);
+    // Later lines never start with a block construct, as they are not broken
+    // before one.
+    if let Some(at) = block_start_escape(&lines[0]) {
+        lines[0].insert(at, '\\');
+    }
+    lines.join("\n")
+}
@@ -1,13 +1,74 @@
-use crate::components::render::render_to_html_string;
-use crate::config::RenderFlags;
+use crate::config::TocFlags;
+use crate::types::{HeadingInfo, TocEntry};
+use v_htmlescape::escape;
 
-pub fn generate_toc_html(input: &str) -> String {
-    let toc_markdown = pulldown_cmark_toc::TableOfContents::new(input).to_cmark();
-    if toc_markdown.is_empty() {
+/// Nests the headings within the configured level range, e.g. an `h3` under
+/// the preceding `h2`. A heading whose parent level is missing is attached to
+/// the nearest shallower heading.
+pub fn build_toc(headings: &[HeadingInfo], flags: &TocFlags) -> Vec<TocEntry> {
+    let mut roots: Vec<TocEntry> = Vec::new();
+    // Path of open entries from a root to the most recent heading.
+    let mut open: Vec<TocEntry> = Vec::new();
+
+    for heading in headings.iter().filter(|heading| flags.includes(heading.level)) {
+        while open.last().is_some_and(|entry| entry.level >= heading.level) {
+            close_entry(&mut open, &mut roots);
+        }
+        open.push(TocEntry {
+            level: heading.level,
+            text: heading.text.clone(),
+            slug: heading.slug.clone(),
+            children: Vec::new(),
+        });
+    }
+    while !open.is_empty() {
+        close_entry(&mut open, &mut roots);
+    }
+    roots
+}
+
+fn close_entry(open: &mut Vec<TocEntry>, roots: &mut Vec<TocEntry>) {
+    if let Some(entry) = open.pop() {
+        match open.last_mut() {
+            Some(parent) => parent.children.push(entry),
+            None => roots.push(entry),
+        }
+    }
+}
+
+/// Renders the tree as nested `<ul>` (or `<ol>`) lists linking to the
+/// heading ids. Returns an empty string when there are no entries.
+pub fn render_toc_html(entries: &[TocEntry], flags: &TocFlags) -> String {
+    if entries.is_empty() {
         return String::new();
     }
+    let mut html = String::new();
+    push_list(&mut html, entries, flags, true);
+    html
+}
+
+fn push_list(html: &mut String, entries: &[TocEntry], flags: &TocFlags, root: bool) {
+    let tag = if flags.ordered { "ol" } else { "ul" };
+    html.push('<');
+    html.push_str(tag);
+    if root {
+        html.push_str(" class=\"toc\"");
+    }
+    html.push('>');
+
+    for entry in entries {
+        html.push_str(&format!(
+            "<li><a href=\"#{}\">{}</a>",
+            escape(&entry.slug),
+            escape(&entry.text)
+        ));
+        if !entry.children.is_empty() {
+            push_list(html, &entry.children, flags, false);
+        }
+        html.push_str("</li>");
+    }
 
-    let flags = RenderFlags { toc: false, ..Default::default() };
-    let toc_html = render_to_html_string(&toc_markdown, flags);
-    toc_html.replacen("<ul>", r#"<ul class="toc">"#, 1)
+    html.push_str("</");
+    html.push_str(tag);
+    html.push('>');
 }
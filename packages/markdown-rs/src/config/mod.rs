@@ -1,11 +1,83 @@
+use crate::constants::{
+    DEFAULT_CLASS_PREFIX, DEFAULT_DARK_THEME, DEFAULT_DATA_IMAGE_TYPES, DEFAULT_LIGHT_THEME, DEFAULT_RAW_HTML_TAGS,
+    DEFAULT_THEME, DEFAULT_URL_SCHEMES,
+};
 use napi_derive::napi;
+use crate::services::resolver::{FsResolver, MemoryResolver, SourceResolver};
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+use std::sync::Arc;
 
+/// Overrides for the HTML sanitizer. Unset fields keep the built-in policy.
 #[napi(object)]
+#[derive(Debug, Clone, Default)]
+pub struct SanitizePolicy {
+    /// Replaces the set of allowed tags.
+    #[napi(js_name = "allowedTags")]
+    pub allowed_tags: Option<Vec<String>>,
+    /// Replaces the allowed attributes of each listed tag.
+    #[napi(js_name = "tagAttributes")]
+    pub tag_attributes: Option<HashMap<String, Vec<String>>>,
+    /// Replaces the classes kept on each tag; any other class is removed.
+    #[napi(js_name = "allowedClasses")]
+    pub allowed_classes: Option<HashMap<String, Vec<String>>>,
+    /// Replaces the URL schemes allowed in links and images.
+    #[napi(js_name = "urlSchemes")]
+    pub url_schemes: Option<Vec<String>>,
+    /// `rel` added to every link. An empty string disables it.
+    #[napi(js_name = "linkRel")]
+    pub link_rel: Option<String>,
+}
+
+/// Which URLs links and images may point to. Links to other URLs keep their
+/// text; images fall back to their alt text.
+#[napi(object)]
+#[derive(Debug, Clone, Default)]
+pub struct UrlPolicy {
+    /// Schemes allowed in links and images, e.g. `["https", "mailto"]`.
+    /// Defaults to the schemes the sanitizer allows. Relative URLs are always
+    /// allowed.
+    #[napi(js_name = "allowedSchemes")]
+    pub allowed_schemes: Option<Vec<String>>,
+    /// Allows relative URLs only, no scheme or `//host`.
+    #[napi(js_name = "relativeOnly")]
+    pub relative_only: Option<bool>,
+    /// Media types of `data:` URLs allowed as image sources. Defaults to
+    /// PNG, JPEG, GIF and WebP; an empty list disallows them.
+    #[napi(js_name = "dataImages")]
+    pub data_images: Option<Vec<String>>,
+}
+
+#[napi(object)]
+#[derive(Default)]
 pub struct RenderOptions {
     pub sanitize: Option<bool>,
     #[napi(js_name = "syntaxHighlight")]
     pub syntax_highlight: Option<bool>,
+    /// `classes` (default) emits prefixed classes styled by `getThemeCss`;
+    /// `inline` writes the theme's colors into `style` attributes; `dual`
+    /// writes the colors of two themes as CSS variables (see `getDualThemeCss`).
+    #[napi(js_name = "highlightMode")]
+    pub highlight_mode: Option<String>,
+    /// A theme from the built-in theme set. Defaults to `base16-ocean.dark`.
+    pub theme: Option<String>,
+    /// The light and dark themes of `dual` mode. Setting them without a
+    /// `highlightMode` selects `dual`.
+    pub themes: Option<ThemePairOptions>,
+    /// Prefix of the highlighting classes. Defaults to `hl-`.
+    #[napi(js_name = "classPrefix")]
+    pub class_prefix: Option<String>,
+    /// Fence languages mapped to a syntax name or extension, e.g.
+    /// `{ "ts": "javascript" }`. Takes precedence over the built-in aliases.
+    #[napi(js_name = "languageAliases")]
+    pub language_aliases: Option<HashMap<String, String>>,
+    /// Guesses the language of fences without one. Defaults to true.
+    #[napi(js_name = "detectLanguage")]
+    pub detect_language: Option<bool>,
     pub toc: Option<bool>,
+    /// Depth range, list style and placeholders of the table of contents.
+    #[napi(js_name = "tocOptions")]
+    pub toc_options: Option<TocOptions>,
     pub directives: Option<bool>,
     pub gfm: Option<bool>,
     pub footnotes: Option<bool>,
@@ -16,15 +88,65 @@ pub struct RenderOptions {
     pub admonitions: Option<bool>,
     pub attributes: Option<bool>,
     pub math: Option<bool>,
+    #[napi(js_name = "definitionLists")]
+    pub definition_lists: Option<bool>,
+    pub superscript: Option<bool>,
+    pub subscript: Option<bool>,
+    /// Recognizes a leading YAML (`---`) or TOML (`+++`) metadata block.
+    /// Off by default, except for `renderDocument`.
+    pub frontmatter: Option<bool>,
+    /// Writes an `id` onto every heading. Always on when `toc` is enabled.
+    #[napi(js_name = "headingIds")]
+    pub heading_ids: Option<bool>,
+    /// How heading ids are derived from heading text.
+    pub slug: Option<SlugOptions>,
+    /// Replaces `<<< @/path/file.rs#region{2-4}` lines with the file's contents
+    /// as a code block.
+    pub snippets: Option<bool>,
+    /// Replaces `<!--@include: ./shared/install.md-->` comments with the
+    /// markdown of the file, optionally cut to a heading's section
+    /// (`#install`) or a line range (`{3,10}`).
+    pub includes: Option<bool>,
+    /// Files read by snippet imports and includes instead of the file
+    /// system, keyed by their path relative to the root.
+    pub files: Option<HashMap<String, String>>,
+    /// Directory that `@/` paths resolve against and that no imported file
+    /// may leave. Defaults to the working directory.
+    pub root: Option<String>,
+    /// Directory that other relative paths resolve against, usually the
+    /// document's own. Defaults to `root`.
+    #[napi(js_name = "baseDir")]
+    pub base_dir: Option<String>,
+    #[napi(js_name = "sanitizePolicy")]
+    pub sanitize_policy: Option<SanitizePolicy>,
+    /// Applied to every link and image, whether or not `sanitize` is on.
+    #[napi(js_name = "urlPolicy")]
+    pub url_policy: Option<UrlPolicy>,
+    /// What happens to HTML written in the markdown: `allow` (default) keeps
+    /// it, `escape` shows it as text, `strip` removes it, and `allowlist`
+    /// keeps only the tags in `rawHtmlTags`. HTML emitted by plugins is not
+    /// affected.
+    #[napi(js_name = "rawHtml")]
+    pub raw_html: Option<String>,
+    /// Tags kept in `allowlist` mode. Defaults to inline formatting such as
+    /// `kbd`, `mark` and `sub`. Setting it without `rawHtml` selects
+    /// `allowlist`.
+    #[napi(js_name = "rawHtmlTags")]
+    pub raw_html_tags: Option<Vec<String>>,
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone)]
 pub struct RenderFlags {
     pub sanitize: bool,
     pub syntax_highlight: bool,
+    pub highlight: HighlightFlags,
     pub toc: bool,
+    pub toc_options: TocFlags,
     pub directives: bool,
     pub gfm: bool,
+    /// Reads `> [!NOTE]` alert markers along with `gfm`. Only the parse AST
+    /// uses them; rendering leaves the marker as text.
+    pub gfm_alerts: bool,
     pub footnotes: bool,
     pub spoiler: bool,
     pub smart_punctuation: bool,
@@ -32,6 +154,19 @@ pub struct RenderFlags {
     pub admonitions: bool,
     pub attributes: bool,
     pub math: bool,
+    pub definition_lists: bool,
+    pub superscript: bool,
+    pub subscript: bool,
+    pub frontmatter: bool,
+    pub heading_ids: bool,
+    pub slug: SlugFlags,
+    pub snippets: bool,
+    pub includes: bool,
+    /// Reads the files named by snippet imports and includes.
+    pub resolver: Arc<dyn SourceResolver>,
+    pub sanitize_policy: Option<SanitizePolicy>,
+    pub url_policy: UrlPolicyFlags,
+    pub raw_html: RawHtmlMode,
 }
 
 impl Default for RenderFlags {
@@ -39,9 +174,12 @@ impl Default for RenderFlags {
         Self {
             sanitize: true,
             syntax_highlight: false, // Disabled by default
+            highlight: HighlightFlags::default(),
             toc: false, // Disabled by default
+            toc_options: TocFlags::default(),
             directives: false, // Disabled by default
             gfm: true,
+            gfm_alerts: false,
             footnotes: true,
             spoiler: false, // Disabled by default
             smart_punctuation: false, // Disabled by default
@@ -49,18 +187,37 @@ impl Default for RenderFlags {
             admonitions: false, // Disabled by default
             attributes: false, // Disabled by default
             math: false, // Disabled by default
+            definition_lists: false, // Disabled by default
+            superscript: false, // Disabled by default
+            subscript: false, // Disabled by default
+            frontmatter: false, // Disabled by default
+            heading_ids: false, // Disabled by default
+            slug: SlugFlags::default(),
+            snippets: false, // Disabled by default
+            includes: false, // Disabled by default
+            resolver: default_resolver(),
+            sanitize_policy: None,
+            url_policy: UrlPolicyFlags::default(),
+            raw_html: RawHtmlMode::Allow,
         }
     }
 }
 
+fn default_resolver() -> Arc<dyn SourceResolver> {
+    Arc::new(FsResolver::new(PathBuf::from("."), None))
+}
+
 impl RenderFlags {
     pub fn fast() -> Self {
         Self {
             sanitize: false,
             syntax_highlight: false,
+            highlight: HighlightFlags::default(),
             toc: false,
+            toc_options: TocFlags::default(),
             directives: false,
             gfm: false,
+            gfm_alerts: false,
             footnotes: false,
             spoiler: false,
             smart_punctuation: false,
@@ -68,18 +225,70 @@ impl RenderFlags {
             admonitions: false,
             attributes: false,
             math: false,
+            definition_lists: false,
+            superscript: false,
+            subscript: false,
+            frontmatter: false,
+            heading_ids: false,
+            slug: SlugFlags::default(),
+            snippets: false,
+            includes: false,
+            resolver: default_resolver(),
+            sanitize_policy: None,
+            url_policy: UrlPolicyFlags::default(),
+            raw_html: RawHtmlMode::Allow,
         }
     }
 
+    /// Like `from_options`, but reads frontmatter unless `frontmatter` is
+    /// false, since documents return it.
+    pub fn document_from_options(options: Option<RenderOptions>) -> Self {
+        let mut options = options.unwrap_or_default();
+        options.frontmatter.get_or_insert(true);
+        Self::from_options(Some(options))
+    }
+
     pub fn from_options(options: Option<RenderOptions>) -> Self {
         let default_flags = Self::default();
         if let Some(o) = options {
             Self {
                 sanitize: o.sanitize.unwrap_or(default_flags.sanitize),
                 syntax_highlight: o.syntax_highlight.unwrap_or(default_flags.syntax_highlight),
+                highlight: HighlightFlags {
+                    mode: match o.highlight_mode.as_deref() {
+                        Some("inline") => HighlightMode::Inline,
+                        Some("classes") => HighlightMode::Classes,
+                        Some("dual") => HighlightMode::Dual,
+                        None if o.themes.is_some() => HighlightMode::Dual,
+                        _ => default_flags.highlight.mode,
+                    },
+                    theme: o.theme.unwrap_or(default_flags.highlight.theme),
+                    light_theme: o
+                        .themes
+                        .as_ref()
+                        .and_then(|themes| themes.light.clone())
+                        .unwrap_or(default_flags.highlight.light_theme),
+                    dark_theme: o
+                        .themes
+                        .and_then(|themes| themes.dark)
+                        .unwrap_or(default_flags.highlight.dark_theme),
+                    class_prefix: o.class_prefix.unwrap_or(default_flags.highlight.class_prefix),
+                    aliases: o
+                        .language_aliases
+                        .map(|aliases| {
+                            aliases
+                                .into_iter()
+                                .map(|(alias, lang)| (alias.to_lowercase(), lang))
+                                .collect()
+                        })
+                        .unwrap_or(default_flags.highlight.aliases),
+                    detect_language: o.detect_language.unwrap_or(default_flags.highlight.detect_language),
+                },
                 toc: o.toc.unwrap_or(default_flags.toc),
+                toc_options: TocFlags::from_options(o.toc_options),
                 directives: o.directives.unwrap_or(default_flags.directives),
                 gfm: o.gfm.unwrap_or(default_flags.gfm),
+                gfm_alerts: default_flags.gfm_alerts,
                 footnotes: o.footnotes.unwrap_or(default_flags.footnotes),
                 spoiler: o.spoiler.unwrap_or(default_flags.spoiler),
                 smart_punctuation: o.smart_punctuation.unwrap_or(default_flags.smart_punctuation),
@@ -87,9 +296,523 @@ impl RenderFlags {
                 admonitions: o.admonitions.unwrap_or(default_flags.admonitions),
                 attributes: o.attributes.unwrap_or(default_flags.attributes),
                 math: o.math.unwrap_or(default_flags.math),
+                definition_lists: o.definition_lists.unwrap_or(default_flags.definition_lists),
+                superscript: o.superscript.unwrap_or(default_flags.superscript),
+                subscript: o.subscript.unwrap_or(default_flags.subscript),
+                frontmatter: o.frontmatter.unwrap_or(default_flags.frontmatter),
+                heading_ids: o.heading_ids.unwrap_or(default_flags.heading_ids),
+                slug: SlugFlags::from_options(o.slug),
+                snippets: o.snippets.unwrap_or(default_flags.snippets),
+                includes: o.includes.unwrap_or(default_flags.includes),
+                resolver: match (o.files, o.root.or(o.base_dir.clone())) {
+                    (Some(files), _) => Arc::new(MemoryResolver::new(files)),
+                    (None, Some(root)) => Arc::new(FsResolver::new(
+                        PathBuf::from(root),
+                        o.base_dir.map(PathBuf::from),
+                    )),
+                    (None, None) => default_flags.resolver,
+                },
+                sanitize_policy: o.sanitize_policy,
+                url_policy: UrlPolicyFlags::from_options(o.url_policy),
+                raw_html: match (o.raw_html.as_deref(), o.raw_html_tags) {
+                    (Some("escape"), _) => RawHtmlMode::Escape,
+                    (Some("strip"), _) => RawHtmlMode::Strip,
+                    (Some("allow"), _) => RawHtmlMode::Allow,
+                    (Some("allowlist") | None, Some(tags)) => {
+                        RawHtmlMode::Allowlist(tags.iter().map(|tag| tag.to_ascii_lowercase()).collect())
+                    }
+                    (Some("allowlist"), None) => {
+                        RawHtmlMode::Allowlist(DEFAULT_RAW_HTML_TAGS.iter().map(|tag| tag.to_string()).collect())
+                    }
+                    _ => default_flags.raw_html,
+                },
             }
         } else {
             default_flags
         }
     }
 }
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RawHtmlMode {
+    Allow,
+    Escape,
+    Strip,
+    /// Lowercased tag names.
+    Allowlist(Vec<String>),
+}
+
+#[derive(Debug, Clone)]
+pub struct UrlPolicyFlags {
+    /// Lowercased.
+    pub allowed_schemes: Vec<String>,
+    pub relative_only: bool,
+    /// Lowercased media types.
+    pub data_images: Vec<String>,
+}
+
+impl Default for UrlPolicyFlags {
+    fn default() -> Self {
+        Self {
+            allowed_schemes: DEFAULT_URL_SCHEMES.iter().map(|s| s.to_string()).collect(),
+            relative_only: false,
+            data_images: DEFAULT_DATA_IMAGE_TYPES.iter().map(|s| s.to_string()).collect(),
+        }
+    }
+}
+
+impl UrlPolicyFlags {
+    pub fn from_options(options: Option<UrlPolicy>) -> Self {
+        let default_flags = Self::default();
+        let lowercase = |values: Vec<String>| values.into_iter().map(|v| v.trim().to_lowercase()).collect();
+        if let Some(o) = options {
+            Self {
+                allowed_schemes: o.allowed_schemes.map(lowercase).unwrap_or(default_flags.allowed_schemes),
+                relative_only: o.relative_only.unwrap_or(default_flags.relative_only),
+                data_images: o.data_images.map(lowercase).unwrap_or(default_flags.data_images),
+            }
+        } else {
+            default_flags
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HighlightMode {
+    Inline,
+    Classes,
+    Dual,
+}
+
+#[derive(Debug, Clone)]
+pub struct HighlightFlags {
+    pub mode: HighlightMode,
+    pub theme: String,
+    pub light_theme: String,
+    pub dark_theme: String,
+    pub class_prefix: String,
+    /// Lowercased fence language to syntax token.
+    pub aliases: BTreeMap<String, String>,
+    pub detect_language: bool,
+}
+
+impl Default for HighlightFlags {
+    fn default() -> Self {
+        Self {
+            mode: HighlightMode::Classes,
+            theme: DEFAULT_THEME.to_string(),
+            light_theme: DEFAULT_LIGHT_THEME.to_string(),
+            dark_theme: DEFAULT_DARK_THEME.to_string(),
+            class_prefix: DEFAULT_CLASS_PREFIX.to_string(),
+            aliases: BTreeMap::new(),
+            detect_language: true,
+        }
+    }
+}
+
+#[napi(object)]
+#[derive(Debug, Clone, Default)]
+pub struct ThemePairOptions {
+    /// Defaults to `InspiredGitHub`.
+    pub light: Option<String>,
+    /// Defaults to `base16-ocean.dark`.
+    pub dark: Option<String>,
+}
+
+#[napi(object)]
+#[derive(Debug, Clone, Default)]
+pub struct HighlightAssetsOptions {
+    /// Directory of the compiled dumps. Defaults to `markdown-rs` in the
+    /// system temporary directory.
+    #[napi(js_name = "cacheDir")]
+    pub cache_dir: Option<String>,
+}
+
+#[napi(object)]
+#[derive(Debug, Clone, Default)]
+pub struct DualThemeCssOptions {
+    /// Prefix of the highlighting classes. Defaults to `hl-`.
+    #[napi(js_name = "classPrefix")]
+    pub class_prefix: Option<String>,
+    /// Selector of the dark color mode, e.g. `html.dark`. Defaults to the
+    /// `prefers-color-scheme: dark` media query.
+    #[napi(js_name = "darkSelector")]
+    pub dark_selector: Option<String>,
+}
+
+#[napi(object)]
+#[derive(Debug, Clone, Default)]
+pub struct TocOptions {
+    /// Shallowest heading level included. Defaults to 1.
+    #[napi(js_name = "minLevel")]
+    pub min_level: Option<u32>,
+    /// Deepest heading level included. Defaults to 6.
+    #[napi(js_name = "maxLevel")]
+    pub max_level: Option<u32>,
+    /// Renders `<ol>` lists instead of `<ul>`.
+    pub ordered: Option<bool>,
+    /// Paragraphs consisting of one of these are replaced by the table of
+    /// contents. Defaults to `["[toc]"]`.
+    pub placeholders: Option<Vec<String>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct TocFlags {
+    pub min_level: u32,
+    pub max_level: u32,
+    pub ordered: bool,
+    pub placeholders: Vec<String>,
+}
+
+impl Default for TocFlags {
+    fn default() -> Self {
+        Self {
+            min_level: 1,
+            max_level: 6,
+            ordered: false,
+            placeholders: vec!["[toc]".to_string()],
+        }
+    }
+}
+
+impl TocFlags {
+    pub fn from_options(options: Option<TocOptions>) -> Self {
+        let default_flags = Self::default();
+        if let Some(o) = options {
+            let min_level = o.min_level.unwrap_or(default_flags.min_level).clamp(1, 6);
+            Self {
+                min_level,
+                max_level: o.max_level.unwrap_or(default_flags.max_level).clamp(min_level, 6),
+                ordered: o.ordered.unwrap_or(default_flags.ordered),
+                placeholders: o.placeholders.unwrap_or(default_flags.placeholders),
+            }
+        } else {
+            default_flags
+        }
+    }
+
+    pub fn includes(&self, level: u32) -> bool {
+        (self.min_level..=self.max_level).contains(&level)
+    }
+}
+
+#[napi(object)]
+#[derive(Debug, Clone, Default)]
+pub struct SlugOptions {
+    /// `github` (default) follows GitHub's anchors. `simple` only lowercases
+    /// and replaces spaces, matching ids generated by earlier versions.
+    pub style: Option<String>,
+    /// Replaces spaces and joins duplicate suffixes. Defaults to `-`.
+    pub separator: Option<String>,
+    /// Lowercases the text. Defaults to `true`.
+    pub lowercase: Option<bool>,
+    /// Prepended to every generated id, e.g. `user-content-`.
+    pub prefix: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SlugStyle {
+    Github,
+    Simple,
+}
+
+#[derive(Debug, Clone)]
+pub struct SlugFlags {
+    pub style: SlugStyle,
+    pub separator: String,
+    pub lowercase: bool,
+    pub prefix: String,
+}
+
+impl Default for SlugFlags {
+    fn default() -> Self {
+        Self {
+            style: SlugStyle::Github,
+            separator: "-".to_string(),
+            lowercase: true,
+            prefix: String::new(),
+        }
+    }
+}
+
+impl SlugFlags {
+    pub fn from_options(options: Option<SlugOptions>) -> Self {
+        let default_flags = Self::default();
+        if let Some(o) = options {
+            Self {
+                style: match o.style.as_deref() {
+                    Some("simple") => SlugStyle::Simple,
+                    Some("github") => SlugStyle::Github,
+                    _ => default_flags.style,
+                },
+                separator: o.separator.unwrap_or(default_flags.separator),
+                lowercase: o.lowercase.unwrap_or(default_flags.lowercase),
+                prefix: o.prefix.unwrap_or(default_flags.prefix),
+            }
+        } else {
+            default_flags
+        }
+    }
+}
+
+#[napi(object)]
+pub struct ParseOptions {
+    /// Adds a source `position` to every node.
+    pub positions: Option<bool>,
+    pub gfm: Option<bool>,
+    pub footnotes: Option<bool>,
+    pub math: Option<bool>,
+    #[napi(js_name = "definitionLists")]
+    pub definition_lists: Option<bool>,
+    pub superscript: Option<bool>,
+    pub subscript: Option<bool>,
+    /// Keeps a leading YAML or TOML metadata block as a `MetadataBlock` node.
+    pub frontmatter: Option<bool>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ParseFlags {
+    pub positions: bool,
+    pub gfm: bool,
+    pub footnotes: bool,
+    pub math: bool,
+    pub definition_lists: bool,
+    pub superscript: bool,
+    pub subscript: bool,
+    pub frontmatter: bool,
+}
+
+impl Default for ParseFlags {
+    fn default() -> Self {
+        Self {
+            positions: false,
+            gfm: true,
+            footnotes: true,
+            math: false,
+            definition_lists: false,
+            superscript: false,
+            subscript: false,
+            frontmatter: true,
+        }
+    }
+}
+
+impl ParseFlags {
+    pub fn from_options(options: Option<ParseOptions>) -> Self {
+        let default_flags = Self::default();
+        if let Some(o) = options {
+            Self {
+                positions: o.positions.unwrap_or(default_flags.positions),
+                gfm: o.gfm.unwrap_or(default_flags.gfm),
+                footnotes: o.footnotes.unwrap_or(default_flags.footnotes),
+                math: o.math.unwrap_or(default_flags.math),
+                definition_lists: o.definition_lists.unwrap_or(default_flags.definition_lists),
+                superscript: o.superscript.unwrap_or(default_flags.superscript),
+                subscript: o.subscript.unwrap_or(default_flags.subscript),
+                frontmatter: o.frontmatter.unwrap_or(default_flags.frontmatter),
+            }
+        } else {
+            default_flags
+        }
+    }
+
+    /// The render flags that select the same markdown syntax.
+    pub fn render_flags(&self) -> RenderFlags {
+        RenderFlags {
+            gfm: self.gfm,
+            gfm_alerts: self.gfm,
+            footnotes: self.footnotes,
+            math: self.math,
+            definition_lists: self.definition_lists,
+            superscript: self.superscript,
+            subscript: self.subscript,
+            frontmatter: self.frontmatter,
+            ..RenderFlags::default()
+        }
+    }
+}
+
+#[napi(object)]
+pub struct FormatOptions {
+    /// Bullet list marker: `-`, `*` or `+`.
+    pub bullet: Option<String>,
+    /// Ordered list delimiter: `.` or `)`.
+    #[napi(js_name = "orderedDelimiter")]
+    pub ordered_delimiter: Option<String>,
+    /// Emphasis marker: `*` or `_`.
+    pub emphasis: Option<String>,
+    /// Strong emphasis marker: `*` or `_`.
+    pub strong: Option<String>,
+    /// `atx` (`# Title`) or `setext` (underlined) for levels 1 and 2.
+    #[napi(js_name = "headingStyle")]
+    pub heading_style: Option<String>,
+    /// Pads table cells so the columns line up.
+    #[napi(js_name = "alignTables")]
+    pub align_tables: Option<bool>,
+    /// Re-wraps paragraphs at this width. Line breaks are kept when unset or 0.
+    #[napi(js_name = "lineWidth")]
+    pub line_width: Option<u32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeadingStyle {
+    Atx,
+    Setext,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct FormatFlags {
+    pub bullet: char,
+    pub ordered_delimiter: char,
+    pub emphasis: char,
+    pub strong: char,
+    pub heading_style: HeadingStyle,
+    pub align_tables: bool,
+    pub line_width: Option<usize>,
+}
+
+impl Default for FormatFlags {
+    fn default() -> Self {
+        Self {
+            bullet: '-',
+            ordered_delimiter: '.',
+            emphasis: '*',
+            strong: '*',
+            heading_style: HeadingStyle::Atx,
+            align_tables: true,
+            line_width: None,
+        }
+    }
+}
+
+impl FormatFlags {
+    pub fn from_options(options: Option<FormatOptions>) -> Self {
+        let default_flags = Self::default();
+        let marker = |value: Option<String>, allowed: &str, default: char| {
+            value
+                .and_then(|v| v.chars().next().filter(|c| v.len() == 1 && allowed.contains(*c)))
+                .unwrap_or(default)
+        };
+        if let Some(o) = options {
+            Self {
+                bullet: marker(o.bullet, "-*+", default_flags.bullet),
+                ordered_delimiter: marker(o.ordered_delimiter, ".)", default_flags.ordered_delimiter),
+                emphasis: marker(o.emphasis, "*_", default_flags.emphasis),
+                strong: marker(o.strong, "*_", default_flags.strong),
+                heading_style: match o.heading_style.as_deref() {
+                    Some("setext") => HeadingStyle::Setext,
+                    Some("atx") => HeadingStyle::Atx,
+                    _ => default_flags.heading_style,
+                },
+                align_tables: o.align_tables.unwrap_or(default_flags.align_tables),
+                line_width: o
+                    .line_width
+                    .filter(|width| *width > 0)
+                    .map(|width| width as usize)
+                    .or(default_flags.line_width),
+            }
+        } else {
+            default_flags
+        }
+    }
+}
+
+#[napi(object)]
+#[derive(Debug, Clone, Default)]
+pub struct LintConfig {
+    /// Severity of each rule by name: `off`, `warning` or `error`. Rules not
+    /// listed report warnings, except `line-length`, which is off unless
+    /// listed or `maxLineLength` is set.
+    pub rules: Option<HashMap<String, String>>,
+    /// Longest line `line-length` allows. Defaults to 80.
+    #[napi(js_name = "maxLineLength")]
+    pub max_line_length: Option<u32>,
+    /// Characters `heading-punctuation` reports at the end of a heading.
+    /// Defaults to `.,;:!`.
+    #[napi(js_name = "headingPunctuation")]
+    pub heading_punctuation: Option<String>,
+    /// Bullet `list-marker-style` expects: `-`, `*`, `+`, or `consistent`
+    /// (default) for whichever the document uses first.
+    #[napi(js_name = "listMarker")]
+    pub list_marker: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LintSeverity {
+    Off,
+    Warning,
+    Error,
+}
+
+#[derive(Debug, Clone)]
+pub struct LintFlags {
+    pub rules: HashMap<String, LintSeverity>,
+    pub max_line_length: usize,
+    pub heading_punctuation: String,
+    /// `None` expects the first bullet of the document everywhere.
+    pub list_marker: Option<char>,
+}
+
+// Rules that report nothing unless configured: wrapping prose is a matter of
+// taste.
+const OPT_IN_LINT_RULES: &[&str] = &["line-length"];
+
+impl Default for LintFlags {
+    fn default() -> Self {
+        Self {
+            rules: HashMap::new(),
+            max_line_length: 80,
+            heading_punctuation: ".,;:!".to_string(),
+            list_marker: None,
+        }
+    }
+}
+
+impl LintFlags {
+    pub fn from_options(options: Option<LintConfig>) -> Self {
+        let default_flags = Self::default();
+        if let Some(o) = options {
+            let mut rules: HashMap<String, LintSeverity> = o
+                .rules
+                .unwrap_or_default()
+                .into_iter()
+                .filter_map(|(rule, severity)| {
+                    let severity = match severity.as_str() {
+                        "off" => LintSeverity::Off,
+                        "warning" => LintSeverity::Warning,
+                        "error" => LintSeverity::Error,
+                        _ => return None,
+                    };
+                    Some((rule, severity))
+                })
+                .collect();
+            if o.max_line_length.is_some() {
+                rules.entry("line-length".to_string()).or_insert(LintSeverity::Warning);
+            }
+            Self {
+                rules,
+                max_line_length: o
+                    .max_line_length
+                    .map(|length| length as usize)
+                    .unwrap_or(default_flags.max_line_length),
+                heading_punctuation: o.heading_punctuation.unwrap_or(default_flags.heading_punctuation),
+                list_marker: match o.list_marker.as_deref() {
+                    Some("-") => Some('-'),
+                    Some("*") => Some('*'),
+                    Some("+") => Some('+'),
+                    _ => default_flags.list_marker,
+                },
+            }
+        } else {
+            default_flags
+        }
+    }
+
+    pub fn severity(&self, rule: &str) -> LintSeverity {
+        self.rules.get(rule).copied().unwrap_or(if OPT_IN_LINT_RULES.contains(&rule) {
+            LintSeverity::Off
+        } else {
+            LintSeverity::Warning
+        })
+    }
+}
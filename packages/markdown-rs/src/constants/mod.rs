@@ -5,3 +5,80 @@ lazy_static::lazy_static! {
     pub static ref SYNTAX_SET: SyntaxSet = SyntaxSet::load_defaults_newlines();
     pub static ref THEME_SET: ThemeSet = ThemeSet::load_defaults();
 }
+
+/// Theme used when none is configured or the configured one is unknown.
+pub const DEFAULT_THEME: &str = "base16-ocean.dark";
+
+/// Prefix of the CSS classes emitted by class-based highlighting.
+pub const DEFAULT_CLASS_PREFIX: &str = "hl-";
+
+/// Themes of dual light/dark highlighting when none are configured.
+pub const DEFAULT_LIGHT_THEME: &str = "InspiredGitHub";
+pub const DEFAULT_DARK_THEME: &str = "base16-ocean.dark";
+
+/// URL schemes links and images may use when no URL policy is configured:
+/// those the sanitizer allows by default.
+pub const DEFAULT_URL_SCHEMES: &[&str] = &[
+    "bitcoin", "ftp", "ftps", "geo", "http", "https", "im", "irc", "ircs", "magnet", "mailto", "mms",
+    "mx", "news", "nntp", "openpgp4fpr", "sip", "sms", "smsto", "ssh", "tel", "url", "webcal", "wtai",
+    "xmpp",
+];
+
+/// Media types of `data:` URLs allowed as image sources by default. SVG is
+/// left out since it can carry scripts.
+pub const DEFAULT_DATA_IMAGE_TYPES: &[&str] = &["image/png", "image/jpeg", "image/gif", "image/webp"];
+
+/// Tags `allowlist` raw HTML keeps when no tags are configured: inline
+/// formatting that markdown has no syntax for.
+pub const DEFAULT_RAW_HTML_TAGS: &[&str] = &[
+    "abbr", "b", "br", "del", "em", "i", "ins", "kbd", "mark", "s", "small", "strong", "sub", "sup", "u",
+];
+
+/// Attributes kept on allowlisted raw HTML tags. Event handlers and styles
+/// never are; URLs must pass the URL policy.
+pub const RAW_HTML_ATTRIBUTES: &[&str] = &[
+    "alt", "cite", "colspan", "datetime", "dir", "height", "href", "lang", "rowspan", "src", "title",
+    "width",
+];
+
+/// How many bytes of markdown includes may add to a document. A file that is
+/// included many times counts each time, so includes repeated across nested
+/// files cannot grow the document without bound.
+pub const MAX_INCLUDED_BYTES: usize = 8 * 1024 * 1024;
+/// Directory under the system temporary directory that holds compiled
+/// syntax and theme dumps when no cache directory is given.
+pub const DEFAULT_ASSETS_CACHE_DIR: &str = "markdown-rs";
+
+/// Fence languages the built-in syntax set does not know, mapped to the
+/// closest syntax it has. Consulted after the syntax set's own names and
+/// extensions, so registered syntaxes take precedence.
+pub const DEFAULT_LANGUAGE_ALIASES: &[(&str, &str)] = &[
+    ("ts", "javascript"),
+    ("typescript", "javascript"),
+    ("tsx", "javascript"),
+    ("mts", "javascript"),
+    ("cts", "javascript"),
+    ("jsx", "javascript"),
+    ("mjs", "javascript"),
+    ("cjs", "javascript"),
+    ("sh", "bash"),
+    ("shell", "bash"),
+    ("zsh", "bash"),
+    ("console", "bash"),
+    ("shellsession", "bash"),
+    ("vue", "html"),
+    ("svelte", "html"),
+    ("jsonc", "json"),
+    ("json5", "json"),
+    ("yml", "yaml"),
+    ("py", "python"),
+    ("rb", "ruby"),
+    ("rs", "rust"),
+    ("golang", "go"),
+    ("c++", "cpp"),
+    ("md", "markdown"),
+    ("mdx", "markdown"),
+    ("text", "plain text"),
+    ("txt", "plain text"),
+    ("plaintext", "plain text"),
+];
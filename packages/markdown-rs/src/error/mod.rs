@@ -4,6 +4,102 @@ use thiserror::Error;
 pub enum AppError {
     #[error("I/O Error: {0}")]
     Io(#[from] std::io::Error),
+    #[error("Invalid AST: {0}")]
+    InvalidAst(#[from] serde_json::Error),
+    #[error("Invalid frontmatter: {0}")]
+    InvalidFrontmatter(String),
+    #[error("Unknown theme: {0}")]
+    UnknownTheme(String),
+    #[error("Highlighting failed: {0}")]
+    Highlight(String),
+    #[error("Failed to load highlighting assets: {0}")]
+    HighlightAssets(String),
+    #[error("Cannot read {path}: {reason}")]
+    Resolve { path: String, reason: String },
+    #[error("Cannot read {0}: outside of the root directory")]
+    OutsideRoot(String),
+    #[error("Cannot read {path}: {reason}")]
+    IncludeNotFound { path: String, reason: String },
+    #[error("Cannot read {0}: outside of the root directory")]
+    IncludeOutsideRoot(String),
+    #[error("Cannot read {path}: {reason}")]
+    SnippetNotFound { path: String, reason: String },
+    #[error("Cannot read {0}: outside of the root directory")]
+    SnippetOutsideRoot(String),
+    #[error("Region {region} not found in {path}")]
+    RegionNotFound { region: String, path: String },
+    #[error("Include cycle: {}", .0.join(" -> "))]
+    IncludeCycle(Vec<String>),
+    #[error("No heading #{section} in {path}")]
+    SectionNotFound { section: String, path: String },
+    #[error("Including {path} would add more than {limit} bytes to the document")]
+    IncludeTooLarge { path: String, limit: usize },
+    #[error("`::: {0}` is never closed, so it contains the rest of the document")]
+    UnclosedContainer(String),
+    #[error("Unknown directive ::{0}")]
+    UnknownDirective(String),
+    #[error("Invalid content for ::{name}: {content}")]
+    InvalidDirective { name: String, content: String },
+    #[error("No definition for reference [{0}]")]
+    BrokenReference(String),
+    #[error("Invalid attribute `{0}`, expected `#id` or `.class`")]
+    InvalidAttributes(String),
+    #[error("Heading id #{id} is already taken, so #{used} is used instead")]
+    DuplicateHeadingId { id: String, used: String },
+    #[error("Paragraph id #{id} is already taken, so #{used} is used instead")]
+    DuplicateParagraphId { id: String, used: String },
+    #[error("Failed to serialize the AST: {0}")]
+    Serialize(String),
+}
+
+impl AppError {
+    /// Identifies the kind of problem in diagnostics.
+    pub fn code(&self) -> &'static str {
+        match self {
+            AppError::Io(_) => "io",
+            AppError::InvalidAst(_) => "invalid-ast",
+            AppError::InvalidFrontmatter(_) => "invalid-frontmatter",
+            AppError::UnknownTheme(_) => "unknown-theme",
+            AppError::Highlight(_) => "highlight",
+            AppError::HighlightAssets(_) => "highlight-assets",
+            AppError::Resolve { .. } => "file-not-found",
+            AppError::OutsideRoot(_) => "outside-root",
+            AppError::IncludeNotFound { .. } => "include-not-found",
+            AppError::IncludeOutsideRoot(_) => "include-outside-root",
+            AppError::SnippetNotFound { .. } => "snippet-not-found",
+            AppError::SnippetOutsideRoot(_) => "snippet-outside-root",
+            AppError::RegionNotFound { .. } => "snippet-region-not-found",
+            AppError::IncludeCycle(_) => "include-cycle",
+            AppError::SectionNotFound { .. } => "include-section-not-found",
+            AppError::IncludeTooLarge { .. } => "include-too-large",
+            AppError::UnclosedContainer(_) => "unclosed-container",
+            AppError::UnknownDirective(_) => "unknown-directive",
+            AppError::InvalidDirective { .. } => "invalid-directive",
+            AppError::BrokenReference(_) => "broken-reference",
+            AppError::InvalidAttributes(_) => "invalid-attributes",
+            AppError::DuplicateHeadingId { .. } => "duplicate-heading-id",
+            AppError::DuplicateParagraphId { .. } => "duplicate-paragraph-id",
+            AppError::Serialize(_) => "serialize",
+        }
+    }
+
+    /// The error for an include whose file the resolver could not read.
+    pub fn for_include(self) -> Self {
+        match self {
+            AppError::Resolve { path, reason } => AppError::IncludeNotFound { path, reason },
+            AppError::OutsideRoot(path) => AppError::IncludeOutsideRoot(path),
+            other => other,
+        }
+    }
+
+    /// The error for a snippet whose file the resolver could not read.
+    pub fn for_snippet(self) -> Self {
+        match self {
+            AppError::Resolve { path, reason } => AppError::SnippetNotFound { path, reason },
+            AppError::OutsideRoot(path) => AppError::SnippetOutsideRoot(path),
+            other => other,
+        }
+    }
 }
 
 pub type AppResult<T> = Result<T, AppError>;
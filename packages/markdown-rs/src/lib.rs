@@ -1,6 +1,12 @@
 use napi_derive::napi;
-use crate::config::RenderOptions;
+use crate::config::{
+    DualThemeCssOptions, FormatOptions, HighlightAssetsOptions, LintConfig, ParseOptions,
+    RenderOptions,
+};
 use crate::app::markdown_orchestrator;
+use crate::app::tasks::{ParseTask, RenderTask};
+use napi::bindgen_prelude::AsyncTask;
+use crate::constants::DEFAULT_CLASS_PREFIX;
 
 pub mod app;
 pub mod components;
@@ -14,25 +20,125 @@ pub mod utils;
 pub mod telemetry;
 
 pub use app::markdown_orchestrator::{render_unsafe, render_unsafe_no_highlight};
+pub use components::render::render_node;
 
 #[napi]
-pub fn parse(input: String) -> String {
-    markdown_orchestrator::parse(input)
+pub fn parse(input: String, options: Option<ParseOptions>) -> napi::Result<String> {
+    let flags = config::ParseFlags::from_options(options);
+    markdown_orchestrator::parse(input, flags)
+        .map_err(|e| napi::Error::from_reason(e.to_string()))
+}
+
+#[napi(js_name = "parseAsync")]
+pub fn parse_async(input: String, options: Option<ParseOptions>) -> AsyncTask<ParseTask> {
+    AsyncTask::new(ParseTask::new(input, config::ParseFlags::from_options(options)))
 }
 
 #[napi]
-pub fn render(input: String) -> String {
-    markdown_orchestrator::render(input)
+pub fn render(input: String) -> napi::Result<String> {
+    markdown_orchestrator::render(input).map_err(|e| napi::Error::from_reason(e.to_string()))
 }
 
 #[napi(js_name = "renderWithOptions")]
-pub fn render_with_options(input: String, options: Option<RenderOptions>) -> String {
+pub fn render_with_options(input: String, options: Option<RenderOptions>) -> napi::Result<String> {
     let flags = config::RenderFlags::from_options(options);
     markdown_orchestrator::render_with_options(input, flags)
+        .map_err(|e| napi::Error::from_reason(e.to_string()))
+}
+
+#[napi(js_name = "renderAsync")]
+pub fn render_async(input: String, options: Option<RenderOptions>) -> AsyncTask<RenderTask> {
+    AsyncTask::new(RenderTask::new(input, config::RenderFlags::document_from_options(options)))
+}
+
+#[napi(js_name = "renderBatch")]
+pub fn render_batch(
+    inputs: Vec<types::BatchInput>,
+    options: Option<RenderOptions>,
+) -> Vec<types::BatchResult> {
+    let flags = config::RenderFlags::from_options(options);
+    markdown_orchestrator::render_batch(inputs, flags)
+}
+
+#[napi(js_name = "renderDocument")]
+pub fn render_document(
+    input: String,
+    options: Option<RenderOptions>,
+) -> napi::Result<types::RenderedDocument> {
+    let flags = config::RenderFlags::document_from_options(options);
+    markdown_orchestrator::render_document(input, flags)
+        .map_err(|e| napi::Error::from_reason(e.to_string()))
+}
+
+#[napi(js_name = "getToc")]
+pub fn get_toc(input: String, options: Option<RenderOptions>) -> Vec<types::TocEntry> {
+    let flags = config::RenderFlags::from_options(options);
+    markdown_orchestrator::toc(input, flags)
+}
+
+#[napi(js_name = "expandIncludes")]
+pub fn expand_includes(input: String, options: Option<RenderOptions>) -> types::ExpandedMarkdown {
+    let flags = config::RenderFlags::from_options(options);
+    markdown_orchestrator::expand_includes(input, flags)
+}
+
+#[napi]
+pub fn lint(input: String, config: Option<LintConfig>) -> types::LintResult {
+    let flags = config::LintFlags::from_options(config);
+    markdown_orchestrator::lint(input, flags)
+}
+
+#[napi(js_name = "renderAst")]
+pub fn render_ast(ast_json: String, options: Option<RenderOptions>) -> napi::Result<String> {
+    let flags = config::RenderFlags::from_options(options);
+    markdown_orchestrator::render_ast(&ast_json, flags)
+        .map_err(|e| napi::Error::from_reason(e.to_string()))
+}
+
+#[napi]
+pub fn stringify(ast_json: String, style: Option<FormatOptions>) -> napi::Result<String> {
+    let flags = config::FormatFlags::from_options(style);
+    markdown_orchestrator::stringify(&ast_json, flags)
+        .map_err(|e| napi::Error::from_reason(e.to_string()))
+}
+
+#[napi]
+pub fn format(input: String, style: Option<FormatOptions>) -> String {
+    let flags = config::FormatFlags::from_options(style);
+    markdown_orchestrator::format(input, flags)
+}
+
+#[napi(js_name = "getThemeCss")]
+pub fn get_theme_css(theme: String, prefix: Option<String>) -> napi::Result<String> {
+    let prefix = prefix.as_deref().unwrap_or(DEFAULT_CLASS_PREFIX);
+    markdown_orchestrator::theme_css(&theme, prefix)
+        .map_err(|e| napi::Error::from_reason(e.to_string()))
+}
+
+#[napi(js_name = "getDualThemeCss")]
+pub fn get_dual_theme_css(options: Option<DualThemeCssOptions>) -> String {
+    let options = options.unwrap_or_default();
+    let prefix = options.class_prefix.as_deref().unwrap_or(DEFAULT_CLASS_PREFIX);
+    markdown_orchestrator::dual_theme_css(prefix, options.dark_selector.as_deref())
+}
+
+#[napi(js_name = "registerHighlightAssets")]
+pub fn register_highlight_assets(
+    dir: String,
+    options: Option<HighlightAssetsOptions>,
+) -> napi::Result<types::HighlightAssetsInfo> {
+    let cache_dir = options.and_then(|o| o.cache_dir);
+    markdown_orchestrator::register_highlight_assets(&dir, cache_dir.as_deref())
+        .map_err(|e| napi::Error::from_reason(e.to_string()))
+}
+
+#[napi(js_name = "resetHighlightAssets")]
+pub fn reset_highlight_assets() {
+    markdown_orchestrator::reset_highlight_assets();
 }
 
 #[napi]
-pub fn render_gfm(input: String) -> String {
+pub fn render_gfm(input: String) -> napi::Result<String> {
     render_with_options(input, None)
 }
 
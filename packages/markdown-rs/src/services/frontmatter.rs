@@ -0,0 +1,40 @@
+use crate::error::{AppError, AppResult};
+use crate::types::MetadataBlockKind;
+use figment::providers::{Format, Toml};
+use figment::Figment;
+use serde_json::{Map, Value};
+
+const TOML_DATETIME_KEY: &str = "$__toml_private_datetime";
+
+/// Parses the body of a metadata block into JSON. YAML is used for `---`
+/// blocks and TOML for `+++` blocks. An empty block yields an empty object.
+pub fn parse_frontmatter(kind: MetadataBlockKind, source: &str) -> AppResult<Value> {
+    if source.trim().is_empty() {
+        return Ok(Value::Object(Map::new()));
+    }
+
+    match kind {
+        MetadataBlockKind::YamlStyle => match serde_yaml_ng::from_str::<Value>(source) {
+            Ok(Value::Null) => Ok(Value::Object(Map::new())),
+            Ok(value) => Ok(value),
+            Err(e) => Err(AppError::InvalidFrontmatter(e.to_string())),
+        },
+        MetadataBlockKind::PlusesStyle => Figment::from(Toml::string(source))
+            .extract::<Value>()
+            .map(unwrap_datetimes)
+            .map_err(|e| AppError::InvalidFrontmatter(e.to_string())),
+    }
+}
+
+// TOML dates have no JSON counterpart and come out of figment as a wrapper
+// object, so they are kept as their string form.
+fn unwrap_datetimes(value: Value) -> Value {
+    match value {
+        Value::Object(map) => match map.get(TOML_DATETIME_KEY) {
+            Some(Value::String(date)) if map.len() == 1 => Value::String(date.clone()),
+            _ => Value::Object(map.into_iter().map(|(key, value)| (key, unwrap_datetimes(value))).collect()),
+        },
+        Value::Array(items) => Value::Array(items.into_iter().map(unwrap_datetimes).collect()),
+        other => other,
+    }
+}
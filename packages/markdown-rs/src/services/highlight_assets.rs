@@ -0,0 +1,155 @@
+use crate::constants::{SYNTAX_SET, THEME_SET};
+use crate::error::{AppError, AppResult};
+use crate::types::HighlightAssetsInfo;
+use fnv::FnvHasher;
+use std::fs;
+use std::hash::Hasher;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock, RwLock};
+use syntect::dumps::{dump_to_file, from_dump_file};
+use syntect::highlighting::ThemeSet;
+use syntect::parsing::SyntaxSet;
+use walkdir::WalkDir;
+
+const SYNTAX_EXTENSION: &str = "sublime-syntax";
+const THEME_EXTENSION: &str = "tmTheme";
+// The syntect release the crate was built with, from `Cargo.lock` (see
+// `build.rs`). Its dumps are only readable by the same release.
+const SYNTECT_VERSION: &str = env!("SYNTECT_VERSION");
+
+/// The syntaxes and themes highlighting uses: the built-in sets, or the
+/// built-in sets extended with a registered directory.
+pub struct HighlightAssets {
+    /// Content hash of the registered files, 0 for the built-in sets. Part of
+    /// the highlight cache key, so registering new files invalidates it.
+    pub id: u64,
+    syntaxes: Option<SyntaxSet>,
+    themes: Option<ThemeSet>,
+}
+
+impl HighlightAssets {
+    pub fn syntaxes(&self) -> &SyntaxSet {
+        self.syntaxes.as_ref().unwrap_or(&SYNTAX_SET)
+    }
+
+    pub fn themes(&self) -> &ThemeSet {
+        self.themes.as_ref().unwrap_or(&THEME_SET)
+    }
+}
+
+static DEFAULT_ASSETS: OnceLock<Arc<HighlightAssets>> = OnceLock::new();
+static REGISTERED_ASSETS: RwLock<Option<Arc<HighlightAssets>>> = RwLock::new(None);
+
+/// The assets of the most recent registration, or the built-in ones.
+pub fn current() -> Arc<HighlightAssets> {
+    let registered = REGISTERED_ASSETS.read().unwrap_or_else(|e| e.into_inner());
+    match registered.as_ref() {
+        Some(assets) => Arc::clone(assets),
+        None => Arc::clone(DEFAULT_ASSETS.get_or_init(|| {
+            Arc::new(HighlightAssets {
+                id: 0,
+                syntaxes: None,
+                themes: None,
+            })
+        })),
+    }
+}
+
+/// Goes back to the built-in syntaxes and themes.
+pub fn reset() {
+    *REGISTERED_ASSETS.write().unwrap_or_else(|e| e.into_inner()) = None;
+}
+
+/// Adds every `.sublime-syntax` and `.tmTheme` file under `dir` to the
+/// built-in sets and makes the result current. Compiled sets are dumped to
+/// `cache_dir` under the hash of the files' contents and loaded from there
+/// while the files stay unchanged. Failing to write the cache is not an
+/// error; the reason is returned in `cache_error`.
+pub fn register_directory(dir: &Path, cache_dir: &Path) -> AppResult<HighlightAssetsInfo> {
+    let files = asset_files(dir)?;
+    let id = content_hash(dir, &files)?;
+
+    let syntax_dump = cache_dir.join(format!("syntaxes-{id:016x}.packdump"));
+    let theme_dump = cache_dir.join(format!("themes-{id:016x}.themedump"));
+    let cached = from_dump_file::<SyntaxSet, _>(&syntax_dump)
+        .ok()
+        .zip(from_dump_file::<ThemeSet, _>(&theme_dump).ok());
+
+    let (syntaxes, themes, from_cache, cache_error) = match cached {
+        Some((syntaxes, themes)) => (syntaxes, themes, true, None),
+        None => {
+            let (syntaxes, themes) = build_sets(dir)?;
+            let written = fs::create_dir_all(cache_dir)
+                .map_err(|e| e.to_string())
+                .and_then(|_| dump_to_file(&syntaxes, &syntax_dump).map_err(|e| e.to_string()))
+                .and_then(|_| dump_to_file(&themes, &theme_dump).map_err(|e| e.to_string()));
+            let cache_error = written
+                .err()
+                .map(|e| format!("Cannot write the cache to {}: {e}", cache_dir.display()));
+            (syntaxes, themes, false, cache_error)
+        }
+    };
+
+    let info = HighlightAssetsInfo {
+        hash: format!("{id:016x}"),
+        syntaxes: syntaxes.syntaxes().len() as u32,
+        themes: themes.themes.len() as u32,
+        cached: from_cache,
+        cache_error,
+    };
+    *REGISTERED_ASSETS.write().unwrap_or_else(|e| e.into_inner()) = Some(Arc::new(HighlightAssets {
+        id,
+        syntaxes: Some(syntaxes),
+        themes: Some(themes),
+    }));
+    Ok(info)
+}
+
+fn build_sets(dir: &Path) -> AppResult<(SyntaxSet, ThemeSet)> {
+    let mut builder = SYNTAX_SET.clone().into_builder();
+    builder
+        .add_from_folder(dir, true)
+        .map_err(|e| AppError::HighlightAssets(e.to_string()))?;
+    let mut themes = ThemeSet {
+        themes: THEME_SET.themes.clone(),
+    };
+    themes
+        .add_from_folder(dir)
+        .map_err(|e| AppError::HighlightAssets(e.to_string()))?;
+    Ok((builder.build(), themes))
+}
+
+// The syntax and theme files under `dir`, in a stable order.
+fn asset_files(dir: &Path) -> AppResult<Vec<PathBuf>> {
+    if !dir.is_dir() {
+        return Err(AppError::HighlightAssets(format!("{} is not a directory", dir.display())));
+    }
+    let mut files: Vec<PathBuf> = WalkDir::new(dir)
+        .into_iter()
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_type().is_file())
+        .map(|entry| entry.into_path())
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|ext| ext == SYNTAX_EXTENSION || ext == THEME_EXTENSION)
+        })
+        .collect();
+    files.sort();
+    Ok(files)
+}
+
+// FNV is stable across builds, unlike std's default hasher, so the hash can
+// name files that outlive the process. The syntect version is included so
+// dumps written by another release are not read back.
+fn content_hash(dir: &Path, files: &[PathBuf]) -> AppResult<u64> {
+    let mut hasher = FnvHasher::default();
+    hasher.write(SYNTECT_VERSION.as_bytes());
+    for file in files {
+        let relative = file.strip_prefix(dir).unwrap_or(file);
+        hasher.write(relative.to_string_lossy().as_bytes());
+        hasher.write_u8(0);
+        hasher.write(&fs::read(file)?);
+        hasher.write_u8(0);
+    }
+    Ok(hasher.finish())
+}
@@ -0,0 +1,549 @@
+use crate::components::code_meta::{extract_diff_markers, extract_notations, CodeMeta};
+use crate::config::{HighlightFlags, HighlightMode};
+use crate::constants::{DEFAULT_LANGUAGE_ALIASES, DEFAULT_THEME};
+use crate::error::{AppError, AppResult};
+use crate::services::highlight_assets::{self, HighlightAssets};
+use crate::services::language_detection::detect_language;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::hash::{Hash, Hasher};
+use std::sync::{Mutex, OnceLock};
+use syntect::easy::{HighlightLines, ScopeRangeIterator};
+use syntect::highlighting::{Color, FontStyle, Highlighter, Style, Theme};
+use syntect::html::{
+    append_highlighted_html_for_styled_line, css_for_theme_with_class_style, ClassStyle,
+    IncludeBackground,
+};
+use syntect::parsing::{ParseState, Scope, ScopeStack, SyntaxReference, SyntaxSet};
+use syntect::util::LinesWithEndings;
+use v_htmlescape::escape;
+
+const CACHE_MAX_ENTRIES: usize = 128;
+
+// Highlighted blocks keyed by a hash of the code and every setting that
+// affects the output. The oldest entry is evicted first.
+struct HighlightCache {
+    order: VecDeque<u64>,
+    map: HashMap<u64, String>,
+}
+
+impl HighlightCache {
+    fn new() -> Self {
+        Self {
+            order: VecDeque::new(),
+            map: HashMap::new(),
+        }
+    }
+
+    fn get(&self, key: u64) -> Option<String> {
+        self.map.get(&key).cloned()
+    }
+
+    fn insert(&mut self, key: u64, value: String) {
+        if self.map.insert(key, value).is_some() {
+            return;
+        }
+        self.order.push_back(key);
+        while self.order.len() > CACHE_MAX_ENTRIES {
+            if let Some(old) = self.order.pop_front() {
+                self.map.remove(&old);
+            }
+        }
+    }
+}
+
+static HIGHLIGHT_CACHE: OnceLock<Mutex<HighlightCache>> = OnceLock::new();
+
+// syntect wants a `&'static str` prefix. Each distinct prefix is leaked once,
+// so memory stays bounded by the number of prefixes in use.
+static CLASS_PREFIXES: OnceLock<Mutex<HashSet<&'static str>>> = OnceLock::new();
+
+fn class_style(prefix: &str) -> ClassStyle {
+    let mut prefixes = CLASS_PREFIXES
+        .get_or_init(|| Mutex::new(HashSet::new()))
+        .lock()
+        .unwrap_or_else(|e| e.into_inner());
+    let prefix = match prefixes.get(prefix) {
+        Some(prefix) => *prefix,
+        None => {
+            let leaked: &'static str = Box::leak(prefix.to_string().into_boxed_str());
+            prefixes.insert(leaked);
+            leaked
+        }
+    };
+    ClassStyle::SpacedPrefixed { prefix }
+}
+
+fn find_theme<'a>(assets: &'a HighlightAssets, name: &str) -> Option<&'a Theme> {
+    assets.themes().themes.get(name)
+}
+
+fn resolve_theme<'a>(assets: &'a HighlightAssets, name: &str) -> &'a Theme {
+    find_theme(assets, name).unwrap_or_else(|| &assets.themes().themes[DEFAULT_THEME])
+}
+
+fn hex(color: Color) -> String {
+    format!("#{:02x}{:02x}{:02x}", color.r, color.g, color.b)
+}
+
+// Configured aliases win over the syntax set's own names and extensions,
+// which win over the built-in aliases. Fences without a language are
+// detected when enabled. Returns the syntax with the language to name in the
+// `language-*` class.
+fn resolve_syntax<'a>(
+    syntaxes: &'a SyntaxSet,
+    lang: &str,
+    code: &str,
+    flags: &HighlightFlags,
+) -> (&'a SyntaxReference, Option<String>) {
+    if lang.is_empty() {
+        let detected = flags
+            .detect_language
+            .then(|| detect_language(code, syntaxes))
+            .flatten();
+        return match detected {
+            Some((syntax, token)) => (syntax, Some(token)),
+            None => (syntaxes.find_syntax_plain_text(), None),
+        };
+    }
+
+    let key = lang.to_lowercase();
+    let default_alias = DEFAULT_LANGUAGE_ALIASES
+        .iter()
+        .find(|(alias, _)| *alias == key)
+        .map(|(_, token)| *token);
+    let syntax = flags
+        .aliases
+        .get(&key)
+        .and_then(|token| syntaxes.find_syntax_by_token(token))
+        .or_else(|| syntaxes.find_syntax_by_token(lang))
+        .or_else(|| default_alias.and_then(|token| syntaxes.find_syntax_by_token(token)))
+        .unwrap_or_else(|| syntaxes.find_syntax_plain_text());
+    (syntax, Some(lang.to_string()))
+}
+
+fn cache_key(assets_id: u64, meta: &CodeMeta, code: &str, flags: &HighlightFlags) -> u64 {
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    assets_id.hash(&mut hasher);
+    flags.mode.hash(&mut hasher);
+    flags.theme.hash(&mut hasher);
+    flags.light_theme.hash(&mut hasher);
+    flags.dark_theme.hash(&mut hasher);
+    flags.class_prefix.hash(&mut hasher);
+    flags.aliases.hash(&mut hasher);
+    flags.detect_language.hash(&mut hasher);
+    meta.hash(&mut hasher);
+    code.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Renders a fenced code block as `<pre><code>` with every line wrapped in a
+/// `<span class="{prefix}line" data-line="n">`. Lines selected by `meta` or by
+/// `// [!code …]` comments get `highlighted`/`focused` classes, added and
+/// removed lines of diffs get `diff-add`/`diff-remove`, and a title or line
+/// numbers are added when asked for. Languages are resolved through the
+/// alias tables, unlabeled code is detected, and anything else unknown is
+/// rendered as plain text.
+pub fn highlight_code_block(meta: &CodeMeta, code: &str, flags: &HighlightFlags) -> String {
+    let assets = highlight_assets::current();
+    let syntaxes = assets.syntaxes();
+    let key = cache_key(assets.id, meta, code, flags);
+    let cache = HIGHLIGHT_CACHE.get_or_init(|| Mutex::new(HighlightCache::new()));
+    if let Some(html) = cache.lock().ok().and_then(|cache| cache.get(key)) {
+        return html;
+    }
+
+    let mut meta = meta.clone();
+    let code = extract_notations(code, &mut meta);
+    let code = extract_diff_markers(&code, &mut meta);
+    let (syntax, lang) = resolve_syntax(syntaxes, meta.lang.as_deref().unwrap_or_default(), &code, flags);
+    let (classes, style, lines) = highlight_lines(&assets, syntax, &code, flags);
+    let html = code_block_html(&meta, lang.as_deref(), classes, style, &lines, &flags.class_prefix);
+
+    if let Ok(mut cache) = cache.lock() {
+        cache.insert(key, html.clone());
+    }
+    html
+}
+
+/// Renders a fenced code block with the line markup of
+/// `highlight_code_block` but without highlighting, naming only the fence's
+/// own language. Returns `None` if the info string and the code ask for
+/// nothing that markup shows, so the block can stay as it is.
+pub fn plain_code_block(meta: &CodeMeta, code: &str, flags: &HighlightFlags) -> Option<String> {
+    let mut meta = meta.clone();
+    let code = extract_notations(code, &mut meta);
+    let code = extract_diff_markers(&code, &mut meta);
+    let marked = meta.title.is_some()
+        || meta.line_numbers.is_some()
+        || !meta.highlight.is_empty()
+        || !meta.focus.is_empty()
+        || !meta.added.is_empty()
+        || !meta.removed.is_empty();
+    if !marked {
+        return None;
+    }
+    let lines: Vec<String> = code.lines().map(|line| escape(line).to_string()).collect();
+    Some(code_block_html(&meta, meta.lang.as_deref(), Vec::new(), None, &lines, &flags.class_prefix))
+}
+
+/// Inline code naming `lang` without highlighting it.
+pub fn plain_inline_code(lang: &str, code: &str) -> String {
+    format!("<code class=\"language-{}\">{}</code>", escape(lang), escape(code))
+}
+
+// The `<pre>` around the HTML of every line, with the classes and style of
+// the highlighting mode and those for what `meta` marks.
+fn code_block_html(
+    meta: &CodeMeta,
+    lang: Option<&str>,
+    mut classes: Vec<String>,
+    style: Option<String>,
+    lines: &[String],
+    prefix: &str,
+) -> String {
+    if meta.line_numbers.is_some() {
+        classes.push(format!("{prefix}line-numbers"));
+    }
+    if !meta.highlight.is_empty() {
+        classes.push(format!("{prefix}has-highlighted"));
+    }
+    if !meta.focus.is_empty() {
+        classes.push(format!("{prefix}has-focused"));
+    }
+    if !meta.added.is_empty() || !meta.removed.is_empty() {
+        classes.push(format!("{prefix}has-diff"));
+    }
+
+    let mut html = String::new();
+    if let Some(title) = &meta.title {
+        html.push_str(&format!(
+            "<div class=\"{p}code-block\"><div class=\"{p}code-title\">{}</div>",
+            escape(title),
+            p = escape(prefix)
+        ));
+    }
+    html.push_str("<pre");
+    if !classes.is_empty() {
+        html.push_str(&format!(" class=\"{}\"", escape(&classes.join(" "))));
+    }
+    if let Some(style) = style {
+        html.push_str(&format!(" style=\"{style}\""));
+    }
+    html.push_str("><code");
+    if let Some(lang) = lang {
+        html.push_str(&format!(" class=\"language-{}\"", escape(lang)));
+    }
+    html.push('>');
+    for (index, line) in lines.iter().enumerate() {
+        push_line(&mut html, index + 1, line, meta, prefix);
+    }
+    html.push_str("</code></pre>");
+    if meta.title.is_some() {
+        html.push_str("</div>");
+    }
+    html.push('\n');
+    html
+}
+
+/// Renders inline code as a highlighted `<code>` element, styled like the
+/// code blocks of the same mode.
+pub fn highlight_inline_code(lang: &str, code: &str, flags: &HighlightFlags) -> String {
+    let assets = highlight_assets::current();
+    let (syntax, lang) = resolve_syntax(assets.syntaxes(), lang, code, flags);
+    let (mut classes, style, lines) = highlight_lines(&assets, syntax, code, flags);
+    if let Some(lang) = &lang {
+        classes.push(format!("language-{lang}"));
+    }
+
+    let mut html = String::from("<code");
+    if !classes.is_empty() {
+        html.push_str(&format!(" class=\"{}\"", escape(&classes.join(" "))));
+    }
+    if let Some(style) = style {
+        html.push_str(&format!(" style=\"{style}\""));
+    }
+    html.push('>');
+    html.push_str(&lines.join(" "));
+    html.push_str("</code>");
+    html
+}
+
+// Highlights `code` in the configured mode, returning the classes and style
+// of the enclosing element along with the HTML of every line.
+fn highlight_lines(
+    assets: &HighlightAssets,
+    syntax: &SyntaxReference,
+    code: &str,
+    flags: &HighlightFlags,
+) -> (Vec<String>, Option<String>, Vec<String>) {
+    let syntaxes = assets.syntaxes();
+    let prefix = &flags.class_prefix;
+    match flags.mode {
+        HighlightMode::Classes => (
+            vec![format!("{prefix}code")],
+            None,
+            highlight_classes(syntaxes, syntax, code, prefix),
+        ),
+        HighlightMode::Inline => {
+            let theme = resolve_theme(assets, &flags.theme);
+            let background = theme.settings.background.unwrap_or(Color::WHITE);
+            (
+                Vec::new(),
+                Some(format!("background-color:{};", hex(background))),
+                highlight_inline(syntaxes, syntax, code, theme, background),
+            )
+        }
+        HighlightMode::Dual => {
+            let light = resolve_theme(assets, &flags.light_theme);
+            let dark = resolve_theme(assets, &flags.dark_theme);
+            (
+                vec![format!("{prefix}dual")],
+                Some(format!("{}{}", theme_variables("light", light), theme_variables("dark", dark))),
+                highlight_dual(syntaxes, syntax, code, light, dark),
+            )
+        }
+    }
+}
+
+fn push_line(html: &mut String, line_number: usize, line: &str, meta: &CodeMeta, prefix: &str) {
+    let prefix = escape(prefix);
+    html.push_str(&format!("<span class=\"{prefix}line"));
+    if meta.highlight.contains(line_number) {
+        html.push_str(&format!(" {prefix}highlighted"));
+    }
+    if meta.focus.contains(line_number) {
+        html.push_str(&format!(" {prefix}focused"));
+    }
+    if meta.added.contains(line_number) {
+        html.push_str(&format!(" {prefix}diff-add"));
+    } else if meta.removed.contains(line_number) {
+        html.push_str(&format!(" {prefix}diff-remove"));
+    }
+    html.push_str(&format!("\" data-line=\"{line_number}\">"));
+    if let Some(start) = meta.line_numbers {
+        html.push_str(&format!(
+            "<span class=\"{prefix}line-number\" aria-hidden=\"true\">{}</span>",
+            start.saturating_add(line_number - 1)
+        ));
+    }
+    html.push_str(line);
+    html.push_str("</span>\n");
+}
+
+fn strip_newline(text: &str) -> &str {
+    text.trim_end_matches(['\n', '\r'])
+}
+
+fn scope_classes(scope: Scope, prefix: &str) -> String {
+    scope
+        .build_string()
+        .split('.')
+        .map(|atom| format!("{prefix}{atom}"))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+// Each line is returned with its own balanced spans: scopes that continue
+// over a line break are closed at the end of the line and reopened on the
+// next, so the nesting the stylesheet selects on is kept within every line.
+fn highlight_classes(
+    syntaxes: &SyntaxSet,
+    syntax: &SyntaxReference,
+    code: &str,
+    prefix: &str,
+) -> Vec<String> {
+    let mut parse_state = ParseState::new(syntax);
+    let mut stack = ScopeStack::new();
+    let mut lines = Vec::new();
+
+    let mut source_lines = LinesWithEndings::from(code);
+    for line in source_lines.by_ref() {
+        let Ok(ops) = parse_state.parse_line(line, syntaxes) else {
+            lines.push(escape(strip_newline(line)).to_string());
+            break;
+        };
+        let mut html = String::new();
+        // The scopes whose spans are open on this line.
+        let mut open: Vec<Scope> = Vec::new();
+        for (range, op) in ScopeRangeIterator::new(&ops, line) {
+            let _ = stack.apply(op);
+            let text = strip_newline(&line[range]);
+            if !text.is_empty() {
+                let scopes = stack.as_slice();
+                let common = open.iter().zip(scopes).take_while(|(a, b)| a == b).count();
+                for _ in common..open.len() {
+                    html.push_str("</span>");
+                }
+                open.truncate(common);
+                for scope in &scopes[common..] {
+                    html.push_str(&format!("<span class=\"{}\">", escape(&scope_classes(*scope, prefix))));
+                    open.push(*scope);
+                }
+                html.push_str(&escape(text).to_string());
+            }
+        }
+        for _ in &open {
+            html.push_str("</span>");
+        }
+        lines.push(html);
+    }
+    // A line that failed to parse leaves the rest unhighlighted.
+    lines.extend(source_lines.map(|line| escape(strip_newline(line)).to_string()));
+    lines
+}
+
+fn highlight_inline(
+    syntaxes: &SyntaxSet,
+    syntax: &SyntaxReference,
+    code: &str,
+    theme: &Theme,
+    background: Color,
+) -> Vec<String> {
+    let mut highlighter = HighlightLines::new(syntax, theme);
+
+    LinesWithEndings::from(code)
+        .map(|line| {
+            highlighter
+                .highlight_line(line, syntaxes)
+                .ok()
+                .and_then(|regions| {
+                    let regions: Vec<(Style, &str)> = regions
+                        .into_iter()
+                        .map(|(style, text)| (style, strip_newline(text)))
+                        .filter(|(_, text)| !text.is_empty())
+                        .collect();
+                    let mut line_html = String::new();
+                    append_highlighted_html_for_styled_line(
+                        &regions,
+                        IncludeBackground::IfDifferent(background),
+                        &mut line_html,
+                    )
+                    .ok()
+                    .map(|_| line_html)
+                })
+                .unwrap_or_else(|| escape(strip_newline(line)).to_string())
+        })
+        .collect()
+}
+
+// The foreground and background of a theme as `--shiki-{name}` variables.
+fn theme_variables(name: &str, theme: &Theme) -> String {
+    let foreground = theme.settings.foreground.unwrap_or(Color::BLACK);
+    let background = theme.settings.background.unwrap_or(Color::WHITE);
+    format!(
+        "--shiki-{name}:{};--shiki-{name}-bg:{};",
+        hex(foreground),
+        hex(background)
+    )
+}
+
+fn token_variables(name: &str, style: Style, out: &mut String) {
+    out.push_str(&format!("--shiki-{name}:{};", hex(style.foreground)));
+    if style.font_style.contains(FontStyle::ITALIC) {
+        out.push_str(&format!("--shiki-{name}-font-style:italic;"));
+    }
+    if style.font_style.contains(FontStyle::BOLD) {
+        out.push_str(&format!("--shiki-{name}-font-weight:bold;"));
+    }
+    if style.font_style.contains(FontStyle::UNDERLINE) {
+        out.push_str(&format!("--shiki-{name}-text-decoration:underline;"));
+    }
+}
+
+fn push_dual_token(html: &mut String, text: &str, styles: (Style, Style)) {
+    if text.trim().is_empty() {
+        html.push_str(text);
+        return;
+    }
+    html.push_str("<span style=\"");
+    token_variables("light", styles.0, html);
+    token_variables("dark", styles.1, html);
+    html.push_str("\">");
+    html.push_str(&escape(text).to_string());
+    html.push_str("</span>");
+}
+
+// Parses the code once and styles every token against both themes, merging
+// neighbouring tokens that look the same in each.
+fn highlight_dual(
+    syntaxes: &SyntaxSet,
+    syntax: &SyntaxReference,
+    code: &str,
+    light: &Theme,
+    dark: &Theme,
+) -> Vec<String> {
+    let light = Highlighter::new(light);
+    let dark = Highlighter::new(dark);
+    let mut parse_state = ParseState::new(syntax);
+    let mut stack = ScopeStack::new();
+    let mut lines = Vec::new();
+
+    let mut source_lines = LinesWithEndings::from(code);
+    for line in source_lines.by_ref() {
+        let Ok(ops) = parse_state.parse_line(line, syntaxes) else {
+            lines.push(escape(strip_newline(line)).to_string());
+            break;
+        };
+        let mut html = String::new();
+        let mut token = String::new();
+        let mut token_styles = None;
+        for (range, op) in ScopeRangeIterator::new(&ops, line) {
+            let _ = stack.apply(op);
+            let text = strip_newline(&line[range]);
+            if !text.is_empty() {
+                let styles = (
+                    light.style_for_stack(stack.as_slice()),
+                    dark.style_for_stack(stack.as_slice()),
+                );
+                if token_styles.is_some_and(|current| current != styles) {
+                    push_dual_token(&mut html, &token, token_styles.unwrap_or(styles));
+                    token.clear();
+                }
+                token.push_str(text);
+                token_styles = Some(styles);
+            }
+        }
+        if let Some(styles) = token_styles {
+            push_dual_token(&mut html, &token, styles);
+        }
+        lines.push(html);
+    }
+    // A line that failed to parse leaves the rest unhighlighted.
+    lines.extend(source_lines.map(|line| escape(strip_newline(line)).to_string()));
+    lines
+}
+
+/// The stylesheet for class-based highlighting with `theme`, using classes
+/// that start with `prefix`.
+pub fn theme_css(theme: &str, prefix: &str) -> AppResult<String> {
+    let assets = highlight_assets::current();
+    let theme = find_theme(&assets, theme).ok_or_else(|| AppError::UnknownTheme(theme.to_string()))?;
+    css_for_theme_with_class_style(theme, class_style(prefix))
+        .map_err(|e| AppError::Highlight(e.to_string()))
+}
+
+/// The stylesheet that applies the light or dark colors of `dual` output,
+/// switching on `dark_selector` or, by default, the user's color scheme.
+pub fn dual_theme_css(prefix: &str, dark_selector: Option<&str>) -> String {
+    let block = format!(".{prefix}dual");
+    let rules = |name: &str, scope: &str| {
+        format!(
+            "{scope}{block} {{\n  color: var(--shiki-{name});\n  background-color: var(--shiki-{name}-bg);\n}}\n\
+             {scope}{block} span {{\n  color: var(--shiki-{name});\n  \
+             font-style: var(--shiki-{name}-font-style, inherit);\n  \
+             font-weight: var(--shiki-{name}-font-weight, inherit);\n  \
+             text-decoration: var(--shiki-{name}-text-decoration, inherit);\n}}\n"
+        )
+    };
+
+    let mut css = rules("light", "");
+    match dark_selector {
+        Some(selector) => css.push_str(&rules("dark", &format!("{selector} "))),
+        None => {
+            css.push_str("@media (prefers-color-scheme: dark) {\n");
+            css.push_str(&rules("dark", ""));
+            css.push_str("}\n");
+        }
+    }
+    css
+}
@@ -0,0 +1,275 @@
+use crate::config::SlugFlags;
+use crate::constants::MAX_INCLUDED_BYTES;
+use crate::error::AppError;
+use crate::services::resolver::{normalize_relative, SourceResolver};
+use crate::types::Diagnostic;
+use crate::utils::line_index::LineIndex;
+use crate::utils::slug::slugify;
+use lazy_static::lazy_static;
+use regex::{Captures, Regex};
+use std::ops::Range;
+
+lazy_static! {
+    // `<!--@include: ./shared/install.md#setup{3,10}-->`
+    static ref INCLUDE_RE: Regex = Regex::new(
+        r"<!--\s*@include:\s*(?P<path>[^\s#{]+)(?:#(?P<section>[^\s{}]+?))?(?:\{(?P<start>\d*)(?P<comma>,)?(?P<end>\d*)\})?\s*-->"
+    )
+    .unwrap();
+    static ref HEADING_RE: Regex = Regex::new(
+        r"^ {0,3}(?P<level>#{1,6})(?:[ \t]+(?P<text>.*?))?(?:[ \t]+#+)?[ \t]*$"
+    )
+    .unwrap();
+    static ref HEADING_ID_RE: Regex = Regex::new(r"\s*\{#(?P<id>[^\s}]+)[^}]*\}\s*$").unwrap();
+}
+
+/// Replaces `<!--@include: path-->` comments with the markdown of the files
+/// they name, recursively. `#heading` keeps only the section under that
+/// heading and `{start,end}` a range of its lines. Includes that cannot be
+/// resolved, or that would add more than [`MAX_INCLUDED_BYTES`] in all, are
+/// left in place and reported at the include of the document they come from.
+/// Heading ids are derived with `slug`, as rendering derives them.
+pub fn expand_includes(
+    source: &str,
+    resolver: &dyn SourceResolver,
+    slug: &SlugFlags,
+) -> (String, Vec<Diagnostic>) {
+    let mut expander = Expander {
+        resolver,
+        slug,
+        stack: Vec::new(),
+        problems: Vec::new(),
+        included: 0,
+    };
+    let expanded = expander.expand(source, None, None);
+    let lines = LineIndex::new(source);
+    let diagnostics = expander
+        .problems
+        .iter()
+        .map(|(error, range)| Diagnostic::error(error, Some(lines.position(range.clone()).into())))
+        .collect();
+    (expanded, diagnostics)
+}
+
+struct Include {
+    path: String,
+    section: Option<String>,
+    start: Option<usize>,
+    end: Option<usize>,
+}
+
+impl Include {
+    fn parse(captures: &Captures) -> Self {
+        let number = |name| captures.name(name).and_then(|m| m.as_str().parse().ok());
+        let start = number("start");
+        Include {
+            path: captures["path"].to_string(),
+            section: captures.name("section").map(|m| m.as_str().to_string()),
+            start,
+            // `{3}` is a single line, `{3,}` runs to the end.
+            end: if captures.name("comma").is_some() { number("end") } else { start },
+        }
+    }
+}
+
+struct Expander<'r> {
+    resolver: &'r dyn SourceResolver,
+    slug: &'r SlugFlags,
+    // Paths of the files being expanded, outermost first.
+    stack: Vec<String>,
+    // Problems with the byte range of the document's include they occur in.
+    problems: Vec<(AppError, Range<usize>)>,
+    // Bytes of included markdown so far, counted once per include.
+    included: usize,
+}
+
+impl Expander<'_> {
+    // `file` is the path of the file `source` was read from, `None` for the
+    // document itself; `origin` is the range of the document's include.
+    fn expand(&mut self, source: &str, file: Option<&str>, origin: Option<Range<usize>>) -> String {
+        let mut output = String::with_capacity(source.len());
+        let mut fence = None;
+        let mut line_start = 0;
+
+        for line in source.split_inclusive('\n') {
+            let offset = line_start;
+            line_start += line.len();
+            // Includes inside code blocks are shown, not expanded.
+            let in_code = fence.is_some();
+            fence = next_fence(fence, line);
+            if in_code || fence.is_some() || !INCLUDE_RE.is_match(line) {
+                output.push_str(line);
+                continue;
+            }
+
+            let mut last = 0;
+            for captures in INCLUDE_RE.captures_iter(line) {
+                let Some(whole) = captures.get(0) else { continue };
+                output.push_str(&line[last..whole.start()]);
+                let range = origin
+                    .clone()
+                    .unwrap_or(offset + whole.start()..offset + whole.end());
+                match self.include(&Include::parse(&captures), file, range) {
+                    Some(text) => output.push_str(&text),
+                    None => output.push_str(whole.as_str()),
+                }
+                last = whole.end();
+            }
+            output.push_str(&line[last..]);
+        }
+        output
+    }
+
+    fn include(&mut self, include: &Include, parent: Option<&str>, range: Range<usize>) -> Option<String> {
+        let path = join_path(parent, &include.path);
+        if let Some(start) = self.stack.iter().position(|open| *open == path) {
+            let mut chain = self.stack[start..].to_vec();
+            chain.push(path);
+            self.problems.push((AppError::IncludeCycle(chain), range));
+            return None;
+        }
+
+        let contents = match self.resolver.read(&path) {
+            Ok(contents) => contents,
+            Err(e) => {
+                self.problems.push((e.for_include(), range));
+                return None;
+            }
+        };
+        let body = strip_frontmatter(&contents);
+        let selected = match &include.section {
+            Some(section) => match extract_section(body, section, self.slug) {
+                Some(text) => text,
+                None => {
+                    let section = section.clone();
+                    self.problems.push((AppError::SectionNotFound { section, path }, range));
+                    return None;
+                }
+            },
+            None => body.to_string(),
+        };
+        let selected = select_lines(&selected, include.start, include.end);
+        self.included += selected.len();
+        if self.included > MAX_INCLUDED_BYTES {
+            let limit = MAX_INCLUDED_BYTES;
+            self.problems.push((AppError::IncludeTooLarge { path, limit }, range));
+            return None;
+        }
+
+        self.stack.push(path.clone());
+        let expanded = self.expand(&selected, Some(&path), Some(range));
+        self.stack.pop();
+        Some(expanded.trim_end_matches(['\n', '\r']).to_string())
+    }
+}
+
+// Paths are relative to the file that includes them, except `@/` paths,
+// which stay relative to the root.
+fn join_path(parent: Option<&str>, path: &str) -> String {
+    if let Some(rest) = path.strip_prefix("@/") {
+        return format!("@/{}", normalize_relative(rest));
+    }
+    let Some(parent) = parent else {
+        return normalize_relative(path);
+    };
+    let (prefix, parent) = match parent.strip_prefix("@/") {
+        Some(rest) => ("@/", rest),
+        None => ("", parent),
+    };
+    let dir = parent.rsplit_once('/').map_or("", |(dir, _)| dir);
+    format!("{prefix}{}", normalize_relative(&format!("{dir}/{path}")))
+}
+
+// The fence a line leaves open: `current` if the line neither opens nor
+// closes one. A fence is closed by a run of its character at least as long.
+fn next_fence(current: Option<(char, usize)>, line: &str) -> Option<(char, usize)> {
+    let trimmed = line.trim_start_matches(' ');
+    if line.len() - trimmed.len() > 3 {
+        return current;
+    }
+    let Some(marker) = trimmed.chars().next().filter(|c| *c == '`' || *c == '~') else {
+        return current;
+    };
+    let run = trimmed.chars().take_while(|c| *c == marker).count();
+    if run < 3 {
+        return current;
+    }
+    match current {
+        Some((open, length)) if open == marker && run >= length && trimmed[run..].trim().is_empty() => None,
+        Some(open) => Some(open),
+        None => Some((marker, run)),
+    }
+}
+
+// Included files keep their own frontmatter out of the including document.
+fn strip_frontmatter(source: &str) -> &str {
+    for delimiter in ["---", "+++"] {
+        let Some(rest) = source
+            .strip_prefix(delimiter)
+            .and_then(|rest| rest.strip_prefix('\n').or_else(|| rest.strip_prefix("\r\n")))
+        else {
+            continue;
+        };
+        let mut offset = 0;
+        for line in rest.split_inclusive('\n') {
+            offset += line.len();
+            if line.trim_end() == delimiter {
+                return &rest[offset..];
+            }
+        }
+    }
+    source
+}
+
+// The lines from the heading whose id is `section` up to the next heading of
+// the same or a higher level. Ids are written as `{#id}` or derived from the
+// heading text with `slug`.
+fn extract_section(source: &str, section: &str, slug: &SlugFlags) -> Option<String> {
+    let mut output = String::new();
+    let mut level = None;
+    let mut fence = None;
+
+    for line in source.split_inclusive('\n') {
+        let in_code = fence.is_some();
+        fence = next_fence(fence, line);
+        let heading = (!in_code && fence.is_none())
+            .then(|| HEADING_RE.captures(line.trim_end_matches(['\n', '\r'])))
+            .flatten();
+
+        if let Some(heading) = heading {
+            let heading_level = heading["level"].len();
+            match level {
+                Some(open) if heading_level <= open => break,
+                Some(_) => {}
+                None => {
+                    let text = heading.name("text").map_or("", |m| m.as_str());
+                    let id = match HEADING_ID_RE.captures(text) {
+                        Some(custom) => custom["id"].to_string(),
+                        None => slugify(text, slug),
+                    };
+                    if id == section {
+                        level = Some(heading_level);
+                    }
+                }
+            }
+        }
+        if level.is_some() {
+            output.push_str(line);
+        }
+    }
+    level.map(|_| output)
+}
+
+// 1-based and inclusive; a missing bound is the first or last line.
+fn select_lines(source: &str, start: Option<usize>, end: Option<usize>) -> String {
+    if start.is_none() && end.is_none() {
+        return source.to_string();
+    }
+    let start = start.unwrap_or(1).max(1);
+    let end = end.unwrap_or(usize::MAX);
+    source
+        .split_inclusive('\n')
+        .enumerate()
+        .filter(|(index, _)| (start..=end).contains(&(index + 1)))
+        .map(|(_, line)| line)
+        .collect()
+}
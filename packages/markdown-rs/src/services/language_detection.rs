@@ -0,0 +1,172 @@
+use lazy_static::lazy_static;
+use regex::Regex;
+use syntect::parsing::{SyntaxReference, SyntaxSet};
+
+// Below this score a guess is no better than plain text.
+const MIN_SCORE: usize = 3;
+// Caps how much a single pattern can add, so one repetitive line does not
+// outweigh everything else.
+const MAX_MATCHES_PER_PATTERN: usize = 3;
+
+lazy_static! {
+    // Weighted patterns per language token. Weights favour constructs that
+    // are distinctive for a language over ones several languages share.
+    static ref LANGUAGE_PATTERNS: Vec<(&'static str, Vec<(Regex, usize)>)> = vec![
+        ("rust", patterns(&[
+            (r"\bfn\s+\w+\s*(<[^>]*>)?\s*\(", 3),
+            (r"\blet\s+mut\b", 3),
+            (r"\bimpl\b.*\{", 3),
+            (r"\bpub(\(crate\))?\s+(fn|struct|enum|mod|use)\b", 3),
+            (r"\buse\s+\w+(::\w+)+", 2),
+            (r"\w+!\(", 1),
+            (r"&mut\s|->\s*\w+", 1),
+        ])),
+        ("python", patterns(&[
+            (r"(?m)^\s*def\s+\w+\s*\(.*\)\s*(->\s*[\w\[\], ]+)?:\s*$", 4),
+            (r"(?m)^\s*(from\s+[\w.]+\s+)?import\s+\w+", 2),
+            (r"(?m)^\s*(elif|except|class\s+\w+.*):", 3),
+            (r"\bself\.\w+", 2),
+            (r"\bprint\(", 1),
+            (r"\bNone\b|\bTrue\b|\bFalse\b", 1),
+        ])),
+        ("javascript", patterns(&[
+            (r"\b(const|let|var)\s+\w+\s*=", 2),
+            (r"\bfunction\s*\w*\s*\(", 3),
+            (r"=>\s*[{(]?", 2),
+            (r"\bconsole\.\w+\(", 3),
+            (r"\b(require\(|module\.exports|export\s+(default|const|function))", 3),
+            (r"===|!==", 2),
+        ])),
+        ("go", patterns(&[
+            (r"(?m)^package\s+\w+", 4),
+            (r"\bfunc\s+(\(\w+\s+\*?\w+\)\s*)?\w+\(", 4),
+            (r"\w+\s*:=", 2),
+            (r"\bfmt\.\w+\(", 3),
+        ])),
+        ("java", patterns(&[
+            (r"\bpublic\s+(static\s+)?(class|void|interface)\b", 4),
+            (r"\bSystem\.out\.print", 4),
+            (r"\bprivate\s+(final\s+)?\w+\s+\w+", 2),
+        ])),
+        ("c", patterns(&[
+            (r"(?m)^#include\s*[<\x22]", 4),
+            (r"\bint\s+main\s*\(", 3),
+            (r"\bprintf\s*\(", 2),
+        ])),
+        ("bash", patterns(&[
+            (r"(?m)^\$\s+\w+", 3),
+            (r"(?m)^\s*(echo|export|cd|sudo|apt(-get)?|npm|cargo|git)\s", 2),
+            (r"\|\s*(grep|sed|awk|xargs)\b", 3),
+            (r"(?m)^\s*(if|while)\s+\[\[?\s", 3),
+            (r"\$\{?\w+\}?", 1),
+        ])),
+        ("sql", patterns(&[
+            (r"(?i)\bselect\b[\s\S]+\bfrom\b", 4),
+            (r"(?i)\b(insert\s+into|create\s+table|update\s+\w+\s+set|delete\s+from)\b", 4),
+            (r"(?i)\bwhere\b", 1),
+        ])),
+        ("html", patterns(&[
+            (r"(?i)<!doctype\s+html", 5),
+            (r"(?i)</(div|span|p|a|html|body|head|ul|li|section|template)>", 3),
+            (r#"<\w+(\s+[\w-]+="[^"]*")+\s*/?>"#, 2),
+        ])),
+        ("css", patterns(&[
+            (r"(?m)^\s*[.#]?[\w-]+(\s*[,>+~]?\s*[.#:]?[\w-]+)*\s*\{\s*$", 3),
+            (r"(?m)^\s*[\w-]+\s*:\s*[^;{}]+;\s*$", 2),
+            (r"@media\b|@import\b", 3),
+        ])),
+        ("yaml", patterns(&[
+            (r"(?m)^[\w-]+:\s*$", 2),
+            (r"(?m)^[\w-]+:\s+\S", 1),
+            (r"(?m)^\s*-\s+[\w-]+:\s", 2),
+            (r"(?m)^---\s*$", 2),
+        ])),
+    ];
+}
+
+fn patterns(patterns: &[(&str, usize)]) -> Vec<(Regex, usize)> {
+    patterns
+        .iter()
+        .map(|(pattern, weight)| (Regex::new(pattern).unwrap(), *weight))
+        .collect()
+}
+
+// Interpreters named on shebang lines that differ from the syntax token.
+fn interpreter_language(interpreter: &str) -> &str {
+    match interpreter {
+        "sh" | "zsh" | "dash" | "ksh" => "bash",
+        "node" | "deno" | "bun" => "javascript",
+        "python2" | "python3" => "python",
+        other => other,
+    }
+}
+
+/// Guesses the language of an unlabeled code block, returning the syntax and
+/// a token naming it. Shebang and first-line matches are trusted; otherwise
+/// the language whose patterns score highest wins if the score is high
+/// enough and unambiguous.
+pub fn detect_language<'a>(
+    code: &str,
+    syntaxes: &'a SyntaxSet,
+) -> Option<(&'a SyntaxReference, String)> {
+    let first_line = code.lines().next().unwrap_or_default();
+
+    if let Some(shebang) = first_line.strip_prefix("#!") {
+        let mut words = shebang.split_whitespace();
+        let program = words.next().unwrap_or_default().rsplit('/').next().unwrap_or_default();
+        let interpreter = if program == "env" {
+            words.find(|word| !word.starts_with('-')).unwrap_or_default()
+        } else {
+            program
+        };
+        let interpreter = interpreter.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.');
+        let token = interpreter_language(interpreter);
+        if let Some(syntax) = syntaxes.find_syntax_by_token(token) {
+            return Some((syntax, token.to_string()));
+        }
+    }
+
+    if let Some(syntax) = syntaxes
+        .find_syntax_by_first_line(first_line)
+        .filter(|syntax| syntax.name != "Plain Text")
+    {
+        let token = syntax
+            .file_extensions
+            .first()
+            .cloned()
+            .unwrap_or_else(|| syntax.name.to_lowercase());
+        return Some((syntax, token));
+    }
+
+    let trimmed = code.trim();
+    if (trimmed.starts_with('{') || trimmed.starts_with('['))
+        && serde_json::from_str::<serde_json::Value>(trimmed).is_ok()
+    {
+        return syntaxes.find_syntax_by_token("json").map(|syntax| (syntax, "json".to_string()));
+    }
+
+    let mut scores: Vec<(&str, usize)> = LANGUAGE_PATTERNS
+        .iter()
+        .map(|(token, patterns)| {
+            let score = patterns
+                .iter()
+                .map(|(pattern, weight)| {
+                    pattern.find_iter(code).take(MAX_MATCHES_PER_PATTERN).count() * weight
+                })
+                .sum();
+            (*token, score)
+        })
+        .collect();
+    scores.sort_by_key(|(_, score)| std::cmp::Reverse(*score));
+
+    match scores.as_slice() {
+        [(token, best), rest @ ..]
+            if *best >= MIN_SCORE && rest.first().is_none_or(|(_, second)| second < best) =>
+        {
+            syntaxes
+                .find_syntax_by_token(token)
+                .map(|syntax| (syntax, token.to_string()))
+        }
+        _ => None,
+    }
+}
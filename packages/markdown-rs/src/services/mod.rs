@@ -1 +1,7 @@
+pub mod frontmatter;
+pub mod highlight_assets;
+pub mod highlighter;
+pub mod includes;
+pub mod language_detection;
+pub mod resolver;
 pub mod sanitizer;
@@ -0,0 +1,116 @@
+use crate::error::{AppError, AppResult};
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+/// Reads the files that snippet imports and includes refer to. Implement it
+/// to serve files from somewhere other than the file system.
+pub trait SourceResolver: Send + Sync + Debug {
+    /// Returns the contents of `path` as written in the document.
+    fn read(&self, path: &str) -> AppResult<String>;
+}
+
+/// Resolves `@/` paths against `root` and other paths against `base_dir`,
+/// refusing anything that would leave `root`.
+#[derive(Debug, Clone)]
+pub struct FsResolver {
+    pub root: PathBuf,
+    pub base_dir: PathBuf,
+}
+
+impl FsResolver {
+    pub fn new(root: PathBuf, base_dir: Option<PathBuf>) -> Self {
+        let base_dir = base_dir.unwrap_or_else(|| root.clone());
+        Self { root, base_dir }
+    }
+
+    fn resolve(&self, path: &str) -> AppResult<PathBuf> {
+        let joined = match path.strip_prefix("@/") {
+            Some(rest) => self.root.join(rest),
+            None => self.base_dir.join(path),
+        };
+        let resolved = normalize(&joined);
+        if !resolved.starts_with(normalize(&self.root)) {
+            return Err(AppError::OutsideRoot(path.to_string()));
+        }
+        Ok(resolved)
+    }
+}
+
+impl SourceResolver for FsResolver {
+    fn read(&self, path: &str) -> AppResult<String> {
+        let resolved = self.resolve(path)?;
+        fs::read_to_string(&resolved).map_err(|e| AppError::Resolve {
+            path: path.to_string(),
+            reason: e.to_string(),
+        })
+    }
+}
+
+/// Serves files from memory, keyed by their path relative to the root. `@/`
+/// and other relative paths both resolve against the root.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryResolver {
+    files: HashMap<String, String>,
+}
+
+impl MemoryResolver {
+    pub fn new(files: HashMap<String, String>) -> Self {
+        let files = files
+            .into_iter()
+            .map(|(path, contents)| (normalize_relative(path.trim_start_matches('/')), contents))
+            .collect();
+        Self { files }
+    }
+}
+
+impl SourceResolver for MemoryResolver {
+    fn read(&self, path: &str) -> AppResult<String> {
+        let key = normalize_relative(path.strip_prefix("@/").unwrap_or(path));
+        if key == ".." || key.starts_with("../") {
+            return Err(AppError::OutsideRoot(path.to_string()));
+        }
+        self.files.get(&key).cloned().ok_or_else(|| AppError::Resolve {
+            path: path.to_string(),
+            reason: "no such file".to_string(),
+        })
+    }
+}
+
+/// Resolves `.` and `..` in a `/`-separated relative path without touching
+/// the file system. `..` that would climb above the start is kept.
+pub fn normalize_relative(path: &str) -> String {
+    let mut segments: Vec<&str> = Vec::new();
+    for segment in path.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." if segments.last().is_some_and(|last| *last != "..") => {
+                segments.pop();
+            }
+            other => segments.push(other),
+        }
+    }
+    segments.join("/")
+}
+
+// Resolves `.` and `..` lexically, so paths to files that do not exist yet
+// can still be checked against the root.
+fn normalize(path: &Path) -> PathBuf {
+    let path = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        std::env::current_dir().unwrap_or_default().join(path)
+    };
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            other => normalized.push(other),
+        }
+    }
+    normalized
+}
@@ -1,44 +1,363 @@
+use crate::components::plugins::directive::{YOUTUBE_EMBED_URL, YOUTUBE_FRAME_STYLE, YOUTUBE_WRAPPER_STYLE};
+use crate::config::{RenderFlags, SanitizePolicy};
 use crate::error::AppResult;
 use lazy_static::lazy_static;
 use ammonia::Builder;
-use std::collections::{HashMap, HashSet};
+use regex::Regex;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::{Mutex, OnceLock};
+
+// Classes emitted by the built-in plugins that survive the default policy.
+const PLUGIN_CLASSES: &[(&str, &[&str])] = &[
+    (
+        "div",
+        &[
+            "admonition", "note", "info", "tip", "warning", "danger", "caution", "important",
+            "details",
+        ],
+    ),
+    ("span", &["math-inline", "math-block", "spoiler"]),
+    ("details", &["spoiler"]),
+    ("p", &["admonition-title"]),
+];
+
+// Elements whose ids and classes come from the markdown: `{#id .class}`
+// blocks and generated heading ids. Anything else on them is dropped, so raw
+// HTML cannot place arbitrary ids on the page.
+const MARKUP_TAGS: &[&str] = &["p", "h1", "h2", "h3", "h4", "h5", "h6"];
+
+// Elements that carry syntax highlighting classes or inline styles.
+const HIGHLIGHT_TAGS: &[&str] = &["code", "div", "pre", "span"];
+
+// Elements the default tags leave out, allowed while the syntax that emits
+// them is enabled: task list checkboxes and `::youtube` embeds.
+const TASK_LIST_TAGS: &[&str] = &["input"];
+const EMBED_TAGS: &[&str] = &["iframe"];
+
+// Inline styles of the `::youtube` embed, by element.
+const EMBED_STYLES: &[(&str, &str)] = &[("div", YOUTUBE_WRAPPER_STYLE), ("iframe", YOUTUBE_FRAME_STYLE)];
 
 lazy_static! {
-    static ref AMMONIA_BUILDER: Builder<'static> = {
-        let mut builder = Builder::new();
-        builder.link_rel(Some("noopener noreferrer"));
-
-        let mut tag_attributes: HashMap<&'static str, HashSet<&'static str>> = builder.clone_tag_attributes();
-        tag_attributes
-            .entry("ul")
-            .or_default()
-            .insert("class");
-        tag_attributes
-            .entry("ol")
-            .or_default()
-            .insert("class");
-        builder.tag_attributes(tag_attributes);
-
-        builder
+    // The exact declarations syntect writes for inline highlighting.
+    static ref HIGHLIGHT_STYLE_RE: Regex = Regex::new(
+        r"^(background-color:#[0-9a-f]{6}([0-9a-f]{2})?;)?(text-decoration:underline;)?(font-weight:bold;)?(font-style:italic;)?(color:#[0-9a-f]{6}([0-9a-f]{2})?;)?$"
+    )
+    .unwrap();
+    // The CSS variables written by dual-theme highlighting.
+    static ref DUAL_THEME_STYLE_RE: Regex = Regex::new(
+        r"^(--shiki-(light|dark)(-bg|-font-style|-font-weight|-text-decoration)?:(#[0-9a-f]{6}([0-9a-f]{2})?|italic|bold|underline);)+$"
+    )
+    .unwrap();
+}
+
+type ClassAllowlist<'a> = HashMap<&'a str, HashSet<&'a str>>;
+
+/// The ids and classes the markdown put on paragraphs and headings, which the
+/// sanitizer lets through on those elements.
+#[derive(Debug, Default)]
+pub struct DocumentMarkup {
+    pub ids: HashSet<String>,
+    pub classes: HashSet<String>,
+}
+
+thread_local! {
+    // The markup of the document being cleaned. Builders are shared between
+    // documents, so their attribute filter reads it from here.
+    static DOCUMENT_MARKUP: RefCell<DocumentMarkup> = RefCell::new(DocumentMarkup::default());
+}
+
+// What a builder depends on besides the policy.
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct BuilderKey {
+    class_prefix: String,
+    task_lists: bool,
+    embeds: bool,
+    // A canonical form of the policy, if there is one.
+    policy: Option<String>,
+}
+
+impl BuilderKey {
+    fn plugin_tags(&self) -> Vec<&'static str> {
+        let mut tags = Vec::new();
+        if self.task_lists {
+            tags.extend(TASK_LIST_TAGS);
+        }
+        if self.embeds {
+            tags.extend(EMBED_TAGS);
+        }
+        tags
+    }
+}
+
+// Builders are built once per configuration and leaked, together with the
+// policy they borrow from, so memory stays bounded by the number of
+// configurations in use.
+static BUILDERS: OnceLock<Mutex<HashMap<BuilderKey, &'static Builder<'static>>>> = OnceLock::new();
+
+fn cached_builder(key: BuilderKey, policy: Option<&SanitizePolicy>) -> &'static Builder<'static> {
+    let mut builders = BUILDERS
+        .get_or_init(|| Mutex::new(HashMap::new()))
+        .lock()
+        .unwrap_or_else(|e| e.into_inner());
+    if let Some(builder) = builders.get(&key) {
+        return builder;
+    }
+    let builder = match policy {
+        Some(policy) => {
+            let policy: &'static SanitizePolicy = Box::leak(Box::new(policy.clone()));
+            policy_builder(policy, &key)
+        }
+        None => default_builder(&key),
+    };
+    let builder: &'static Builder<'static> = Box::leak(Box::new(builder));
+    builders.insert(key, builder);
+    builder
+}
+
+// Orders the maps of `policy`, so equal policies give equal keys.
+fn policy_key(policy: &SanitizePolicy) -> String {
+    let sorted = |map: &Option<HashMap<String, Vec<String>>>| {
+        format!("{:?}", map.as_ref().map(|map| map.iter().collect::<BTreeMap<_, _>>()))
     };
+    format!(
+        "{:?}|{}|{}|{:?}|{:?}",
+        policy.allowed_tags,
+        sorted(&policy.tag_attributes),
+        sorted(&policy.allowed_classes),
+        policy.url_schemes,
+        policy.link_rel
+    )
+}
+
+fn plugin_classes() -> ClassAllowlist<'static> {
+    PLUGIN_CLASSES
+        .iter()
+        .map(|(tag, classes)| (*tag, classes.iter().copied().collect()))
+        .collect()
+}
+
+fn base_tag_attributes<'a>() -> HashMap<&'a str, HashSet<&'a str>> {
+    let mut tag_attributes: HashMap<&'a str, HashSet<&'a str>> = Builder::new().clone_tag_attributes();
+    tag_attributes
+        .entry("ul")
+        .or_default()
+        .insert("class");
+    tag_attributes
+        .entry("ol")
+        .or_default()
+        .insert("class");
+    // Ids and classes from the markdown, and admonition titles.
+    for tag in MARKUP_TAGS {
+        tag_attributes.entry(tag).or_default().extend(["id", "class"]);
+    }
+    // Task list checkboxes. The sanitizer forces `type`, so no other kind of
+    // input gets through.
+    tag_attributes.entry("input").or_default().extend(["checked", "disabled"]);
+    tag_attributes
+        .entry("iframe")
+        .or_default()
+        .extend(["src", "style", "allowfullscreen", "frameborder"]);
+    for tag in HIGHLIGHT_TAGS {
+        tag_attributes.entry(tag).or_default().insert("style");
+    }
+    // Line wrappers and line numbers of highlighted code.
+    tag_attributes
+        .entry("span")
+        .or_default()
+        .extend(["data-line", "aria-hidden"]);
+    tag_attributes
+}
+
+fn default_builder<'a>(key: &BuilderKey) -> Builder<'a> {
+    let mut builder = Builder::new();
+    builder.link_rel(Some("noopener noreferrer"));
+    builder.add_tags(key.plugin_tags());
+    force_checkbox_inputs(&mut builder);
+
+    let mut tag_attributes = base_tag_attributes();
+    filter_classes(&mut builder, &mut tag_attributes, plugin_classes(), &HashSet::new(), &key.class_prefix);
+    builder.tag_attributes(tag_attributes);
+
+    builder
+}
+
+// Any `<input>` let through, by default or by a policy, is a checkbox.
+fn force_checkbox_inputs(builder: &mut Builder) {
+    builder.set_tag_attribute_value("input", "type", "checkbox");
+}
+
+// Keeps only allowlisted classes on the listed tags. Unlike ammonia's own
+// `allowed_classes`, the attribute is dropped entirely when no class survives.
+// Highlighting classes (those starting with `class_prefix`) and syntect's
+// inline styles are kept on the elements the highlighter emits. Ids and
+// classes on `MARKUP_TAGS` must also come from the document's markup, unless
+// the tag is in `policy_tags`, whose attributes the policy sets itself.
+fn filter_classes<'a>(
+    builder: &mut Builder<'a>,
+    tag_attributes: &mut HashMap<&'a str, HashSet<&'a str>>,
+    allowed_classes: ClassAllowlist<'a>,
+    policy_tags: &HashSet<String>,
+    class_prefix: &str,
+) {
+    let mut owned: HashMap<String, HashSet<String>> = HashMap::new();
+    for (tag, classes) in allowed_classes {
+        tag_attributes.entry(tag).or_default().insert("class");
+        owned.insert(tag.to_string(), classes.into_iter().map(str::to_string).collect());
+    }
+    for tag in HIGHLIGHT_TAGS {
+        tag_attributes.entry(tag).or_default().insert("class");
+        owned.entry(tag.to_string()).or_default();
+    }
+    let allowed_classes = owned;
+    let class_prefix = class_prefix.to_string();
+    let policy_tags = policy_tags.clone();
+
+    builder.attribute_filter(move |element, attribute, value| {
+        let from_markup = MARKUP_TAGS.contains(&element) && !policy_tags.contains(element);
+        if from_markup && attribute == "id" {
+            let allowed = DOCUMENT_MARKUP.with(|markup| markup.borrow().ids.contains(value));
+            return allowed.then(|| value.into());
+        }
+        if attribute == "style" {
+            let allowed = (HIGHLIGHT_TAGS.contains(&element)
+                && (HIGHLIGHT_STYLE_RE.is_match(value) || DUAL_THEME_STYLE_RE.is_match(value)))
+                || EMBED_STYLES.contains(&(element, value));
+            return allowed.then(|| value.into());
+        }
+        if element == "iframe" && attribute == "src" {
+            return value.starts_with(YOUTUBE_EMBED_URL).then(|| value.into());
+        }
+        if attribute != "class" {
+            return Some(value.into());
+        }
+        let allowed = allowed_classes.get(element);
+        if allowed.is_none() && !from_markup {
+            return Some(value.into());
+        }
+        let highlight_tag = HIGHLIGHT_TAGS.contains(&element);
+        let classes: Vec<&str> = value
+            .split_ascii_whitespace()
+            .filter(|class| {
+                allowed.is_some_and(|allowed| allowed.contains(*class))
+                    || (from_markup && DOCUMENT_MARKUP.with(|markup| markup.borrow().classes.contains(*class)))
+                    || (highlight_tag && class.starts_with(class_prefix.as_str()))
+                    || (element == "code" && class.starts_with("language-"))
+            })
+            .collect();
+        if classes.is_empty() {
+            None
+        } else {
+            Some(classes.join(" ").into())
+        }
+    });
+}
+
+// Applies `policy` on top of the default builder. Settings that ammonia
+// rejects in combination (e.g. a `rel` attribute together with `link_rel`)
+// are resolved in favour of the policy.
+fn policy_builder<'a>(policy: &'a SanitizePolicy, key: &BuilderKey) -> Builder<'a> {
+    let mut builder = Builder::new();
+    builder.link_rel(Some("noopener noreferrer"));
+
+    if let Some(tags) = &policy.allowed_tags {
+        let tags: HashSet<&str> = tags.iter().map(String::as_str).collect();
+        let clean_content: HashSet<&str> = builder
+            .clone_clean_content_tags()
+            .into_iter()
+            .filter(|tag| !tags.contains(tag))
+            .collect();
+        builder.clean_content_tags(clean_content);
+        builder.tags(tags);
+    } else {
+        builder.add_tags(key.plugin_tags());
+    }
+    force_checkbox_inputs(&mut builder);
+
+    let mut tag_attributes = base_tag_attributes();
+    let mut policy_tags = HashSet::new();
+    if let Some(overrides) = &policy.tag_attributes {
+        for (tag, attributes) in overrides {
+            tag_attributes.insert(tag.as_str(), attributes.iter().map(String::as_str).collect());
+            policy_tags.insert(tag.clone());
+        }
+    }
+
+    if let Some(schemes) = &policy.url_schemes {
+        builder.url_schemes(schemes.iter().map(String::as_str).collect());
+    }
+
+    if let Some(rel) = &policy.link_rel {
+        builder.link_rel(if rel.is_empty() { None } else { Some(rel.as_str()) });
+    }
+    if policy.link_rel.as_deref() != Some("") {
+        if let Some(attributes) = tag_attributes.get_mut("a") {
+            attributes.remove("rel");
+        }
+    }
+
+    let allowed_classes: ClassAllowlist = match &policy.allowed_classes {
+        Some(classes) => classes
+            .iter()
+            .map(|(tag, classes)| (tag.as_str(), classes.iter().map(String::as_str).collect()))
+            .collect(),
+        None => plugin_classes()
+            .into_iter()
+            .filter(|(tag, _)| {
+                // An explicit `class` attribute in the policy keeps every class.
+                !policy
+                    .tag_attributes
+                    .as_ref()
+                    .and_then(|overrides| overrides.get(*tag))
+                    .is_some_and(|attributes| attributes.iter().any(|a| a == "class"))
+            })
+            .collect(),
+    };
+    filter_classes(&mut builder, &mut tag_attributes, allowed_classes, &policy_tags, &key.class_prefix);
+
+    builder.tag_attributes(tag_attributes);
+    builder
 }
 
 pub trait SanitizerService {
     fn clean(&self, html: &str) -> AppResult<String>;
 }
 
-pub struct AmmoniaSanitizer;
+pub struct AmmoniaSanitizer<'a> {
+    policy: Option<&'a SanitizePolicy>,
+    key: BuilderKey,
+}
+
+impl<'a> AmmoniaSanitizer<'a> {
+    pub fn new(flags: &'a RenderFlags) -> Self {
+        let policy = flags.sanitize_policy.as_ref();
+        Self {
+            policy,
+            key: BuilderKey {
+                class_prefix: flags.highlight.class_prefix.clone(),
+                task_lists: flags.gfm,
+                embeds: flags.directives,
+                policy: policy.map(policy_key),
+            },
+        }
+    }
+}
 
-impl SanitizerService for AmmoniaSanitizer {
+impl SanitizerService for AmmoniaSanitizer<'_> {
     fn clean(&self, html: &str) -> AppResult<String> {
-        Ok(AMMONIA_BUILDER.clean(html).to_string())
+        Ok(cached_builder(self.key.clone(), self.policy).clean(html).to_string())
     }
 }
 
-pub fn sanitize(html: String, should_sanitize: bool) -> String {
-    if !should_sanitize {
-        return html;
+/// Cleans `html` if `flags` ask for it. Ids and classes on paragraphs and
+/// headings are kept only if they are in `markup`.
+pub fn sanitize(html: String, flags: &RenderFlags, markup: DocumentMarkup) -> AppResult<String> {
+    if !flags.sanitize {
+        return Ok(html);
     }
-    let sanitizer = AmmoniaSanitizer;
-    sanitizer.clean(&html).unwrap_or(html)
+    let sanitizer = AmmoniaSanitizer::new(flags);
+    DOCUMENT_MARKUP.with(|current| *current.borrow_mut() = markup);
+    let cleaned = sanitizer.clean(&html);
+    DOCUMENT_MARKUP.with(|current| current.take());
+    cleaned
 }
@@ -1,3 +1,5 @@
+use napi_derive::napi;
+use crate::error::AppError;
 use serde::{Deserialize, Serialize};
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
@@ -8,43 +10,331 @@ pub enum Alignment {
     Right,
 }
 
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy, Default)]
+pub enum LinkType {
+    #[default]
+    Inline,
+    Reference,
+    ReferenceUnknown,
+    Collapsed,
+    CollapsedUnknown,
+    Shortcut,
+    ShortcutUnknown,
+    Autolink,
+    Email,
+    WikiLink { has_pothole: bool },
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+pub enum BlockQuoteKind {
+    Note,
+    Tip,
+    Important,
+    Warning,
+    Caution,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+pub enum MetadataBlockKind {
+    YamlStyle,
+    PlusesStyle,
+}
+
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Default)]
 pub enum NodeType {
     // Block-level
     #[default]
     Document,
-    BlockQuote,
+    BlockQuote {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        kind: Option<BlockQuoteKind>,
+    },
     List { start: Option<u64> },
     Item,
+    TaskListMarker { checked: bool },
     FootnoteDefinition { label: String },
+    DefinitionList,
+    DefinitionListTitle,
+    DefinitionListDefinition,
     Table(Vec<Alignment>),
     TableHead,
     TableRow,
     TableCell,
-    Heading(u32),
-    CodeBlock { lang: Option<String> },
+    Heading {
+        level: u32,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        id: Option<String>,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        classes: Vec<String>,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        attrs: Vec<(String, Option<String>)>,
+    },
+    CodeBlock {
+        lang: Option<String>,
+        /// The rest of the fence info string after the language.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        meta: Option<String>,
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        indented: bool,
+    },
     Paragraph,
     Rule,
+    HtmlBlock,
     Html,
+    MetadataBlock { kind: MetadataBlockKind },
 
     // Inline-level
     Text,
     Strong,
     Emph,
     Strikethrough,
-    Link { dest_url: String, title: String },
-    Image { dest_url: String, title: String },
+    Superscript,
+    Subscript,
+    Link {
+        #[serde(default)]
+        link_type: LinkType,
+        dest_url: String,
+        #[serde(default)]
+        title: String,
+        #[serde(default, skip_serializing_if = "String::is_empty")]
+        id: String,
+    },
+    Image {
+        #[serde(default)]
+        link_type: LinkType,
+        dest_url: String,
+        #[serde(default)]
+        title: String,
+        #[serde(default, skip_serializing_if = "String::is_empty")]
+        id: String,
+    },
     Code,
+    InlineHtml,
+    InlineMath,
+    DisplayMath,
     SoftBreak,
     HardBreak,
     FootnoteReference { label: String },
 }
 
+/// A location in the source: 1-based `line` and `column`, 0-based `offset`.
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub struct Point {
+    pub line: usize,
+    pub column: usize,
+    pub offset: usize,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub struct Position {
+    pub start: Point,
+    pub end: Point,
+}
+
 #[derive(Serialize, Deserialize, Debug, Default, PartialEq, Clone)]
 pub struct Node {
     pub r#type: NodeType,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub content: Option<String>,
-    #[serde(skip_serializing_if = "Vec::is_empty")]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub children: Vec<Node>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub position: Option<Position>,
+}
+
+#[napi(object)]
+#[derive(Debug, Clone)]
+pub struct HeadingInfo {
+    pub level: u32,
+    pub text: String,
+    pub slug: String,
+}
+
+/// A heading in the table of contents with the headings nested below it.
+#[napi(object)]
+#[derive(Debug, Clone)]
+pub struct TocEntry {
+    pub level: u32,
+    pub text: String,
+    pub slug: String,
+    pub children: Vec<TocEntry>,
+}
+
+#[napi(object)]
+#[derive(Debug, Clone)]
+pub struct LinkInfo {
+    pub url: String,
+    pub title: String,
+    pub text: String,
+}
+
+#[napi(object)]
+#[derive(Debug, Clone)]
+pub struct ImageInfo {
+    pub url: String,
+    pub title: String,
+    pub alt: String,
+}
+
+/// Everything `renderDocument` collects while rendering a page.
+#[napi(object)]
+pub struct RenderedDocument {
+    pub html: String,
+    pub frontmatter: Option<serde_json::Value>,
+    pub headings: Vec<HeadingInfo>,
+    pub toc: Vec<TocEntry>,
+    pub links: Vec<LinkInfo>,
+    pub images: Vec<ImageInfo>,
+    #[napi(js_name = "wordCount")]
+    pub word_count: u32,
+    /// Estimated reading time in whole minutes.
+    #[napi(js_name = "readingTime")]
+    pub reading_time: u32,
+    /// Plain text of the first paragraph.
+    pub excerpt: Option<String>,
+    /// Problems found in the markdown, see [`Diagnostic`].
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+/// A [`Point`] for JavaScript callers, which have no `usize`.
+#[napi(object)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SourcePoint {
+    pub line: u32,
+    pub column: u32,
+    pub offset: u32,
+}
+
+impl From<Point> for SourcePoint {
+    fn from(point: Point) -> Self {
+        Self {
+            line: point.line as u32,
+            column: point.column as u32,
+            offset: point.offset as u32,
+        }
+    }
+}
+
+/// A [`Position`] for JavaScript callers.
+#[napi(object)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SourceRange {
+    pub start: SourcePoint,
+    pub end: SourcePoint,
+}
+
+impl From<Position> for SourceRange {
+    fn from(position: Position) -> Self {
+        Self {
+            start: position.start.into(),
+            end: position.end.into(),
+        }
+    }
+}
+
+/// A problem found while processing a document. Processing carries on past
+/// it, so the output is still usable.
+#[napi(object)]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    /// `error` or `warning`.
+    pub severity: String,
+    pub message: String,
+    /// Identifies the kind of problem, e.g. `include-not-found`.
+    pub code: String,
+    /// Where in the markdown the problem is. Include failures point at the
+    /// include in the document; with `includes` enabled, other problems
+    /// point into the markdown after expansion.
+    pub range: Option<SourceRange>,
+    /// An edit that resolves the problem.
+    pub fix: Option<Fix>,
+}
+
+/// A machine-applicable edit: `range` is replaced with `text`.
+#[napi(object)]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fix {
+    pub range: SourceRange,
+    pub text: String,
+}
+
+/// The result of `lint`.
+#[napi(object)]
+#[derive(Debug, Clone)]
+pub struct LintResult {
+    /// Problems in source order. `code` names the rule.
+    pub diagnostics: Vec<Diagnostic>,
+    /// The markdown with every fix applied.
+    pub fixed: String,
+    #[napi(js_name = "errorCount")]
+    pub error_count: u32,
+    #[napi(js_name = "warningCount")]
+    pub warning_count: u32,
+}
+
+impl Diagnostic {
+    pub fn error(error: &AppError, range: Option<SourceRange>) -> Self {
+        Self::new("error", error, range)
+    }
+
+    pub fn warning(error: &AppError, range: Option<SourceRange>) -> Self {
+        Self::new("warning", error, range)
+    }
+
+    fn new(severity: &str, error: &AppError, range: Option<SourceRange>) -> Self {
+        Self {
+            severity: severity.to_string(),
+            message: error.to_string(),
+            code: error.code().to_string(),
+            range,
+            fix: None,
+        }
+    }
+}
+
+/// A document passed to `renderBatch`.
+#[napi(object)]
+#[derive(Debug, Clone)]
+pub struct BatchInput {
+    /// Returned with the result, e.g. the page's path.
+    pub id: String,
+    pub markdown: String,
+}
+
+/// The outcome of rendering one document of a batch: its HTML, or the error
+/// that stopped it.
+#[napi(object)]
+#[derive(Debug, Clone)]
+pub struct BatchResult {
+    pub id: String,
+    pub html: Option<String>,
+    pub error: Option<String>,
+    /// Problems found in the markdown.
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+/// The result of `expandIncludes`.
+#[napi(object)]
+#[derive(Debug, Clone)]
+pub struct ExpandedMarkdown {
+    pub markdown: String,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+/// The result of `registerHighlightAssets`.
+#[napi(object)]
+#[derive(Debug, Clone)]
+pub struct HighlightAssetsInfo {
+    /// Hash of the registered files, naming the cached dumps.
+    pub hash: String,
+    /// Syntaxes available, including the built-in ones.
+    pub syntaxes: u32,
+    /// Themes available, including the built-in ones.
+    pub themes: u32,
+    /// Whether the sets were loaded from the dump cache.
+    pub cached: bool,
+    /// Why the compiled sets could not be written to the cache, if they
+    /// could not.
+    #[napi(js_name = "cacheError")]
+    pub cache_error: Option<String>,
 }
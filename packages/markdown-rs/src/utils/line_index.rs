@@ -0,0 +1,70 @@
+use crate::types::{Point, Position};
+use std::ops::Range;
+
+/// Maps byte offsets in a source string to line/column points.
+///
+/// Columns and offsets are counted in UTF-16 code units so they can be used
+/// directly as JavaScript string indices.
+pub struct LineIndex<'a> {
+    input: &'a str,
+    // Byte offset and UTF-16 offset of the start of every line.
+    line_starts: Vec<(usize, usize)>,
+}
+
+impl<'a> LineIndex<'a> {
+    pub fn new(input: &'a str) -> Self {
+        let mut line_starts = vec![(0, 0)];
+        let mut utf16_offset = 0;
+
+        for (byte_offset, c) in input.char_indices() {
+            utf16_offset += c.len_utf16();
+            if c == '\n' {
+                line_starts.push((byte_offset + 1, utf16_offset));
+            }
+        }
+
+        Self { input, line_starts }
+    }
+
+    pub fn point(&self, byte_offset: usize) -> Point {
+        let byte_offset = byte_offset.min(self.input.len());
+        let line = self
+            .line_starts
+            .partition_point(|(start, _)| *start <= byte_offset)
+            - 1;
+        let (line_start, line_start_utf16) = self.line_starts[line];
+        let column: usize = self.input[line_start..byte_offset]
+            .chars()
+            .map(char::len_utf16)
+            .sum();
+
+        Point {
+            line: line + 1,
+            column: column + 1,
+            offset: line_start_utf16 + column,
+        }
+    }
+
+    /// The byte offset of `point`, undoing [`LineIndex::point`].
+    pub fn byte_offset(&self, point: Point) -> usize {
+        let Some(&(line_start, _)) = self.line_starts.get(point.line.saturating_sub(1)) else {
+            return self.input.len();
+        };
+        let column = point.column.saturating_sub(1);
+        let mut utf16_column = 0;
+        for (index, c) in self.input[line_start..].char_indices() {
+            if utf16_column >= column {
+                return line_start + index;
+            }
+            utf16_column += c.len_utf16();
+        }
+        self.input.len()
+    }
+
+    pub fn position(&self, range: Range<usize>) -> Position {
+        Position {
+            start: self.point(range.start),
+            end: self.point(range.end),
+        }
+    }
+}
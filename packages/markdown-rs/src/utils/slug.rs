@@ -0,0 +1,72 @@
+use crate::config::{SlugFlags, SlugStyle};
+use lazy_static::lazy_static;
+use regex::Regex;
+use std::collections::HashMap;
+
+lazy_static! {
+    // Everything except letters, marks, numbers, connector punctuation (`_`),
+    // `-` and spaces, which is what GitHub strips from anchors.
+    static ref GITHUB_STRIP_RE: Regex = Regex::new(r"[^\p{L}\p{M}\p{N}\p{Pc} -]").unwrap();
+}
+
+/// Turns heading text into a fragment id, without de-duplication.
+pub fn slugify(text: &str, flags: &SlugFlags) -> String {
+    let text = if flags.lowercase {
+        text.to_lowercase()
+    } else {
+        text.to_string()
+    };
+
+    let slug: String = match flags.style {
+        SlugStyle::Github => GITHUB_STRIP_RE.replace_all(&text, "").into_owned(),
+        SlugStyle::Simple => text,
+    };
+
+    format!("{}{}", flags.prefix, slug.replace(' ', &flags.separator))
+}
+
+/// Hands out unique heading ids for one document. Repeated slugs get a
+/// numeric suffix (`intro`, `intro-1`, `intro-2`) the way GitHub does.
+pub struct Slugger {
+    flags: SlugFlags,
+    // Times each id has been handed out, keyed by the unsuffixed id.
+    occurrences: HashMap<String, usize>,
+}
+
+impl Slugger {
+    pub fn new(flags: SlugFlags) -> Self {
+        Self {
+            flags,
+            occurrences: HashMap::new(),
+        }
+    }
+
+    /// Returns a unique id for a heading with the given text.
+    pub fn slug(&mut self, text: &str) -> String {
+        let base = slugify(text, &self.flags);
+        self.unique(base)
+    }
+
+    /// Records an explicit `{#id}` so generated ids never collide with it.
+    /// The id is kept as written unless an earlier heading already has it, in
+    /// which case it gets a numeric suffix like a repeated slug.
+    pub fn reserve(&mut self, id: &str) -> String {
+        self.unique(id.to_string())
+    }
+
+    /// Every id handed out so far.
+    pub fn ids(&self) -> impl Iterator<Item = &str> {
+        self.occurrences.keys().map(String::as_str)
+    }
+
+    fn unique(&mut self, base: String) -> String {
+        let mut slug = base.clone();
+        while self.occurrences.contains_key(&slug) {
+            let count = self.occurrences.entry(base.clone()).or_insert(0);
+            *count += 1;
+            slug = format!("{}{}{}", base, self.flags.separator, count);
+        }
+        self.occurrences.insert(slug.clone(), 0);
+        slug
+    }
+}